@@ -1,90 +1,673 @@
-use crate::core::notification::NotificationChannel;
+use crate::application::config_loader::{self, ConfigFormat};
+use crate::core::error::RedTokenError;
+use crate::core::injection::{BackupPolicy, WritePolicy};
+use crate::core::notification::{self, ChannelEntry, RoutingRule, TemplateConfig};
+use crate::core::outbox::RetryPolicy;
+use crate::infrastructure::notification::validate_channel;
 use anyhow::Result;
+use log::{info, warn};
 use serde::{Deserialize, Serialize};
 use std::fs;
+use std::net::IpAddr;
 use std::path::{Path, PathBuf};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+// Every section falls back to defaults so older or partial configs keep loading
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct AppConfig {
     pub storage: StorageConfig,
     pub web: WebConfig,
     pub notification: NotificationConfig,
     pub token: TokenConfig,
+    pub injection: WritePolicy,
+    pub history: HistoryConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct StorageConfig {
-    pub db_path: PathBuf,
-    pub backup_enabled: bool,
+    pub backend: StorageBackend,
+    pub db_path: PathBuf,      // Token store of the json backend
+    pub sqlite_path: PathBuf,  // Single database holding everything for the sqlite backend
+    pub outbox_path: PathBuf, // Only used by the json backend, sqlite keeps the outbox in sqlite_path
+    pub catalog_path: PathBuf, // Backup catalog, json backend only like outbox_path
+    pub backup: BackupPolicy,
+    // Older configs; folded into `backup` when loaded
+    #[serde(default, skip_serializing)]
+    pub backup_enabled: Option<bool>,
+    #[serde(default, skip_serializing)]
     pub backup_path: Option<PathBuf>,
 }
 
+// Where tokens and queued alerts are persisted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    Memory, // Nothing survives a restart
+    #[default]
+    Json,
+    Sqlite,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct WebConfig {
     pub port: u16,
     pub host: String,
     pub enable_ssl: bool,
     pub cert_path: Option<PathBuf>,
     pub key_path: Option<PathBuf>,
+    pub public_url: Option<String>, // How decoys reach this server, e.g. behind a proxy
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct NotificationConfig {
-    pub channels: Vec<NotificationChannel>,
+    pub channels: Vec<ChannelEntry>,
     pub rate_limit: Option<u32>, // Notifications per hour
+    pub retry: RetryPolicy,
+    pub routes: Vec<RoutingRule>,
+    pub default_channels: Vec<String>, // Used when no route matches; "*" is every enabled channel
+    pub templates: TemplateConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct TokenConfig {
     pub token_length: usize,
     pub token_prefix: Option<String>,
     pub include_symbols: bool,
 }
 
-impl Default for AppConfig {
+// Fake commands for history decoys
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HistoryConfig {
+    pub command_templates: Vec<PathBuf>, // Added to the built-in library
+}
+
+const CONFIG_FILE: &str = "config.json";
+const CONFIG_FILES: [&str; 4] = ["config.json", "config.yaml", "config.yml", "config.toml"];
+const SYSTEM_CONFIG_DIR: &str = "/etc/redtoken";
+const SYSTEM_DATA_DIR: &str = "/var/lib/redtoken";
+
+// Where the config file lives, and where a freshly created config keeps its data
+#[derive(Debug, Clone)]
+pub struct ConfigLocation {
+    pub path: PathBuf,
+    pub data_dir: PathBuf,
+    pub explicit: bool,
+}
+
+fn xdg_dir(var: &str, fallback: &str) -> Option<PathBuf> {
+    std::env::var_os(var)
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(fallback)))
+}
+
+impl ConfigLocation {
+    // --config/REDTOKEN_CONFIG, then --system, then XDG. The working directory is only used
+    // when no home directory is known.
+    pub fn resolve(explicit: Option<PathBuf>, system: bool) -> Self {
+        if let Some(path) = explicit {
+            let data_dir = config_dir(&path);
+            return Self {
+                path,
+                data_dir,
+                explicit: true,
+            };
+        }
+
+        if system {
+            return Self {
+                path: find_config(Path::new(SYSTEM_CONFIG_DIR)),
+                data_dir: PathBuf::from(SYSTEM_DATA_DIR),
+                explicit: false,
+            };
+        }
+
+        let legacy = find_config(Path::new("."));
+        let xdg =
+            xdg_dir("XDG_CONFIG_HOME", ".config").zip(xdg_dir("XDG_DATA_HOME", ".local/share"));
+
+        match xdg {
+            Some((config_home, data_home)) => {
+                let path = find_config(&config_home.join("redtoken"));
+                // Older versions read ./config.json; point at it until an XDG config exists
+                if legacy.exists() && !path.exists() {
+                    warn!(
+                        "Ignoring {:?}: the working directory is no longer searched for a config. \
+                         Move it and its data files to {:?}, or pass --config {:?}",
+                        legacy,
+                        config_dir(&path),
+                        legacy
+                    );
+                }
+                Self {
+                    path,
+                    data_dir: data_home.join("redtoken"),
+                    explicit: false,
+                }
+            }
+            None => Self {
+                path: legacy,
+                data_dir: PathBuf::from("."),
+                explicit: false,
+            },
+        }
+    }
+
+    // Base for relative paths inside the config
+    pub fn base_dir(&self) -> PathBuf {
+        config_dir(&self.path)
+    }
+}
+
+// First existing config.{json,yaml,yml,toml} in the directory, config.json otherwise
+fn find_config(dir: &Path) -> PathBuf {
+    CONFIG_FILES
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.exists())
+        .unwrap_or_else(|| dir.join(CONFIG_FILE))
+}
+
+fn config_dir(path: &Path) -> PathBuf {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+// Report lines carry their own context, so drop the error type's prefix
+fn describe(error: &anyhow::Error) -> String {
+    match error.downcast_ref::<RedTokenError>() {
+        Some(RedTokenError::ConfigError(message)) => message.clone(),
+        _ => error.to_string(),
+    }
+}
+
+fn annotate(path: &Path, error: anyhow::Error) -> anyhow::Error {
+    RedTokenError::ConfigError(format!("{}: {}", path.display(), describe(&error))).into()
+}
+
+fn resolve_path(base: &Path, path: &Path) -> PathBuf {
+    if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        base.join(path)
+    }
+}
+
+impl StorageConfig {
+    // Copy with relative paths anchored at the config file's directory
+    pub fn resolved(&self, base: &Path) -> Self {
+        let mut backup = self.backup.clone();
+        backup.directory = resolve_path(base, &backup.directory);
+
+        Self {
+            backend: self.backend,
+            db_path: resolve_path(base, &self.db_path),
+            sqlite_path: resolve_path(base, &self.sqlite_path),
+            outbox_path: resolve_path(base, &self.outbox_path),
+            catalog_path: resolve_path(base, &self.catalog_path),
+            backup,
+            backup_enabled: None,
+            backup_path: None,
+        }
+    }
+
+    fn migrate_legacy(&mut self) {
+        if let Some(enabled) = self.backup_enabled.take() {
+            self.backup.enabled = enabled;
+        }
+        if let Some(path) = self.backup_path.take() {
+            self.backup.directory = path;
+        }
+    }
+}
+
+impl WebConfig {
+    // Certificate and key to serve with, when TLS is enabled
+    pub fn tls_paths(&self, base: &Path) -> Option<(PathBuf, PathBuf)> {
+        if !self.enable_ssl {
+            return None;
+        }
+        let cert = self.cert_path.as_deref()?;
+        let key = self.key_path.as_deref()?;
+        Some((resolve_path(base, cert), resolve_path(base, key)))
+    }
+
+    // Base URL decoys that call home are pointed at. None when only a loopback or
+    // wildcard bind address is known, since a decoy on another machine cannot reach it.
+    pub fn canary_url(&self) -> Option<String> {
+        if let Some(url) = &self.public_url {
+            return Some(url.trim_end_matches('/').to_string());
+        }
+        let local = self.host.eq_ignore_ascii_case("localhost")
+            || self
+                .host
+                .trim_matches(['[', ']'])
+                .parse::<IpAddr>()
+                .is_ok_and(|ip| ip.is_loopback() || ip.is_unspecified());
+        if local {
+            return None;
+        }
+        Some(format!(
+            "{}://{}:{}",
+            if self.enable_ssl { "https" } else { "http" },
+            self.host,
+            self.port
+        ))
+    }
+}
+
+impl Default for StorageConfig {
     fn default() -> Self {
         Self {
-            storage: StorageConfig {
-                db_path: PathBuf::from("tokens.db"),
-                backup_enabled: true,
-                backup_path: Some(PathBuf::from("backups")),
-            },
-            web: WebConfig {
-                port: 8080,
-                host: "127.0.0.1".to_string(),
-                enable_ssl: false,
-                cert_path: None,
-                key_path: None,
-            },
-            notification: NotificationConfig {
-                channels: Vec::new(),
-                rate_limit: Some(10),
-            },
-            token: TokenConfig {
-                token_length: 32,
-                token_prefix: Some("RT_".to_string()),
-                include_symbols: true,
-            },
+            backend: StorageBackend::default(),
+            db_path: PathBuf::from("tokens.db"),
+            sqlite_path: PathBuf::from("redtoken.sqlite"),
+            outbox_path: PathBuf::from("outbox.db"),
+            catalog_path: PathBuf::from("backups.db"),
+            backup: BackupPolicy::default(),
+            backup_enabled: None,
+            backup_path: None,
+        }
+    }
+}
+
+impl Default for WebConfig {
+    fn default() -> Self {
+        Self {
+            port: 8080,
+            host: "127.0.0.1".to_string(),
+            enable_ssl: false,
+            cert_path: None,
+            key_path: None,
+            public_url: None,
         }
     }
 }
 
+impl Default for NotificationConfig {
+    fn default() -> Self {
+        Self {
+            channels: Vec::new(),
+            rate_limit: Some(10),
+            retry: RetryPolicy::default(),
+            routes: Vec::new(),
+            default_channels: notification::default_channels(),
+            templates: TemplateConfig::default(),
+        }
+    }
+}
+
+impl Default for TokenConfig {
+    fn default() -> Self {
+        Self {
+            token_length: 32,
+            token_prefix: Some("RT_".to_string()),
+            include_symbols: true,
+        }
+    }
+}
+
+// Result of `config validate`: errors make the config unusable, warnings do not
+#[derive(Debug, Default)]
+pub struct ConfigReport {
+    pub errors: Vec<String>,
+    pub warnings: Vec<String>,
+    pub overrides: Vec<String>,
+}
+
 impl AppConfig {
+    // Effective config: file contents plus REDTOKEN__ overrides and resolved secret references
     pub fn load(path: &Path) -> Result<Self> {
-        let config_str = fs::read_to_string(path)?;
-        let config = serde_json::from_str(&config_str)?;
+        let mut tree = Self::read_tree(path)?;
+
+        let overrides = config_loader::apply_env_overrides(&mut tree, std::env::vars());
+        if !overrides.is_empty() {
+            info!(
+                "Config overridden from environment: {}",
+                overrides.join(", ")
+            );
+        }
+        config_loader::expand_references(&mut tree, &config_dir(path))
+            .map_err(|e| annotate(path, e))?;
+
+        let (mut config, unknown) = Self::deserialize(tree).map_err(|e| annotate(path, e))?;
+        for key in unknown {
+            warn!("Unknown config key '{}' in {:?} is ignored", key, path);
+        }
+        config.storage.migrate_legacy();
+        config.name_unnamed_channels();
+        Ok(config)
+    }
+
+    // File contents only, for commands that edit and save the config.
+    // Secret references stay unresolved so saving never writes plaintext secrets.
+    pub fn load_raw(path: &Path) -> Result<Self> {
+        let tree = Self::read_tree(path)?;
+        let (mut config, _) =
+            config_loader::deserialize::<Self>(tree).map_err(|e| annotate(path, e))?;
+        config.storage.migrate_legacy();
+        config.name_unnamed_channels();
         Ok(config)
     }
 
+    // Channel entries are flattened, which hides their unknown keys from serde_ignored,
+    // so those are found by comparing each entry with the entry written back out
+    fn deserialize(tree: serde_json::Value) -> Result<(Self, Vec<String>)> {
+        let raw_channels = tree.pointer("/notification/channels").cloned();
+        let (config, mut unknown) = config_loader::deserialize::<Self>(tree)?;
+
+        if let Some(serde_json::Value::Array(raw_channels)) = raw_channels {
+            for (index, (raw, entry)) in raw_channels
+                .iter()
+                .zip(&config.notification.channels)
+                .enumerate()
+            {
+                let known = serde_json::to_value(entry)?;
+                let path = format!("notification.channels[{}]", index);
+                unknown.extend(config_loader::unknown_keys(raw, &known, &path));
+            }
+        }
+
+        Ok((config, unknown))
+    }
+
+    fn read_tree(path: &Path) -> Result<serde_json::Value> {
+        let content = fs::read_to_string(path).map_err(|e| RedTokenError::FileReadError {
+            path: path.to_path_buf(),
+            source: e,
+        })?;
+        ConfigFormat::from_path(path)
+            .parse(&content)
+            .map_err(|e| annotate(path, e))
+    }
+
     pub fn save(&self, path: &Path) -> Result<()> {
-        let config_str = serde_json::to_string_pretty(self)?;
+        let config_str = ConfigFormat::from_path(path).serialize(self)?;
         fs::write(path, config_str)?;
         Ok(())
     }
 
-    pub fn init_default(path: &Path) -> Result<Self> {
-        let config = Self::default();
-        config.save(path)?;
+    // Everything `load` does, but collecting every problem instead of stopping at the first
+    pub fn check(path: &Path) -> ConfigReport {
+        let mut report = ConfigReport::default();
+
+        let mut tree = match Self::read_tree(path) {
+            Ok(tree) => tree,
+            Err(e) => {
+                report.errors.push(describe(&e));
+                return report;
+            }
+        };
+
+        report.overrides = config_loader::apply_env_overrides(&mut tree, std::env::vars());
+        if let Err(e) = config_loader::expand_references(&mut tree, &config_dir(path)) {
+            report.errors.push(describe(&e));
+        }
+
+        match Self::deserialize(tree) {
+            Ok((config, unknown)) => {
+                report.warnings.extend(
+                    unknown
+                        .into_iter()
+                        .map(|key| format!("{}: unknown key, ignored", key)),
+                );
+                report.errors.extend(config.validate(&config_dir(path)));
+            }
+            Err(e) => report.errors.push(describe(&e)),
+        }
+
+        report
+    }
+
+    // Semantic checks that serde cannot express, as "path: problem" messages
+    pub fn validate(&self, base_dir: &Path) -> Vec<String> {
+        let mut errors = Vec::new();
+
+        if self.web.port == 0 {
+            errors.push("web.port: must be between 1 and 65535".to_string());
+        }
+        if self.web.enable_ssl {
+            if self.web.cert_path.is_none() {
+                errors.push("web.cert_path: required when web.enable_ssl is true".to_string());
+            }
+            if self.web.key_path.is_none() {
+                errors.push("web.key_path: required when web.enable_ssl is true".to_string());
+            }
+        }
+        if let Some(url) = &self.web.public_url {
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                errors.push("web.public_url: must start with http:// or https://".to_string());
+            }
+        }
+        if self.storage.backup.retention_count == Some(0) {
+            errors.push("storage.backup.retention_count: must be at least 1".to_string());
+        }
+        if self.token.token_length == 0 {
+            errors.push("token.token_length: must be greater than 0".to_string());
+        }
+
+        let retry = &self.notification.retry;
+        if retry.max_attempts == 0 {
+            errors.push("notification.retry.max_attempts: must be at least 1".to_string());
+        }
+        if !(0.0..=1.0).contains(&retry.jitter) {
+            errors.push("notification.retry.jitter: must be between 0.0 and 1.0".to_string());
+        }
+
+        let channels = &self.notification.channels;
+        for (index, entry) in channels.iter().enumerate() {
+            if channels[..index].iter().any(|c| c.name == entry.name) {
+                errors.push(format!(
+                    "notification.channels[{}].name: duplicate channel name '{}'",
+                    index, entry.name
+                ));
+            }
+            if let Err(e) = validate_channel(&entry.channel) {
+                errors.push(format!(
+                    "notification.channels[{}]: {}",
+                    index,
+                    describe(&e.into())
+                ));
+            }
+        }
+
+        for (index, rule) in self.notification.routes.iter().enumerate() {
+            let path = format!("notification.routes[{}]", index);
+            for selector in &rule.channels {
+                if !channels.iter().any(|c| c.matches_selector(selector)) {
+                    errors.push(format!(
+                        "{}.channels: '{}' matches no configured channel name or kind",
+                        path, selector
+                    ));
+                }
+            }
+            for (escalation, channel) in rule.escalate.iter().enumerate() {
+                if let Err(e) = validate_channel(channel) {
+                    errors.push(format!(
+                        "{}.escalate[{}]: {}",
+                        path,
+                        escalation,
+                        describe(&e.into())
+                    ));
+                }
+            }
+            if let Some(pattern) = &rule.path_glob {
+                if let Err(e) = glob::Pattern::new(pattern) {
+                    errors.push(format!("{}.path_glob: {}", path, e));
+                }
+            }
+            if let Some(hours) = &rule.hours {
+                if hours.start > 23 || hours.end > 23 {
+                    errors.push(format!("{}.hours: hours must be between 0 and 23", path));
+                }
+            }
+        }
+
+        // "*" stays valid with no channels configured yet
+        for selector in &self.notification.default_channels {
+            if selector != "*" && !channels.iter().any(|c| c.matches_selector(selector)) {
+                errors.push(format!(
+                    "notification.default_channels: '{}' matches no configured channel name or kind",
+                    selector
+                ));
+            }
+        }
+
+        for (index, path) in self.history.command_templates.iter().enumerate() {
+            if !resolve_path(base_dir, path).is_file() {
+                errors.push(format!(
+                    "history.command_templates[{}]: {:?} does not exist",
+                    index, path
+                ));
+            }
+        }
+
+        for (index, template) in self.notification.templates.overrides.iter().enumerate() {
+            if !resolve_path(base_dir, &template.path).is_file() {
+                errors.push(format!(
+                    "notification.templates.overrides[{}].path: {:?} does not exist",
+                    index, template.path
+                ));
+            }
+        }
+
+        errors
+    }
+
+    // Load the config, creating a default one only at the implicit locations
+    pub fn load_or_init(location: &ConfigLocation) -> Result<Self> {
+        if location.path.exists() {
+            return Self::load(&location.path);
+        }
+        Self::init(location)
+    }
+
+    // Like load_or_init, but without resolving secrets or environment overrides
+    pub fn load_raw_or_init(location: &ConfigLocation) -> Result<Self> {
+        if location.path.exists() {
+            return Self::load_raw(&location.path);
+        }
+        Self::init(location)
+    }
+
+    fn init(location: &ConfigLocation) -> Result<Self> {
+        if location.explicit {
+            return Err(RedTokenError::ConfigError(format!(
+                "Config file {:?} does not exist",
+                location.path
+            ))
+            .into());
+        }
+
+        let mut config = Self::default();
+        let base = location.base_dir();
+        // Data lives outside the config directory unless both are the same place
+        if location.data_dir != base {
+            config.storage.db_path = location.data_dir.join("tokens.db");
+            config.storage.sqlite_path = location.data_dir.join("redtoken.sqlite");
+            config.storage.outbox_path = location.data_dir.join("outbox.db");
+            config.storage.catalog_path = location.data_dir.join("backups.db");
+            config.storage.backup.directory = location.data_dir.join("backups");
+        }
+
+        fs::create_dir_all(&base)?;
+        fs::create_dir_all(&location.data_dir)?;
+        config.save(&location.path)?;
+        warn!("Created default configuration at {:?}", location.path);
         Ok(config)
     }
+
+    // Settings handed to the notification service, with template paths made absolute
+    pub fn notification_config(&self, base_dir: &Path) -> notification::NotificationConfig {
+        let mut templates = self.notification.templates.clone();
+        for template in &mut templates.overrides {
+            template.path = resolve_path(base_dir, &template.path);
+        }
+
+        notification::NotificationConfig {
+            channels: self.notification.channels.clone(),
+            rate_limit: self.notification.rate_limit,
+            routes: self.notification.routes.clone(),
+            default_channels: self.notification.default_channels.clone(),
+            templates,
+        }
+    }
+
+    // Command template files with relative paths anchored at the config file's directory
+    pub fn command_templates(&self, base_dir: &Path) -> Vec<PathBuf> {
+        self.history
+            .command_templates
+            .iter()
+            .map(|path| resolve_path(base_dir, path))
+            .collect()
+    }
+
+    // Configs written before channels had names get one derived from the kind
+    fn name_unnamed_channels(&mut self) {
+        for index in 0..self.notification.channels.len() {
+            if self.notification.channels[index].name.is_empty() {
+                let kind = self.notification.channels[index].channel.kind();
+                self.notification.channels[index].name = self.unique_channel_name(kind);
+            }
+        }
+    }
+
+    // First free name of the form kind, kind-2, kind-3, ...
+    pub fn unique_channel_name(&self, base: &str) -> String {
+        let taken = |name: &str| self.notification.channels.iter().any(|c| c.name == name);
+        if !taken(base) {
+            return base.to_string();
+        }
+        (2..)
+            .map(|n| format!("{}-{}", base, n))
+            .find(|name| !taken(name))
+            .expect("unbounded range always yields a name")
+    }
+
+    pub fn find_channel_mut(&mut self, name: &str) -> Result<&mut ChannelEntry> {
+        self.notification
+            .channels
+            .iter_mut()
+            .find(|c| c.name == name)
+            .ok_or_else(|| {
+                RedTokenError::ConfigError(format!("No channel named '{}'", name)).into()
+            })
+    }
+
+    pub fn add_channel(&mut self, entry: ChannelEntry) -> Result<()> {
+        if entry.name.trim().is_empty() {
+            return Err(RedTokenError::ConfigError("Channel name must not be empty".into()).into());
+        }
+        if self
+            .notification
+            .channels
+            .iter()
+            .any(|c| c.name == entry.name)
+        {
+            return Err(RedTokenError::ConfigError(format!(
+                "A channel named '{}' already exists",
+                entry.name
+            ))
+            .into());
+        }
+        self.notification.channels.push(entry);
+        Ok(())
+    }
+
+    pub fn remove_channel(&mut self, name: &str) -> Result<ChannelEntry> {
+        let index = self
+            .notification
+            .channels
+            .iter()
+            .position(|c| c.name == name)
+            .ok_or_else(|| RedTokenError::ConfigError(format!("No channel named '{}'", name)))?;
+        Ok(self.notification.channels.remove(index))
+    }
 }
@@ -0,0 +1,474 @@
+use crate::core::error::RedTokenError;
+use anyhow::Result;
+use regex::{Captures, Regex};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{Map, Value};
+use std::path::Path;
+
+const ENV_PREFIX: &str = "REDTOKEN__";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfigFormat {
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl ConfigFormat {
+    // Chosen by file extension, JSON when there is none
+    pub fn from_path(path: &Path) -> Self {
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_ascii_lowercase());
+
+        match extension.as_deref() {
+            Some("yaml") | Some("yml") => ConfigFormat::Yaml,
+            Some("toml") => ConfigFormat::Toml,
+            _ => ConfigFormat::Json,
+        }
+    }
+
+    // Parse into a JSON tree so overrides and references work the same for every format
+    pub fn parse(self, content: &str) -> Result<Value> {
+        let parsed = match self {
+            ConfigFormat::Json => serde_json::from_str(content).map_err(|e| e.to_string()),
+            ConfigFormat::Yaml => serde_yaml::from_str(content).map_err(|e| e.to_string()),
+            ConfigFormat::Toml => toml::from_str::<toml::Value>(content)
+                .map_err(|e| e.to_string())
+                .and_then(|value| serde_json::to_value(value).map_err(|e| e.to_string())),
+        };
+
+        parsed.map_err(|e| RedTokenError::ConfigError(format!("Parse error: {}", e)).into())
+    }
+
+    pub fn serialize<T: Serialize>(self, value: &T) -> Result<String> {
+        let content = match self {
+            ConfigFormat::Json => serde_json::to_string_pretty(value)?,
+            ConfigFormat::Yaml => serde_yaml::to_string(value)?,
+            // TOML has no null, so drop unset options before serializing
+            ConfigFormat::Toml => {
+                let mut tree = serde_json::to_value(value)?;
+                strip_nulls(&mut tree);
+                toml::to_string_pretty(&tree)?
+            }
+        };
+        Ok(content)
+    }
+}
+
+fn strip_nulls(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            map.retain(|_, v| !v.is_null());
+            map.values_mut().for_each(strip_nulls);
+        }
+        Value::Array(items) => items.iter_mut().for_each(strip_nulls),
+        _ => {}
+    }
+}
+
+// REDTOKEN__WEB__PORT=9000 sets web.port; values are parsed as JSON when possible.
+// Returns the config paths that were overridden.
+pub fn apply_env_overrides<I>(tree: &mut Value, vars: I) -> Vec<String>
+where
+    I: IntoIterator<Item = (String, String)>,
+{
+    let mut applied = Vec::new();
+
+    for (key, raw) in vars {
+        let Some(path) = key.strip_prefix(ENV_PREFIX) else {
+            continue;
+        };
+        let segments: Vec<String> = path
+            .split("__")
+            .filter(|segment| !segment.is_empty())
+            .map(|segment| segment.to_ascii_lowercase())
+            .collect();
+        if segments.is_empty() {
+            continue;
+        }
+
+        let value = serde_json::from_str(&raw).unwrap_or(Value::String(raw));
+        set_path(tree, &segments, value);
+        applied.push(segments.iter().fold(String::new(), |path, segment| {
+            match segment.parse::<usize>() {
+                Ok(index) => format!("{}[{}]", path, index),
+                Err(_) => join_path(&path, segment),
+            }
+        }));
+    }
+
+    applied
+}
+
+fn set_path(tree: &mut Value, segments: &[String], value: Value) {
+    let (first, rest) = match segments.split_first() {
+        Some(split) => split,
+        None => {
+            *tree = value;
+            return;
+        }
+    };
+
+    // Numeric segments index into existing arrays, e.g. NOTIFICATION__CHANNELS__0__ENABLED
+    if let (Value::Array(items), Ok(index)) = (&mut *tree, first.parse::<usize>()) {
+        if let Some(item) = items.get_mut(index) {
+            set_path(item, rest, value);
+            return;
+        }
+    }
+
+    if !tree.is_object() {
+        *tree = Value::Object(Map::new());
+    }
+    if let Value::Object(map) = tree {
+        // Keys written in other cases (e.g. channel variants) are matched case-insensitively
+        let key = map
+            .keys()
+            .find(|key| key.eq_ignore_ascii_case(first))
+            .cloned()
+            .unwrap_or_else(|| first.clone());
+        set_path(map.entry(key).or_insert(Value::Null), rest, value);
+    }
+}
+
+// Replace ${env:VAR} and ${file:/path} inside string values.
+// Relative file paths are read from the config file's directory.
+pub fn expand_references(tree: &mut Value, base_dir: &Path) -> Result<()> {
+    let pattern = Regex::new(r"\$\{(env|file):([^}]+)\}").expect("valid reference pattern");
+    expand_value(tree, "", &pattern, base_dir)
+}
+
+fn expand_value(value: &mut Value, path: &str, pattern: &Regex, base_dir: &Path) -> Result<()> {
+    match value {
+        Value::String(text) if pattern.is_match(text) => {
+            let mut failure = None;
+            let expanded = pattern.replace_all(text, |caps: &Captures| {
+                match resolve_reference(&caps[1], caps[2].trim(), base_dir) {
+                    Ok(resolved) => resolved,
+                    Err(e) => {
+                        failure.get_or_insert(e);
+                        String::new()
+                    }
+                }
+            });
+
+            if let Some(e) = failure {
+                return Err(
+                    RedTokenError::ConfigError(format!("{}: {}", display_path(path), e)).into(),
+                );
+            }
+            *text = expanded.into_owned();
+        }
+        Value::Object(map) => {
+            for (key, child) in map.iter_mut() {
+                expand_value(child, &join_path(path, key), pattern, base_dir)?;
+            }
+        }
+        Value::Array(items) => {
+            for (index, child) in items.iter_mut().enumerate() {
+                expand_value(child, &format!("{}[{}]", path, index), pattern, base_dir)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn resolve_reference(kind: &str, target: &str, base_dir: &Path) -> Result<String, String> {
+    match kind {
+        "env" => {
+            std::env::var(target).map_err(|_| format!("environment variable {} is not set", target))
+        }
+        _ => {
+            let path = base_dir.join(target);
+            std::fs::read_to_string(&path)
+                .map(|content| content.trim_end_matches(['\r', '\n']).to_string())
+                .map_err(|e| format!("cannot read secret file {:?}: {}", path, e))
+        }
+    }
+}
+
+fn join_path(parent: &str, key: &str) -> String {
+    if parent.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", parent, key)
+    }
+}
+
+fn display_path(path: &str) -> &str {
+    if path.is_empty() {
+        "(root)"
+    } else {
+        path
+    }
+}
+
+// Deserialize with the failing field's path in the error, collecting keys serde ignored
+pub fn deserialize<T: DeserializeOwned>(tree: Value) -> Result<(T, Vec<String>)> {
+    let mut unknown = Vec::new();
+    let mut record = |path: serde_ignored::Path| unknown.push(ignored_path(&path));
+    let deserializer = serde_ignored::Deserializer::new(tree, &mut record);
+
+    let value = serde_path_to_error::deserialize(deserializer).map_err(|e| {
+        let path = e.path().to_string();
+        RedTokenError::ConfigError(format!(
+            "{}: {}",
+            if path == "." { "(root)" } else { &path },
+            e.inner()
+        ))
+    })?;
+
+    Ok((value, unknown))
+}
+
+// serde_ignored writes `channels.0`; use `channels[0]` like every other config path
+fn ignored_path(path: &serde_ignored::Path) -> String {
+    match path {
+        serde_ignored::Path::Root => String::new(),
+        serde_ignored::Path::Seq { parent, index } => {
+            format!("{}[{}]", ignored_path(parent), index)
+        }
+        serde_ignored::Path::Map { parent, key } => join_path(&ignored_path(parent), key),
+        serde_ignored::Path::Some { parent }
+        | serde_ignored::Path::NewtypeStruct { parent }
+        | serde_ignored::Path::NewtypeVariant { parent } => ignored_path(parent),
+    }
+}
+
+// Keys of `raw` that are missing from `known`, the same value written back out by serde.
+// Finds unknown keys that serde_ignored cannot see, such as those under #[serde(flatten)].
+pub fn unknown_keys(raw: &Value, known: &Value, path: &str) -> Vec<String> {
+    if let (Value::Array(raw_items), Value::Array(known_items)) = (raw, known) {
+        return raw_items
+            .iter()
+            .zip(known_items)
+            .enumerate()
+            .flat_map(|(index, (raw_child, known_child))| {
+                unknown_keys(raw_child, known_child, &format!("{}[{}]", path, index))
+            })
+            .collect();
+    }
+    let (Value::Object(raw_map), Value::Object(known_map)) = (raw, known) else {
+        return Vec::new();
+    };
+
+    let mut unknown = Vec::new();
+    for (key, raw_child) in raw_map {
+        let child_path = join_path(path, key);
+        match known_map.get(key) {
+            Some(known_child) => unknown.extend(unknown_keys(raw_child, known_child, &child_path)),
+            None => unknown.push(child_path),
+        }
+    }
+    unknown
+}
+
+// Paths that differ between two config trees, prefixed with +, - or ~.
+// Values are left out so secrets never reach the log.
+pub fn diff(old: &Value, new: &Value) -> Vec<String> {
+    let mut changes = Vec::new();
+    diff_value(old, new, "", &mut changes);
+    changes
+}
+
+fn diff_value(old: &Value, new: &Value, path: &str, changes: &mut Vec<String>) {
+    match (old, new) {
+        (Value::Object(old_map), Value::Object(new_map)) => {
+            for (key, old_child) in old_map {
+                let child_path = join_path(path, key);
+                match new_map.get(key) {
+                    Some(new_child) => diff_value(old_child, new_child, &child_path, changes),
+                    None => changes.push(format!("- {}", child_path)),
+                }
+            }
+            for key in new_map.keys().filter(|key| !old_map.contains_key(*key)) {
+                changes.push(format!("+ {}", join_path(path, key)));
+            }
+        }
+        (Value::Array(old_items), Value::Array(new_items)) => {
+            for index in 0..old_items.len().max(new_items.len()) {
+                let child_path = format!("{}[{}]", path, index);
+                match (old_items.get(index), new_items.get(index)) {
+                    (Some(old_child), Some(new_child)) => {
+                        diff_value(old_child, new_child, &child_path, changes)
+                    }
+                    (Some(_), None) => changes.push(format!("- {}", child_path)),
+                    (None, Some(_)) => changes.push(format!("+ {}", child_path)),
+                    (None, None) => {}
+                }
+            }
+        }
+        _ if old != new => changes.push(format!("~ {}", display_path(path))),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use serde_json::json;
+
+    #[derive(Debug, Deserialize)]
+    struct Sample {
+        web: Web,
+        #[serde(default)]
+        channels: Vec<Channel>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Web {
+        port: u16,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Channel {
+        enabled: bool,
+    }
+
+    fn vars(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn env_overrides_set_nested_values() {
+        let mut tree = json!({
+            "web": { "port": 8080 },
+            "notification": { "channels": [{ "Slack": { "enabled": false } }] }
+        });
+
+        let applied = apply_env_overrides(
+            &mut tree,
+            vars(&[
+                ("REDTOKEN__WEB__PORT", "9000"),
+                ("REDTOKEN__WEB__HOST", "0.0.0.0"),
+                (
+                    "REDTOKEN__NOTIFICATION__CHANNELS__0__SLACK__ENABLED",
+                    "true",
+                ),
+                ("PATH", "/usr/bin"),
+                ("REDTOKEN__", "ignored"),
+            ]),
+        );
+
+        assert_eq!(tree["web"]["port"], json!(9000));
+        assert_eq!(tree["web"]["host"], json!("0.0.0.0"));
+        // The existing `Slack` key is reused rather than adding `slack`
+        assert_eq!(
+            tree["notification"]["channels"][0],
+            json!({ "Slack": { "enabled": true } })
+        );
+        assert_eq!(
+            applied,
+            vec![
+                "web.port",
+                "web.host",
+                "notification.channels[0].slack.enabled"
+            ]
+        );
+    }
+
+    #[test]
+    fn env_overrides_create_missing_sections() {
+        let mut tree = json!({});
+        apply_env_overrides(&mut tree, vars(&[("REDTOKEN__STORAGE__PATH", "/data")]));
+        assert_eq!(tree, json!({ "storage": { "path": "/data" } }));
+    }
+
+    #[test]
+    fn references_expand_from_env_and_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("token.txt"), "s3cret\n").unwrap();
+        std::env::set_var("REDTOKEN_LOADER_TEST_HOST", "example.com");
+
+        let mut tree = json!({
+            "web": { "url": "https://${env:REDTOKEN_LOADER_TEST_HOST}/hook" },
+            "list": ["${file:token.txt}", "plain"]
+        });
+        expand_references(&mut tree, dir.path()).unwrap();
+
+        assert_eq!(tree["web"]["url"], json!("https://example.com/hook"));
+        assert_eq!(tree["list"], json!(["s3cret", "plain"]));
+    }
+
+    #[test]
+    fn reference_errors_name_the_config_path() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let mut tree = json!({ "a": { "list": ["ok", "${env:REDTOKEN_LOADER_TEST_UNSET}"] } });
+        let error = expand_references(&mut tree, dir.path()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Configuration error: a.list[1]: environment variable REDTOKEN_LOADER_TEST_UNSET is not set"
+        );
+
+        let mut tree = json!({ "secret": "${file:missing.txt}" });
+        let error = expand_references(&mut tree, dir.path()).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("secret: cannot read secret file"));
+    }
+
+    #[test]
+    fn toml_output_drops_nulls() {
+        let value = json!({
+            "web": { "port": 8080, "host": null },
+            "channels": [{ "name": "ops", "url": null }]
+        });
+
+        let content = ConfigFormat::Toml.serialize(&value).unwrap();
+        assert!(!content.contains("host"));
+        assert!(!content.contains("url"));
+
+        let parsed = ConfigFormat::Toml.parse(&content).unwrap();
+        assert_eq!(
+            parsed,
+            json!({ "web": { "port": 8080 }, "channels": [{ "name": "ops" }] })
+        );
+    }
+
+    #[test]
+    fn deserialize_errors_and_unknown_keys_share_the_path_notation() {
+        let tree = json!({
+            "web": { "port": 8080, "colour": "red" },
+            "channels": [{ "enabled": true }, { "enabled": "yes" }]
+        });
+        let error = deserialize::<Sample>(tree).unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("channels[1].enabled: invalid type"),
+            "{}",
+            error
+        );
+
+        let tree = json!({
+            "web": { "port": 8080, "colour": "red" },
+            "channels": [{ "enabled": true }, { "enabled": false, "extra": 1 }]
+        });
+        let (sample, unknown) = deserialize::<Sample>(tree).unwrap();
+        assert_eq!(sample.web.port, 8080);
+        assert_eq!(sample.channels.len(), 2);
+        assert!(sample.channels[0].enabled);
+        assert_eq!(unknown, vec!["channels[1].extra", "web.colour"]);
+    }
+
+    #[test]
+    fn unknown_keys_walk_objects_and_arrays() {
+        let raw = json!({ "name": "ops", "typo": 1, "headers": [{ "k": 1, "x": 2 }] });
+        let known = json!({ "name": "ops", "headers": [{ "k": 1 }] });
+        assert_eq!(
+            unknown_keys(&raw, &known, "notification.channels[0]"),
+            vec![
+                "notification.channels[0].headers[0].x",
+                "notification.channels[0].typo"
+            ]
+        );
+    }
+}
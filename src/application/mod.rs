@@ -1,2 +1,3 @@
 pub mod config;
+pub mod config_loader;
 pub mod service;
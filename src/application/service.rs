@@ -1,15 +1,64 @@
 use crate::core::{
-    token::{Honeytoken, TokenRepository},
-    notification::{NotificationService, NotificationConfig},
-    injection::{FileInjector, InjectionConfig},
+    error::RedTokenError,
+    injection::{FileInjector, FileType, InjectionPreview},
+    notification::{ChannelResult, NotificationChannel, NotificationService, RoutedChannel},
+    outbox::{AlertAction, DeliveryStatus, OutboxJob, OutboxRepository, RetryPolicy},
+    token::{Honeytoken, Severity, TokenRepository, TriggerEvent},
 };
 use anyhow::Result;
-use log::{info, error};
+use arc_swap::ArcSwap;
+use log::{error, info, warn};
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+// Tokens record an absolute path so removal and routing globs do not depend on the
+// working directory. Only the directory is resolved: a symlinked file stays a symlink.
+async fn absolute_path(file_path: &str) -> Result<String> {
+    let path = Path::new(file_path);
+    let file_name = path.file_name().ok_or_else(|| {
+        RedTokenError::InvalidFileFormat(format!("'{}' does not name a file", file_path))
+    })?;
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let parent =
+        tokio::fs::canonicalize(parent)
+            .await
+            .map_err(|source| RedTokenError::FileReadError {
+                path: parent.to_path_buf(),
+                source,
+            })?;
+    Ok(parent.join(file_name).to_string_lossy().into_owned())
+}
 
 pub struct RedTokenService {
     token_repo: Box<dyn TokenRepository>,
     file_injector: Box<dyn FileInjector>,
-    notification_service: Box<dyn NotificationService>,
+    // Swapped atomically when `serve` reloads its config
+    notification_service: ArcSwap<Box<dyn NotificationService>>,
+    outbox_repo: Box<dyn OutboxRepository>,
+    retry_policy: RetryPolicy,
+    // Serialises outbox processing so a job is never delivered twice
+    outbox_lock: Mutex<()>,
+}
+
+// Due jobs sharing a channel and action, delivered together
+type JobGroup = (
+    String,
+    NotificationChannel,
+    AlertAction,
+    Vec<(OutboxJob, Honeytoken)>,
+);
+
+#[derive(Debug, Default)]
+pub struct OutboxSummary {
+    pub delivered: usize,
+    pub retried: usize,
+    pub dead_lettered: usize,
+    pub dropped: usize, // Jobs whose channel was removed or disabled
 }
 
 impl RedTokenService {
@@ -17,53 +66,888 @@ impl RedTokenService {
         token_repo: Box<dyn TokenRepository>,
         file_injector: Box<dyn FileInjector>,
         notification_service: Box<dyn NotificationService>,
+        outbox_repo: Box<dyn OutboxRepository>,
+        retry_policy: RetryPolicy,
     ) -> Self {
         Self {
             token_repo,
             file_injector,
-            notification_service,
+            notification_service: ArcSwap::from_pointee(notification_service),
+            outbox_repo,
+            retry_policy,
+            outbox_lock: Mutex::new(()),
         }
     }
 
-    pub async fn inject_token(&self, file_path: &str, value: String) -> Result<Honeytoken> {
-        let token = Honeytoken::new(value, file_path.to_string());
-        
+    // In-flight deliveries keep the service they started with
+    pub fn replace_notification_service(&self, notification_service: Box<dyn NotificationService>) {
+        self.notification_service
+            .store(std::sync::Arc::new(notification_service));
+    }
+
+    pub async fn inject_token(
+        &self,
+        file_path: &str,
+        file_type: Option<FileType>,
+        value: Option<String>,
+        severity: Severity,
+        tags: Vec<String>,
+        campaign: Option<String>,
+    ) -> Result<Honeytoken> {
+        let file_path = &absolute_path(file_path).await?;
+        let mut token = self.new_token(file_path, file_type, value, severity, tags, campaign);
+
         // Inject the token into the file
-        self.file_injector.inject_token(file_path, &token).await?;
-        
+        self.file_injector
+            .inject_token(file_path, &mut token)
+            .await?;
+
         // Save the token to repository
         self.token_repo.save(&token).await?;
-        
+
         info!("Successfully injected token into {}", file_path);
         Ok(token)
     }
 
-    pub async fn check_token(&self, token_value: &str) -> Result<()> {
-        if let Some(mut token) = self.token_repo.find_by_value(token_value).await? {
-            if !token.is_triggered {
-                token.mark_as_triggered();
-                self.token_repo.update(&token).await?;
-                
-                // Send notification
-                if let Err(e) = self.notification_service.send_alert(&token).await {
-                    error!("Failed to send notification: {}", e);
-                }
-                
-                info!("Token {} has been triggered!", token.id);
-            }
+    // What inject_token would change; the file and repository are left alone
+    pub async fn preview_inject(
+        &self,
+        file_path: &str,
+        file_type: Option<FileType>,
+        value: Option<String>,
+        severity: Severity,
+        tags: Vec<String>,
+        campaign: Option<String>,
+    ) -> Result<(Honeytoken, InjectionPreview)> {
+        let file_path = &absolute_path(file_path).await?;
+        let token = self.new_token(file_path, file_type, value, severity, tags, campaign);
+        let preview = self
+            .file_injector
+            .preview_injection(file_path, &token)
+            .await?;
+        Ok((token, preview))
+    }
+
+    fn new_token(
+        &self,
+        file_path: &str,
+        file_type: Option<FileType>,
+        value: Option<String>,
+        severity: Severity,
+        tags: Vec<String>,
+        campaign: Option<String>,
+    ) -> Honeytoken {
+        // Auto-detect the format from the file name when not given
+        let file_type = file_type.unwrap_or_else(|| FileType::detect(Path::new(file_path)));
+        let value = value.unwrap_or_else(|| self.file_injector.default_value(&file_type));
+        let mut token = Honeytoken::new(value, file_path.to_string());
+        token.severity = severity;
+        token.tags = tags;
+        token.campaign = campaign;
+        token.kind = Some(file_type.name().to_string());
+        token
+    }
+
+    pub async fn check_token(&self, token_value: &str, event: TriggerEvent) -> Result<()> {
+        match self.token_repo.find_by_value(token_value).await? {
+            Some(token) => self.trigger(token, event).await,
+            None => Ok(()),
+        }
+    }
+
+    // For decoys that call home with the token's id, so the value never shows in a URL
+    pub async fn check_token_id(&self, id: Uuid, event: TriggerEvent) -> Result<()> {
+        match self.token_repo.find_by_id(id).await? {
+            Some(token) => self.trigger(token, event).await,
+            None => Ok(()),
+        }
+    }
+
+    async fn trigger(&self, mut token: Honeytoken, event: TriggerEvent) -> Result<()> {
+        if token.is_triggered {
+            return Ok(());
+        }
+        token.mark_as_triggered(event);
+        self.token_repo.update(&token).await?;
+
+        // Queue one delivery per routed channel so failed alerts survive restarts
+        let routed = self.notification_service.load().route(&token);
+        for routed in routed {
+            let job = OutboxJob::new(token.id, routed.name, AlertAction::Trigger);
+            self.outbox_repo.save(&job).await?;
+        }
+
+        info!("Token {} has been triggered!", token.id);
+
+        if let Err(e) = self.process_outbox().await {
+            error!("Failed to process alert outbox: {}", e);
         }
         Ok(())
     }
 
+    // Send a clearly marked synthetic alert through every configured channel.
+    // Bypasses the outbox so the result of each attempt is reported directly.
+    pub async fn test_alert(&self, severity: Severity) -> Vec<ChannelResult> {
+        let mut token = Honeytoken::new(
+            format!("redtoken_test_{}", Uuid::new_v4()),
+            "[TEST] RedToken test alert - no action required".to_string(),
+        );
+        token.severity = severity;
+        token.tags = vec!["test".to_string()];
+        token.kind = Some("test".to_string());
+        token.campaign = Some("redtoken-test-alert".to_string());
+        token.mark_as_triggered(TriggerEvent {
+            source_ip: None,
+            user_agent: Some(format!("redtoken/{}", env!("CARGO_PKG_VERSION"))),
+        });
+
+        info!("Sending test alert {}", token.id);
+        let notifier = self.notification_service.load_full();
+        notifier.test_channels(&token).await
+    }
+
     pub async fn list_tokens(&self) -> Result<Vec<Honeytoken>> {
         self.token_repo.find_all().await
     }
 
-    pub async fn remove_token(&self, token_id: uuid::Uuid) -> Result<()> {
+    pub async fn get_token(&self, token_id: Uuid) -> Result<Honeytoken> {
+        let token = self
+            .token_repo
+            .find_by_id(token_id)
+            .await?
+            .ok_or_else(|| RedTokenError::TokenNotFound(token_id.to_string()))?;
+        Ok(token)
+    }
+
+    // Channels that would be notified if the token fired right now
+    pub async fn route_token(&self, token_id: Uuid) -> Result<(Honeytoken, Vec<RoutedChannel>)> {
+        let token = self.get_token(token_id).await?;
+        let routed = self.notification_service.load().route(&token);
+        Ok((token, routed))
+    }
+
+    pub async fn remove_token(&self, token_id: Uuid) -> Result<()> {
         if let Some(token) = self.token_repo.find_by_id(token_id).await? {
-            self.file_injector.remove_token(&token.file_path, &token).await?;
+            self.file_injector
+                .remove_token(&token.file_path, &token)
+                .await?;
             info!("Successfully removed token {}", token_id);
+
+            // A retired token should not leave an incident open
+            if token.is_triggered && self.queue_resolves(&token).await? > 0 {
+                if let Err(e) = self.process_outbox().await {
+                    error!("Failed to process alert outbox: {}", e);
+                }
+            }
         }
         Ok(())
     }
-} 
\ No newline at end of file
+
+    // What remove_token would change in the token's file
+    pub async fn preview_remove(&self, token_id: Uuid) -> Result<InjectionPreview> {
+        let token = self.get_token(token_id).await?;
+        self.file_injector
+            .preview_removal(&token.file_path, &token)
+            .await
+    }
+
+    // Acknowledge a triggered token: close its incidents and re-arm it
+    pub async fn resolve_token(&self, token_id: Uuid) -> Result<usize> {
+        let mut token = self
+            .token_repo
+            .find_by_id(token_id)
+            .await?
+            .ok_or_else(|| RedTokenError::TokenNotFound(token_id.to_string()))?;
+
+        let queued = if token.is_triggered {
+            self.queue_resolves(&token).await?
+        } else {
+            0
+        };
+        token.acknowledge();
+        self.token_repo.update(&token).await?;
+        info!("Token {} acknowledged", token.id);
+
+        if let Err(e) = self.process_outbox().await {
+            error!("Failed to process alert outbox: {}", e);
+        }
+        Ok(queued)
+    }
+
+    async fn queue_resolves(&self, token: &Honeytoken) -> Result<usize> {
+        let mut queued = 0;
+        let routed = self.notification_service.load().route(token);
+        for routed in routed {
+            if routed.channel.supports_resolve() {
+                let job = OutboxJob::new(token.id, routed.name, AlertAction::Resolve);
+                self.outbox_repo.save(&job).await?;
+                queued += 1;
+            }
+        }
+        Ok(queued)
+    }
+
+    // Jobs that have not been delivered yet, oldest first
+    pub async fn pending_alerts(&self) -> Result<Vec<OutboxJob>> {
+        let mut jobs: Vec<OutboxJob> = self
+            .outbox_repo
+            .find_all()
+            .await?
+            .into_iter()
+            .filter(|job| job.status != DeliveryStatus::Delivered)
+            .collect();
+        jobs.sort_by_key(|job| job.created_at);
+        Ok(jobs)
+    }
+
+    // Requeue dead-lettered jobs (or a single job) and attempt delivery right away
+    pub async fn retry_alerts(&self, job_id: Option<Uuid>) -> Result<OutboxSummary> {
+        let jobs = match job_id {
+            Some(id) => match self.outbox_repo.find_by_id(id).await? {
+                Some(job) => vec![job],
+                None => return Err(anyhow::anyhow!("Alert job {} not found", id)),
+            },
+            None => self
+                .outbox_repo
+                .find_all()
+                .await?
+                .into_iter()
+                .filter(|job| job.status == DeliveryStatus::DeadLetter)
+                .collect(),
+        };
+
+        for mut job in jobs {
+            if job.status == DeliveryStatus::Delivered {
+                continue;
+            }
+            job.requeue();
+            self.outbox_repo.update(&job).await?;
+        }
+
+        self.process_outbox().await
+    }
+
+    pub async fn process_outbox(&self) -> Result<OutboxSummary> {
+        let _guard = self.outbox_lock.lock().await;
+        let now = SystemTime::now();
+        let mut summary = OutboxSummary::default();
+        let notifier = self.notification_service.load_full();
+
+        let mut due: Vec<OutboxJob> = Vec::new();
+        for job in self.outbox_repo.find_all().await? {
+            // Delivered jobs left behind by earlier versions
+            if job.status == DeliveryStatus::Delivered {
+                self.outbox_repo.delete(job.id).await?;
+            } else if job.is_due(now) {
+                due.push(job);
+            }
+        }
+
+        // Group deliverable jobs by channel so batching channels get one request per chunk
+        let mut groups: Vec<JobGroup> = Vec::new();
+
+        for mut job in due {
+            if self.retry_policy.is_expired(&job, now) {
+                warn!("Alert job {} exceeded its max age", job.id);
+                job.mark_dead("Exceeded max age".to_string());
+                self.outbox_repo.update(&job).await?;
+                summary.dead_lettered += 1;
+                continue;
+            }
+
+            // Removed or disabled channels get no further alerts
+            let Some(channel) = notifier.channel(&job.channel) else {
+                warn!(
+                    "Dropping alert job {}: channel '{}' is no longer configured or enabled",
+                    job.id, job.channel
+                );
+                self.outbox_repo.delete(job.id).await?;
+                summary.dropped += 1;
+                continue;
+            };
+
+            let token = match self.token_repo.find_by_id(job.token_id).await? {
+                Some(token) => token,
+                None => {
+                    job.mark_dead(format!("Token {} not found", job.token_id));
+                    self.outbox_repo.update(&job).await?;
+                    summary.dead_lettered += 1;
+                    continue;
+                }
+            };
+
+            match groups
+                .iter_mut()
+                .find(|(name, _, action, _)| *name == job.channel && *action == job.action)
+            {
+                Some((_, _, _, jobs)) => jobs.push((job, token)),
+                None => groups.push((job.channel.clone(), channel, job.action, vec![(job, token)])),
+            }
+        }
+
+        for (_, channel, action, jobs) in groups {
+            if action == AlertAction::Resolve {
+                for (mut job, token) in jobs {
+                    let result = notifier.resolve(&channel, &token).await;
+                    self.record_attempt(&mut job, &token, &result, &mut summary);
+                    self.store_attempt(&job).await?;
+                }
+                continue;
+            }
+
+            for chunk in jobs.chunks(channel.batch_size()) {
+                let tokens: Vec<Honeytoken> =
+                    chunk.iter().map(|(_, token)| token.clone()).collect();
+                let result = notifier.deliver_batch(&channel, &tokens).await;
+                let failures = match &result {
+                    Err(e) => match e.downcast_ref::<RedTokenError>() {
+                        Some(RedTokenError::PartialBatchError(failures)) => Some(failures),
+                        _ => None,
+                    },
+                    Ok(()) => None,
+                };
+
+                for (index, (job, token)) in chunk.iter().enumerate() {
+                    let mut job = job.clone();
+                    match failures {
+                        // Only the rejected items are retried, the rest were accepted
+                        Some(failures) => {
+                            let result = match failures.iter().find(|(i, ..)| *i == index) {
+                                Some((_, status_code, message)) => {
+                                    Err(anyhow::Error::from(RedTokenError::ApiError {
+                                        status_code: *status_code,
+                                        message: message.clone(),
+                                    }))
+                                }
+                                None => Ok(()),
+                            };
+                            self.record_attempt(&mut job, token, &result, &mut summary);
+                        }
+                        None => self.record_attempt(&mut job, token, &result, &mut summary),
+                    }
+                    self.store_attempt(&job).await?;
+                }
+            }
+        }
+
+        Ok(summary)
+    }
+
+    // Delivered jobs have nothing left to do, so only undelivered ones are kept
+    async fn store_attempt(&self, job: &OutboxJob) -> Result<()> {
+        match job.status {
+            DeliveryStatus::Delivered => self.outbox_repo.delete(job.id).await,
+            _ => self.outbox_repo.update(job).await,
+        }
+    }
+
+    fn record_attempt(
+        &self,
+        job: &mut OutboxJob,
+        token: &Honeytoken,
+        result: &Result<()>,
+        summary: &mut OutboxSummary,
+    ) {
+        job.attempts += 1;
+        match result {
+            Ok(()) => {
+                job.mark_delivered();
+                summary.delivered += 1;
+            }
+            Err(e) => {
+                if is_permanent_failure(e) || job.attempts >= self.retry_policy.max_attempts {
+                    error!(
+                        "Giving up on {} alert for token {} after {} attempts: {}",
+                        job.channel, token.id, job.attempts, e
+                    );
+                    job.mark_dead(e.to_string());
+                    summary.dead_lettered += 1;
+                } else {
+                    let delay = self.retry_policy.backoff(job.attempts);
+                    warn!(
+                        "Failed to send {} alert for token {}, retrying in {:?}: {}",
+                        job.channel, token.id, delay, e
+                    );
+                    job.schedule_retry(delay, e.to_string());
+                    summary.retried += 1;
+                }
+            }
+        }
+    }
+
+    // Background loop used by `serve` to drain the outbox
+    pub async fn run_outbox_worker(&self) {
+        let interval = Duration::from_secs(self.retry_policy.poll_interval_secs.max(1));
+        let mut ticker = tokio::time::interval(interval);
+
+        loop {
+            ticker.tick().await;
+            if let Err(e) = self.process_outbox().await {
+                error!("Failed to process alert outbox: {}", e);
+            }
+        }
+    }
+}
+
+// Client errors other than timeouts and rate limiting will not succeed on retry
+fn is_permanent_failure(err: &anyhow::Error) -> bool {
+    match err.downcast_ref::<RedTokenError>() {
+        Some(RedTokenError::ApiError { status_code, .. }) => {
+            (400..500).contains(status_code) && *status_code != 408 && *status_code != 429
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{
+        injection::InjectionConfig,
+        notification::{default_channels, ChannelEntry, NotificationConfig},
+    };
+    use crate::infrastructure::{
+        injection::FileInjectionService,
+        notification::CompositeNotificationService,
+        repository::{FileOutboxRepository, InMemoryBackupRepository, InMemoryTokenRepository},
+        test_server::TestServer,
+    };
+    use std::sync::Arc;
+
+    fn discord(server: &TestServer) -> NotificationChannel {
+        NotificationChannel::Discord {
+            webhook_url: format!("{}/webhook", server.url),
+        }
+    }
+
+    // A service alerting a single channel named "ops". The outbox is a file so the test
+    // can inspect and reschedule jobs through a second repository; it lives as long
+    // as the returned directory.
+    async fn service(
+        channel: NotificationChannel,
+        retry_policy: RetryPolicy,
+        tokens: &[Honeytoken],
+    ) -> (RedTokenService, FileOutboxRepository, tempfile::TempDir) {
+        let notifier = CompositeNotificationService::new(NotificationConfig {
+            channels: vec![ChannelEntry::new("ops".to_string(), channel)],
+            rate_limit: None,
+            routes: Vec::new(),
+            default_channels: default_channels(),
+            templates: Default::default(),
+        })
+        .unwrap();
+
+        let token_repo = InMemoryTokenRepository::new();
+        for token in tokens {
+            token_repo.save(token).await.unwrap();
+        }
+
+        let injector = FileInjectionService::new(
+            InjectionConfig {
+                backup: Default::default(),
+                write: Default::default(),
+                options: Default::default(),
+                token_length: 32,
+                token_prefix: None,
+                include_symbols: false,
+            },
+            Arc::new(InMemoryBackupRepository::new()),
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let outbox = dir.path().join("outbox.db");
+        let service = RedTokenService::new(
+            Box::new(token_repo),
+            Box::new(injector),
+            Box::new(notifier),
+            Box::new(FileOutboxRepository::new(&outbox)),
+            retry_policy,
+        );
+        (service, FileOutboxRepository::new(&outbox), dir)
+    }
+
+    async fn queued_job(outbox: &FileOutboxRepository, token: &Honeytoken) -> OutboxJob {
+        let job = OutboxJob::new(token.id, "ops".to_string(), AlertAction::Trigger);
+        outbox.save(&job).await.unwrap();
+        job
+    }
+
+    #[tokio::test]
+    async fn failed_delivery_backs_off_then_succeeds() {
+        let server = TestServer::start(&[(503, "busy")]).await;
+        let token = Honeytoken::new("RT_backoff".to_string(), "/tmp/.env".to_string());
+        let policy = RetryPolicy {
+            base_delay_secs: 60,
+            jitter: 0.0,
+            ..RetryPolicy::default()
+        };
+        let (service, outbox, _dir) =
+            service(discord(&server), policy, std::slice::from_ref(&token)).await;
+        let job = queued_job(&outbox, &token).await;
+
+        let summary = service.process_outbox().await.unwrap();
+        assert_eq!((summary.retried, summary.delivered), (1, 0));
+        let mut stored = outbox.find_by_id(job.id).await.unwrap().unwrap();
+        assert_eq!(stored.status, DeliveryStatus::Pending);
+        assert_eq!(stored.attempts, 1);
+        assert!(stored.last_error.as_deref().unwrap().contains("503"));
+        let delay = stored
+            .next_attempt_at
+            .duration_since(SystemTime::now())
+            .unwrap();
+        assert!(delay > Duration::from_secs(55) && delay <= Duration::from_secs(60));
+
+        // Not due yet, so nothing is sent
+        let summary = service.process_outbox().await.unwrap();
+        assert_eq!((summary.retried, summary.delivered), (0, 0));
+        assert_eq!(server.requests().len(), 1);
+
+        stored.next_attempt_at = SystemTime::now();
+        outbox.update(&stored).await.unwrap();
+        let summary = service.process_outbox().await.unwrap();
+        assert_eq!(summary.delivered, 1);
+
+        // Delivered jobs are removed from the outbox
+        assert!(outbox.find_by_id(job.id).await.unwrap().is_none());
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests
+            .iter()
+            .all(|request| request.method == "POST" && request.path == "/webhook"));
+        assert_eq!(requests[1].header("content-type"), Some("application/json"));
+        assert!(requests[1].json()["embeds"][0]["description"]
+            .as_str()
+            .unwrap()
+            .contains("/tmp/.env"));
+    }
+
+    #[tokio::test]
+    async fn exhausted_and_permanent_failures_are_dead_lettered_until_retried() {
+        let server = TestServer::start(&[(500, "down"), (500, "down"), (404, "gone")]).await;
+        let token = Honeytoken::new("RT_dead".to_string(), "/tmp/.env".to_string());
+        let policy = RetryPolicy {
+            max_attempts: 2,
+            base_delay_secs: 0,
+            jitter: 0.0,
+            ..RetryPolicy::default()
+        };
+        let (service, outbox, _dir) =
+            service(discord(&server), policy, std::slice::from_ref(&token)).await;
+        let job = queued_job(&outbox, &token).await;
+
+        assert_eq!(service.process_outbox().await.unwrap().retried, 1);
+        assert_eq!(service.process_outbox().await.unwrap().dead_lettered, 1);
+        let stored = outbox.find_by_id(job.id).await.unwrap().unwrap();
+        assert_eq!(stored.status, DeliveryStatus::DeadLetter);
+        assert_eq!(stored.attempts, 2);
+
+        // Dead letters stay put until `alerts retry`
+        assert_eq!(service.process_outbox().await.unwrap().delivered, 0);
+        assert_eq!(service.pending_alerts().await.unwrap().len(), 1);
+
+        // A 404 will not go away by itself, so it is dead-lettered on the first attempt
+        let summary = service.retry_alerts(None).await.unwrap();
+        assert_eq!((summary.dead_lettered, summary.retried), (1, 0));
+        let stored = outbox.find_by_id(job.id).await.unwrap().unwrap();
+        assert_eq!(stored.attempts, 1);
+
+        let summary = service.retry_alerts(Some(job.id)).await.unwrap();
+        assert_eq!(summary.delivered, 1);
+        assert!(service.pending_alerts().await.unwrap().is_empty());
+        assert_eq!(server.requests().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn delivered_jobs_do_not_accumulate() {
+        let server = TestServer::start(&[]).await;
+        let tokens: Vec<Honeytoken> = (0..3)
+            .map(|n| Honeytoken::new(format!("RT_done_{}", n), format!("/srv/{}.env", n)))
+            .collect();
+        let (service, outbox, _dir) =
+            service(discord(&server), RetryPolicy::default(), &tokens).await;
+        for token in &tokens[..2] {
+            queued_job(&outbox, token).await;
+        }
+        // Written as delivered by an earlier version
+        let mut old = OutboxJob::new(tokens[2].id, "ops".to_string(), AlertAction::Trigger);
+        old.mark_delivered();
+        outbox.save(&old).await.unwrap();
+
+        assert_eq!(service.process_outbox().await.unwrap().delivered, 2);
+        assert!(outbox.find_all().await.unwrap().is_empty());
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn jobs_for_removed_channels_are_dropped() {
+        let server = TestServer::start(&[]).await;
+        let token = Honeytoken::new("RT_dropped".to_string(), "/tmp/.env".to_string());
+        let (service, outbox, _dir) = service(
+            discord(&server),
+            RetryPolicy::default(),
+            std::slice::from_ref(&token),
+        )
+        .await;
+        let job = OutboxJob::new(token.id, "retired".to_string(), AlertAction::Trigger);
+        outbox.save(&job).await.unwrap();
+
+        assert_eq!(service.process_outbox().await.unwrap().dropped, 1);
+        assert!(outbox.find_by_id(job.id).await.unwrap().is_none());
+        assert!(server.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn batching_channels_send_one_request_per_chunk() {
+        let server = TestServer::start(&[]).await;
+        let tokens: Vec<Honeytoken> = (0..5)
+            .map(|n| Honeytoken::new(format!("RT_batch_{}", n), format!("/srv/{}.env", n)))
+            .collect();
+        let channel = NotificationChannel::SplunkHec {
+            url: server.url.clone(),
+            token: "hec-token".to_string(),
+            index: None,
+            sourcetype: None,
+            batch_size: 2,
+            tls: Default::default(),
+        };
+        let (service, outbox, _dir) = service(channel, RetryPolicy::default(), &tokens).await;
+        for token in &tokens {
+            queued_job(&outbox, token).await;
+        }
+
+        assert_eq!(service.process_outbox().await.unwrap().delivered, 5);
+
+        let requests = server.requests();
+        let mut sizes: Vec<usize> = requests
+            .iter()
+            .map(|request| request.body.lines().count())
+            .collect();
+        sizes.sort();
+        assert_eq!(sizes, vec![1, 2, 2]);
+
+        // Every token is sent exactly once
+        let mut paths: Vec<String> = requests
+            .iter()
+            .flat_map(|request| request.body.lines().map(str::to_string).collect::<Vec<_>>())
+            .map(|line| {
+                let event: serde_json::Value = serde_json::from_str(&line).unwrap();
+                event["event"]["file"]["path"].as_str().unwrap().to_string()
+            })
+            .collect();
+        paths.sort();
+        let mut expected: Vec<String> = tokens.iter().map(|t| t.file_path.clone()).collect();
+        expected.sort();
+        assert_eq!(paths, expected);
+    }
+
+    #[tokio::test]
+    async fn bulk_document_errors_requeue_only_the_rejected_items() {
+        let partial = r#"{"errors":true,"items":[
+            {"create":{"status":201,"result":"created"}},
+            {"create":{"status":400,"error":{"reason":"mapper_parsing_exception"}}},
+            {"create":{"status":429,"error":{"reason":"es_rejected_execution_exception"}}}]}"#;
+        let indexed = r#"{"errors":false,"items":[{"create":{"status":201}}]}"#;
+        let server = TestServer::start(&[(200, partial), (200, indexed)]).await;
+        let tokens: Vec<Honeytoken> = ["created", "mapping", "busy"]
+            .iter()
+            .map(|name| Honeytoken::new(format!("RT_{}", name), format!("/srv/{}.env", name)))
+            .collect();
+        let channel = NotificationChannel::Elasticsearch {
+            url: server.url.clone(),
+            index: "alerts-redtoken".to_string(),
+            api_key: None,
+            username: None,
+            password: None,
+            batch_size: 50,
+            tls: Default::default(),
+        };
+        let (service, outbox, _dir) = service(channel, RetryPolicy::default(), &tokens).await;
+        let mut jobs = Vec::new();
+        for token in &tokens {
+            jobs.push(queued_job(&outbox, token).await);
+        }
+
+        // One document was indexed, a mapping error will never pass, a full queue might
+        let summary = service.process_outbox().await.unwrap();
+        assert_eq!(
+            (summary.delivered, summary.dead_lettered, summary.retried),
+            (1, 1, 1)
+        );
+        // Bulk items follow the order of the documents in the request
+        let sent: Vec<String> = server.requests()[0]
+            .body
+            .lines()
+            .skip(1)
+            .step_by(2)
+            .map(|line| {
+                let event: serde_json::Value = serde_json::from_str(line).unwrap();
+                event["file"]["path"].as_str().unwrap().to_string()
+            })
+            .collect();
+        let stored = |position: usize| {
+            let token = tokens
+                .iter()
+                .find(|t| t.file_path == sent[position])
+                .unwrap();
+            jobs.iter().find(|job| job.token_id == token.id).unwrap().id
+        };
+        assert!(outbox.find_by_id(stored(0)).await.unwrap().is_none());
+        let mapping = outbox.find_by_id(stored(1)).await.unwrap().unwrap();
+        assert_eq!(mapping.status, DeliveryStatus::DeadLetter);
+        assert!(mapping
+            .last_error
+            .unwrap()
+            .contains("mapper_parsing_exception"));
+        let mut busy = outbox.find_by_id(stored(2)).await.unwrap().unwrap();
+        assert_eq!(busy.status, DeliveryStatus::Pending);
+        assert!(busy
+            .last_error
+            .as_deref()
+            .unwrap()
+            .contains("es_rejected_execution_exception"));
+
+        // The retry carries only the rejected document, never the indexed one again
+        busy.next_attempt_at = SystemTime::now();
+        outbox.update(&busy).await.unwrap();
+        assert_eq!(service.process_outbox().await.unwrap().delivered, 1);
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1].body.lines().count(), 2);
+        assert!(requests[1].body.contains(&sent[2]));
+    }
+
+    fn trigger_event() -> TriggerEvent {
+        TriggerEvent {
+            source_ip: Some("192.0.2.10".to_string()),
+            user_agent: Some("curl/8.5".to_string()),
+        }
+    }
+
+    #[tokio::test]
+    async fn pagerduty_resolve_closes_the_triggered_incident() {
+        let server = TestServer::start(&[]).await;
+        let mut token = Honeytoken::new("RT_pager".to_string(), "/srv/.env".to_string());
+        token.severity = Severity::Critical;
+        let channel = NotificationChannel::PagerDuty {
+            routing_key: "routing-key".to_string(),
+            url: Some(format!("{}/v2/enqueue", server.url)),
+        };
+        let (service, _, _dir) = service(
+            channel,
+            RetryPolicy::default(),
+            std::slice::from_ref(&token),
+        )
+        .await;
+
+        service
+            .check_token(&token.value, trigger_event())
+            .await
+            .unwrap();
+        // A second hit on an unacknowledged token opens nothing new
+        service
+            .check_token(&token.value, trigger_event())
+            .await
+            .unwrap();
+        assert_eq!(service.resolve_token(token.id).await.unwrap(), 1);
+        // Re-armed after the resolve, so the next hit triggers again under the same key
+        service
+            .check_token(&token.value, trigger_event())
+            .await
+            .unwrap();
+
+        let events: Vec<serde_json::Value> = server
+            .requests()
+            .iter()
+            .inspect(|request| assert_eq!(request.path, "/v2/enqueue"))
+            .map(|request| request.json())
+            .collect();
+        let actions: Vec<&str> = events
+            .iter()
+            .map(|event| event["event_action"].as_str().unwrap())
+            .collect();
+        assert_eq!(actions, ["trigger", "resolve", "trigger"]);
+
+        let dedup_key = format!("redtoken-{}", token.id);
+        for event in &events {
+            assert_eq!(event["routing_key"], "routing-key");
+            assert_eq!(event["dedup_key"], dedup_key.as_str());
+        }
+        assert_eq!(events[0]["payload"]["severity"], "critical");
+        assert_eq!(events[0]["payload"]["component"], "/srv/.env");
+        assert_eq!(
+            events[0]["payload"]["custom_details"]["source"]["ip"],
+            "192.0.2.10"
+        );
+        assert!(events[1].get("payload").is_none());
+    }
+
+    #[tokio::test]
+    async fn opsgenie_resolve_closes_the_alert_by_alias() {
+        let server = TestServer::start(&[]).await;
+        let token = Honeytoken::new("RT_genie".to_string(), "/srv/.env".to_string());
+        let channel = NotificationChannel::Opsgenie {
+            api_key: "genie-key".to_string(),
+            url: Some(format!("{}/", server.url)),
+        };
+        let (service, _, _dir) = service(
+            channel,
+            RetryPolicy::default(),
+            std::slice::from_ref(&token),
+        )
+        .await;
+
+        service
+            .check_token(&token.value, trigger_event())
+            .await
+            .unwrap();
+        assert_eq!(service.resolve_token(token.id).await.unwrap(), 1);
+        // Nothing is open any more, so a second acknowledgement closes nothing
+        assert_eq!(service.resolve_token(token.id).await.unwrap(), 0);
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests
+            .iter()
+            .all(|request| request.header("authorization") == Some("GenieKey genie-key")));
+
+        let alias = format!("redtoken-{}", token.id);
+        assert_eq!(requests[0].path, "/v2/alerts");
+        let alert = requests[0].json();
+        assert_eq!(alert["alias"], alias.as_str());
+        assert_eq!(alert["priority"], "P3");
+        assert_eq!(alert["entity"], "/srv/.env");
+        assert_eq!(alert["details"]["source_ip"], "192.0.2.10");
+
+        assert_eq!(
+            requests[1].path,
+            format!("/v2/alerts/{}/close?identifierType=alias", alias)
+        );
+        assert_eq!(requests[1].json()["source"], "RedToken");
+    }
+
+    #[tokio::test]
+    async fn canary_requests_trigger_by_token_id() {
+        let server = TestServer::start(&[]).await;
+        let token = Honeytoken::new("RT_by_id".to_string(), "/srv/report.docx".to_string());
+        let (service, _, _dir) = service(
+            discord(&server),
+            RetryPolicy::default(),
+            std::slice::from_ref(&token),
+        )
+        .await;
+
+        // Unknown ids are ignored
+        service
+            .check_token_id(Uuid::new_v4(), trigger_event())
+            .await
+            .unwrap();
+        assert!(server.requests().is_empty());
+
+        service
+            .check_token_id(token.id, trigger_event())
+            .await
+            .unwrap();
+        assert_eq!(server.requests().len(), 1);
+        assert!(service.get_token(token.id).await.unwrap().is_triggered);
+    }
+}
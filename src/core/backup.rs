@@ -0,0 +1,51 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::PathBuf;
+use std::time::SystemTime;
+use uuid::Uuid;
+
+// Why a backup was taken
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackupOperation {
+    Inject,
+    Remove,
+    Restore, // Taken right before a restore, so the restore itself can be undone
+}
+
+impl fmt::Display for BackupOperation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BackupOperation::Inject => write!(f, "inject"),
+            BackupOperation::Remove => write!(f, "remove"),
+            BackupOperation::Restore => write!(f, "restore"),
+        }
+    }
+}
+
+// Catalog entry linking a backup copy to the file and token it belongs to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupRecord {
+    pub id: Uuid,
+    pub token_id: Option<Uuid>,
+    pub operation: BackupOperation,
+    pub original_path: PathBuf, // Absolute
+    pub backup_path: PathBuf,
+    pub sha256: String, // Of the original content, before compression
+    pub size: u64,
+    pub compressed: bool,
+    pub created_at: SystemTime,
+    // Metadata of the original file, reapplied on restore
+    pub mode: Option<u32>,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    pub modified: Option<SystemTime>,
+}
+
+#[async_trait::async_trait]
+pub trait BackupRepository: Send + Sync {
+    async fn save(&self, record: &BackupRecord) -> anyhow::Result<()>;
+    async fn find_by_id(&self, id: Uuid) -> anyhow::Result<Option<BackupRecord>>;
+    async fn find_all(&self) -> anyhow::Result<Vec<BackupRecord>>;
+    async fn delete(&self, id: Uuid) -> anyhow::Result<()>;
+}
@@ -20,12 +20,12 @@ pub enum RedTokenError {
     #[error("Invalid file format: {0}")]
     InvalidFileFormat(String),
 
-    #[error("Token validation failed: {0}")]
-    TokenValidationError(String),
-
     #[error("Token with ID {0} not found")]
     TokenNotFound(String),
 
+    #[error("Backup {0} not found")]
+    BackupNotFound(String),
+
     #[error("Failed to send notification: {0}")]
     NotificationError(String),
 
@@ -35,12 +35,13 @@ pub enum RedTokenError {
     #[error("API error: {status_code} - {message}")]
     ApiError { status_code: u16, message: String },
 
+    // Batch request where only some items failed: (index in the batch, status, reason)
+    #[error("{} of the batched alerts were rejected", .0.len())]
+    PartialBatchError(Vec<(usize, u16, String)>),
+
     #[error("Configuration error: {0}")]
     ConfigError(String),
 
-    #[error("Unauthorized access: {0}")]
-    UnauthorizedError(String),
-
     #[error("Unknown error: {0}")]
     Unknown(String),
 }
@@ -1,25 +1,445 @@
 use crate::core::token::Honeytoken;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use rand::{thread_rng, Rng};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use uuid::Uuid;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum FileType {
     Env,
     Json,
     Yaml,
     BashHistory,
+    ZshHistory,
+    FishHistory,
+    PowerShellHistory, // PSReadLine's ConsoleHost_history.txt
+    PsqlHistory,
+    MysqlHistory,
+    PythonHistory,
+    NodeHistory,
+    Toml,
+    Ini, // Also my.cnf, .gitconfig and pg_service.conf
+    AwsCredentials,
+    Kubeconfig,
+    K8sSecret,    // A `kind: Secret` manifest, possibly among other documents
+    DockerConfig, // ~/.docker/config.json
+    Compose,
+    Dockerfile, // Also Containerfile
+    Npmrc,
+    Pypirc,
+    Netrc,
+    GitCredentials,
+    PipConf, // Also pip.ini
+    Source(Language),
+    Tfvars,
+    Tfstate, // Also terraform.tfstate.backup
+    Docx,
+    Xlsx,
     Custom(String),
 }
 
+const BASE62: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+// Digit order GitHub uses for token checksums
+const BASE62_DIGITS: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+fn random_string(charset: &[u8], length: usize) -> String {
+    let mut rng = thread_rng();
+    (0..length)
+        .map(|_| charset[rng.gen_range(0..charset.len())] as char)
+        .collect()
+}
+
+// CRC-32 (IEEE), bit by bit; only used on a few bytes
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+impl FileType {
+    pub fn name(&self) -> &str {
+        match self {
+            FileType::Env => "env",
+            FileType::Json => "json",
+            FileType::Yaml => "yaml",
+            FileType::BashHistory => "bash",
+            FileType::ZshHistory => "zsh",
+            FileType::FishHistory => "fish",
+            FileType::PowerShellHistory => "powershell",
+            FileType::PsqlHistory => "psql",
+            FileType::MysqlHistory => "mysql",
+            FileType::PythonHistory => "python",
+            FileType::NodeHistory => "node",
+            FileType::Toml => "toml",
+            FileType::Ini => "ini",
+            FileType::AwsCredentials => "aws",
+            FileType::Kubeconfig => "kubeconfig",
+            FileType::K8sSecret => "k8s-secret",
+            FileType::DockerConfig => "docker",
+            FileType::Compose => "compose",
+            FileType::Dockerfile => "dockerfile",
+            FileType::Npmrc => "npmrc",
+            FileType::Pypirc => "pypirc",
+            FileType::Netrc => "netrc",
+            FileType::GitCredentials => "git-credentials",
+            FileType::PipConf => "pip",
+            FileType::Source(language) => language.name(),
+            FileType::Tfvars => "tfvars",
+            FileType::Tfstate => "tfstate",
+            FileType::Docx => "docx",
+            FileType::Xlsx => "xlsx",
+            FileType::Custom(name) => name,
+        }
+    }
+
+    // As given with --file-type
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "env" => FileType::Env,
+            "json" => FileType::Json,
+            "yaml" => FileType::Yaml,
+            "bash" => FileType::BashHistory,
+            "zsh" => FileType::ZshHistory,
+            "fish" => FileType::FishHistory,
+            "powershell" => FileType::PowerShellHistory,
+            "psql" => FileType::PsqlHistory,
+            "mysql" => FileType::MysqlHistory,
+            "python" => FileType::PythonHistory,
+            "node" => FileType::NodeHistory,
+            "toml" => FileType::Toml,
+            "ini" => FileType::Ini,
+            "aws" => FileType::AwsCredentials,
+            "kubeconfig" => FileType::Kubeconfig,
+            "k8s-secret" => FileType::K8sSecret,
+            "docker" => FileType::DockerConfig,
+            "compose" => FileType::Compose,
+            "dockerfile" => FileType::Dockerfile,
+            "npmrc" => FileType::Npmrc,
+            "pypirc" => FileType::Pypirc,
+            "netrc" => FileType::Netrc,
+            "git-credentials" => FileType::GitCredentials,
+            "pip" => FileType::PipConf,
+            "tfvars" => FileType::Tfvars,
+            "tfstate" => FileType::Tfstate,
+            "docx" => FileType::Docx,
+            "xlsx" => FileType::Xlsx,
+            other => match Language::from_extension(other) {
+                Some(language) => FileType::Source(language),
+                None => FileType::Custom(other.to_string()),
+            },
+        }
+    }
+
+    // Auto-detect from the file name, then the extension
+    pub fn detect(path: &Path) -> Self {
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_ascii_lowercase())
+            .unwrap_or_default();
+
+        let parent = path
+            .parent()
+            .and_then(|parent| parent.file_name())
+            .map(|name| name.to_string_lossy().to_string());
+
+        match file_name.as_str() {
+            ".env" => return FileType::Env,
+            "credentials" if parent.as_deref() == Some(".aws") => return FileType::AwsCredentials,
+            "config" if parent.as_deref() == Some(".kube") => return FileType::Kubeconfig,
+            "kubeconfig" => return FileType::Kubeconfig,
+            "config.json" if parent.as_deref() == Some(".docker") => return FileType::DockerConfig,
+            "dockerfile" | "containerfile" => return FileType::Dockerfile,
+            name if name.starts_with("dockerfile.") || name.ends_with(".dockerfile") => {
+                return FileType::Dockerfile
+            }
+            name if (name.starts_with("docker-compose") || name.starts_with("compose."))
+                && (name.ends_with(".yml") || name.ends_with(".yaml")) =>
+            {
+                return FileType::Compose
+            }
+            ".bash_history" => return FileType::BashHistory,
+            ".zsh_history" | ".zhistory" => return FileType::ZshHistory,
+            "fish_history" => return FileType::FishHistory,
+            name if name.ends_with(".tfstate") || name.ends_with(".tfstate.backup") => {
+                return FileType::Tfstate
+            }
+            name if name.ends_with("_history") && parent.as_deref() == Some("fish") => {
+                return FileType::FishHistory
+            }
+            "consolehost_history.txt" => return FileType::PowerShellHistory,
+            ".psql_history" => return FileType::PsqlHistory,
+            ".mysql_history" => return FileType::MysqlHistory,
+            ".python_history" => return FileType::PythonHistory,
+            ".node_repl_history" => return FileType::NodeHistory,
+            ".npmrc" | "npmrc" => return FileType::Npmrc,
+            ".pypirc" => return FileType::Pypirc,
+            ".netrc" | "_netrc" => return FileType::Netrc,
+            ".git-credentials" => return FileType::GitCredentials,
+            "pip.conf" | "pip.ini" => return FileType::PipConf,
+            ".gitconfig" | "gitconfig" | "pg_service.conf" | ".pg_service.conf" => {
+                return FileType::Ini
+            }
+            _ => {}
+        }
+
+        match path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_ascii_lowercase())
+            .as_deref()
+        {
+            Some("env") => FileType::Env,
+            Some("json") => FileType::Json,
+            Some("yaml") | Some("yml") => FileType::Yaml,
+            Some("history") => FileType::BashHistory,
+            Some("toml") => FileType::Toml,
+            Some("ini") | Some("cnf") | Some("cfg") => FileType::Ini,
+            Some("kubeconfig") => FileType::Kubeconfig,
+            Some("tfvars") => FileType::Tfvars,
+            Some("docx") => FileType::Docx,
+            Some("xlsx") => FileType::Xlsx,
+            Some(ext) => match Language::from_extension(ext) {
+                Some(language) => FileType::Source(language),
+                None => FileType::Custom("generic".to_string()),
+            },
+            None => FileType::Custom("generic".to_string()),
+        }
+    }
+
+    // Token value for formats that need one that passes for the real thing; None for the
+    // rest, which get a random string shaped by the token settings
+    pub fn default_value(&self) -> Option<String> {
+        let value = match self {
+            // AKIA + 16 base32 characters, like an IAM user access key id
+            FileType::AwsCredentials | FileType::Tfvars | FileType::Tfstate => {
+                format!(
+                    "AKIA{}",
+                    random_string(b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567", 16)
+                )
+            }
+            // Easy to read back out of a Secret, registry auth or image, so it must pass as a password
+            FileType::K8sSecret
+            | FileType::DockerConfig
+            | FileType::Compose
+            | FileType::Dockerfile => random_string(BASE62, 32),
+            // Sits in a password column or list, so a password manager's output
+            FileType::Docx | FileType::Xlsx => random_string(BASE62, 20),
+            // npm granular access token
+            FileType::Npmrc => format!("npm_{}", random_string(BASE62, 36)),
+            // PyPI API token: a macaroon whose location is pypi.org, hence the fixed prefix
+            FileType::Pypirc => {
+                let mut macaroon = b"\x02\x01\x08pypi.org\x02\x25".to_vec();
+                macaroon.extend(Uuid::new_v4().to_string().bytes());
+                macaroon.extend((0..120).map(|_| thread_rng().gen::<u8>()));
+                format!("pypi-{}", URL_SAFE_NO_PAD.encode(macaroon))
+            }
+            // Heroku API key, as the heroku CLI stores it
+            FileType::Netrc => Uuid::new_v4().to_string(),
+            // GitHub classic personal access token: 30 random characters and their CRC32
+            FileType::GitCredentials => {
+                let random = random_string(BASE62, 30);
+                let mut checksum = crc32(random.as_bytes()) as u64;
+                let mut encoded = Vec::new();
+                while checksum > 0 {
+                    encoded.push(BASE62_DIGITS[(checksum % 62) as usize]);
+                    checksum /= 62;
+                }
+                encoded.resize(6, b'0');
+                encoded.reverse();
+                format!("ghp_{}{}", random, String::from_utf8_lossy(&encoded))
+            }
+            // GitLab personal access token, used for its package registries
+            FileType::PipConf => format!(
+                "glpat-{}",
+                random_string(
+                    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_",
+                    20
+                )
+            ),
+            // Stripe live secret key, the classic hard-coded credential
+            FileType::Source(_) => format!("sk_live_51{}", random_string(BASE62, 97)),
+            // Shaped like a service account JWT: header.payload.signature
+            FileType::Kubeconfig => {
+                let header = URL_SAFE_NO_PAD.encode(format!(
+                    r#"{{"alg":"RS256","kid":"{}"}}"#,
+                    Uuid::new_v4().simple()
+                ));
+                let payload = URL_SAFE_NO_PAD.encode(format!(
+                    r#"{{"iss":"kubernetes/serviceaccount","kubernetes.io/serviceaccount/namespace":"kube-system","kubernetes.io/serviceaccount/secret.name":"cluster-admin-token-{}","sub":"system:serviceaccount:kube-system:cluster-admin","jti":"{}"}}"#,
+                    &Uuid::new_v4().simple().to_string()[..5],
+                    Uuid::new_v4()
+                ));
+                let signature: Vec<u8> = (0..256).map(|_| thread_rng().gen()).collect();
+                format!(
+                    "{}.{}.{}",
+                    header,
+                    payload,
+                    URL_SAFE_NO_PAD.encode(signature)
+                )
+            }
+            _ => return None,
+        };
+        Some(value)
+    }
+}
+
+// Languages of the source-code injector
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Language {
+    Python,
+    JavaScript,
+    TypeScript,
+    Tsx,
+    Go,
+    Java,
+    Rust,
+}
+
+impl Language {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Language::Python => "py",
+            Language::JavaScript => "js",
+            Language::TypeScript => "ts",
+            Language::Tsx => "tsx",
+            Language::Go => "go",
+            Language::Java => "java",
+            Language::Rust => "rs",
+        }
+    }
+
+    // Also used for --file-type, whose names are the main extensions
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension {
+            "py" | "pyw" => Some(Language::Python),
+            "js" | "mjs" | "cjs" | "jsx" => Some(Language::JavaScript),
+            "ts" | "mts" | "cts" => Some(Language::TypeScript),
+            "tsx" => Some(Language::Tsx),
+            "go" => Some(Language::Go),
+            "java" => Some(Language::Java),
+            "rs" => Some(Language::Rust),
+            _ => None,
+        }
+    }
+}
+
 #[async_trait::async_trait]
 pub trait FileInjector: Send + Sync {
-    async fn inject_token(&self, file_path: &str, token: &Honeytoken) -> anyhow::Result<()>;
-    async fn verify_injection(&self, file_path: &str, token: &Honeytoken) -> anyhow::Result<bool>;
+    // Value for a token created without one
+    fn default_value(&self, file_type: &FileType) -> String;
+    // Records the files it had to create in `token.created_files`
+    async fn inject_token(&self, file_path: &str, token: &mut Honeytoken) -> anyhow::Result<()>;
     async fn remove_token(&self, file_path: &str, token: &Honeytoken) -> anyhow::Result<()>;
+    // Same changes as inject_token/remove_token, computed in memory; nothing is written
+    async fn preview_injection(
+        &self,
+        file_path: &str,
+        token: &Honeytoken,
+    ) -> anyhow::Result<InjectionPreview>;
+    async fn preview_removal(
+        &self,
+        file_path: &str,
+        token: &Honeytoken,
+    ) -> anyhow::Result<InjectionPreview>;
+}
+
+// What an injection or removal would change in a file
+#[derive(Debug, Clone, Serialize)]
+pub struct InjectionPreview {
+    pub file_path: String,
+    pub key: Option<String>, // Variable or key name holding the token
+    pub placement: String,   // Where in the file the change lands
+    pub diff: String,        // Unified diff, empty when nothing changes
+}
+
+// How target files are copied aside before they are modified
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackupPolicy {
+    pub enabled: bool,
+    pub directory: PathBuf,
+    pub retention_count: Option<usize>, // Backups kept per file, newest first
+    pub retention_days: Option<u64>,
+    pub compress: bool, // gzip, stored with a .gz suffix
+}
+
+impl Default for BackupPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            directory: PathBuf::from("backups"),
+            retention_count: None,
+            retention_days: None,
+            compress: false,
+        }
+    }
+}
+
+// What to do when the target path is a symbolic link
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SymlinkPolicy {
+    #[default]
+    Follow, // Rewrite the file the link points to and keep the link itself
+    Refuse,
+}
+
+// How injectors rewrite target files
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WritePolicy {
+    pub preserve_mtime: bool, // Keep access and modification times, so the change is less visible
+    pub symlinks: SymlinkPolicy,
+}
+
+// How a decoy kubeconfig user authenticates
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum KubeAuth {
+    #[default]
+    Token, // Bearer token, sent by kubectl on every request
+    Cert, // Client certificate; the token goes in the server URL instead
+}
+
+impl FromStr for KubeAuth {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "token" => Ok(KubeAuth::Token),
+            "cert" => Ok(KubeAuth::Cert),
+            other => Err(format!(
+                "Unknown kubeconfig auth '{}' (expected token or cert)",
+                other
+            )),
+        }
+    }
+}
+
+// Per-run switches for injectors that can touch more than the target file
+#[derive(Debug, Clone, Default)]
+pub struct InjectionOptions {
+    pub aws_config: bool, // Also add the profile to the `config` file next to AWS credentials
+    pub kube_auth: KubeAuth,
+    pub canary_url: Option<String>, // Base URL of the monitoring server, for decoys that call home
+    pub service: Option<String>,    // Compose service to add the variable to; the first one if None
+    pub command_templates: Vec<PathBuf>, // Fake command files for history decoys, on top of the built-in ones
 }
 
 pub struct InjectionConfig {
-    pub file_type: FileType,
-    pub backup_enabled: bool,
-    pub injection_pattern: Option<String>,
+    pub backup: BackupPolicy,
+    pub write: WritePolicy,
+    pub options: InjectionOptions,
+    pub token_length: usize,
     pub token_prefix: Option<String>,
     pub include_symbols: bool,
 }
@@ -1,4 +1,6 @@
+pub mod backup;
 pub mod error;
 pub mod injection;
 pub mod notification;
+pub mod outbox;
 pub mod token;
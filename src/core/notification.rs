@@ -1,7 +1,8 @@
-use crate::core::token::Honeytoken;
+use crate::core::token::{Honeytoken, Severity};
 use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum NotificationChannel {
     Telegram {
         webhook_url: String,
@@ -14,15 +15,389 @@ pub enum NotificationChannel {
         from: String,
         to: String,
     },
+    Syslog {
+        address: String, // udp://, tcp://, tls://host:port or unix:///dev/log
+        #[serde(default)]
+        format: SiemFormat,
+    },
+    SplunkHec {
+        url: String, // Base URL or full /services/collector/event endpoint
+        token: String,
+        #[serde(default)]
+        index: Option<String>,
+        #[serde(default)]
+        sourcetype: Option<String>,
+        #[serde(default = "default_batch_size")]
+        batch_size: usize,
+        #[serde(default)]
+        tls: TlsOptions,
+    },
+    Elasticsearch {
+        url: String,
+        index: String,
+        #[serde(default)]
+        api_key: Option<String>,
+        #[serde(default)]
+        username: Option<String>,
+        #[serde(default)]
+        password: Option<String>,
+        #[serde(default = "default_batch_size")]
+        batch_size: usize,
+        #[serde(default)]
+        tls: TlsOptions,
+    },
+    PagerDuty {
+        routing_key: String,
+        #[serde(default)]
+        url: Option<String>, // Defaults to the public Events API v2 endpoint
+    },
+    Opsgenie {
+        api_key: String,
+        #[serde(default)]
+        url: Option<String>, // Defaults to https://api.opsgenie.com, use the EU host if needed
+    },
+}
+
+fn default_batch_size() -> usize {
+    50
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+// A configured channel with a user-facing name; disabled channels stay in the config but never fire
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChannelEntry {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(flatten)]
+    pub channel: NotificationChannel,
+}
+
+impl ChannelEntry {
+    pub fn new(name: String, channel: NotificationChannel) -> Self {
+        Self {
+            name,
+            enabled: true,
+            channel,
+        }
+    }
+
+    // Selectors name a channel, a channel kind, or "*" for every channel
+    pub fn matches_selector(&self, selector: &str) -> bool {
+        selector == "*"
+            || selector == self.name
+            || selector.eq_ignore_ascii_case(self.channel.kind())
+    }
+}
+
+// Keep scheme and host, hide credentials and webhook paths
+fn mask_url(url: &str) -> String {
+    let (scheme, rest) = match url.split_once("://") {
+        Some((scheme, rest)) => (format!("{}://", scheme), rest),
+        None => (String::new(), url),
+    };
+    let (authority, path) = match rest.find('/') {
+        Some(index) => rest.split_at(index),
+        None => (rest, ""),
+    };
+    let host = match authority.rsplit_once('@') {
+        Some((_, host)) => format!("***@{}", host),
+        None => authority.to_string(),
+    };
+
+    if path.trim_matches('/').is_empty() {
+        format!("{}{}", scheme, host)
+    } else {
+        format!("{}{}/***", scheme, host)
+    }
+}
+
+fn mask_secret(secret: &str) -> String {
+    // ${env:...} and ${file:...} references name where the secret lives, not the secret
+    if secret.starts_with("${") && secret.ends_with('}') {
+        return secret.to_string();
+    }
+    let chars: Vec<char> = secret.chars().collect();
+    if chars.len() <= 8 {
+        return "****".to_string();
+    }
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("****{}", tail)
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TlsOptions {
+    #[serde(default)]
+    pub ca_cert: Option<PathBuf>, // Extra PEM root certificate to trust
+    #[serde(default)]
+    pub insecure_skip_verify: bool,
+}
+
+// Message body format for SIEM-oriented channels
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SiemFormat {
+    #[default]
+    Cef,
+    Leef,
+}
+
+impl NotificationChannel {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            NotificationChannel::Telegram { .. } => "telegram",
+            NotificationChannel::Discord { .. } => "discord",
+            NotificationChannel::Email { .. } => "email",
+            NotificationChannel::Syslog { .. } => "syslog",
+            NotificationChannel::SplunkHec { .. } => "splunk_hec",
+            NotificationChannel::Elasticsearch { .. } => "elasticsearch",
+            NotificationChannel::PagerDuty { .. } => "pagerduty",
+            NotificationChannel::Opsgenie { .. } => "opsgenie",
+        }
+    }
+
+    // Human readable destination with secrets masked, for listings
+    pub fn masked_target(&self) -> String {
+        match self {
+            NotificationChannel::Telegram { webhook_url }
+            | NotificationChannel::Discord { webhook_url } => mask_url(webhook_url),
+            NotificationChannel::Email {
+                smtp_server,
+                from,
+                to,
+            } => format!("{} ({} -> {})", mask_url(smtp_server), from, to),
+            NotificationChannel::Syslog { address, format } => {
+                format!("{} ({:?})", address, format)
+            }
+            NotificationChannel::SplunkHec { url, token, .. } => {
+                format!("{} token={}", mask_url(url), mask_secret(token))
+            }
+            NotificationChannel::Elasticsearch {
+                url,
+                index,
+                api_key,
+                username,
+                ..
+            } => {
+                let auth = match (api_key, username) {
+                    (Some(key), _) => format!(" api_key={}", mask_secret(key)),
+                    (None, Some(user)) => format!(" user={}", user),
+                    (None, None) => String::new(),
+                };
+                format!("{} index={}{}", mask_url(url), index, auth)
+            }
+            NotificationChannel::PagerDuty { routing_key, .. } => {
+                format!("routing_key={}", mask_secret(routing_key))
+            }
+            NotificationChannel::Opsgenie { api_key, .. } => {
+                format!("api_key={}", mask_secret(api_key))
+            }
+        }
+    }
+
+    // Channels that open incidents which can later be resolved
+    pub fn supports_resolve(&self) -> bool {
+        matches!(
+            self,
+            NotificationChannel::PagerDuty { .. } | NotificationChannel::Opsgenie { .. }
+        )
+    }
+
+    // Number of alerts that may be sent in a single request
+    pub fn batch_size(&self) -> usize {
+        match self {
+            NotificationChannel::SplunkHec { batch_size, .. }
+            | NotificationChannel::Elasticsearch { batch_size, .. } => (*batch_size).max(1),
+            _ => 1,
+        }
+    }
+}
+
+// Local hour window, e.g. 22-6 wraps past midnight
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HourRange {
+    pub start: u32,
+    pub end: u32,
+}
+
+impl HourRange {
+    pub fn contains(&self, hour: u32) -> bool {
+        if self.start <= self.end {
+            hour >= self.start && hour < self.end
+        } else {
+            hour >= self.start || hour < self.end
+        }
+    }
+}
+
+// Routes matching tokens to a subset of channels plus optional escalation targets.
+// Every condition that is set must match; unset conditions match anything.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoutingRule {
+    pub name: String,
+    #[serde(default)]
+    pub tags: Vec<String>, // Matches if the token has any of these tags
+    #[serde(default)]
+    pub path_glob: Option<String>,
+    #[serde(default)]
+    pub kinds: Vec<String>,
+    #[serde(default)]
+    pub min_severity: Option<Severity>,
+    #[serde(default)]
+    pub hours: Option<HourRange>,
+    #[serde(default)]
+    pub channels: Vec<String>, // Channel names or kinds to notify, empty means all channels
+    #[serde(default)]
+    pub escalate: Vec<NotificationChannel>,
+}
+
+impl RoutingRule {
+    pub fn matches(&self, token: &Honeytoken, hour: u32) -> bool {
+        if !self.tags.is_empty() && !self.tags.iter().any(|tag| token.tags.contains(tag)) {
+            return false;
+        }
+
+        if let Some(pattern) = &self.path_glob {
+            match glob::Pattern::new(pattern) {
+                Ok(pattern) if pattern.matches(&token.file_path) => {}
+                _ => return false,
+            }
+        }
+
+        if !self.kinds.is_empty() {
+            match &token.kind {
+                Some(kind) if self.kinds.iter().any(|k| k.eq_ignore_ascii_case(kind)) => {}
+                _ => return false,
+            }
+        }
+
+        if let Some(min_severity) = self.min_severity {
+            if token.severity < min_severity {
+                return false;
+            }
+        }
+
+        if let Some(hours) = &self.hours {
+            if !hours.contains(hour) {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    // Escalation targets have no configured name, so they are named after their rule
+    pub fn escalation_name(&self, index: usize) -> String {
+        format!("{}/escalate-{}", self.name, index + 1)
+    }
+}
+
+// A channel selected for a token, and the rule that selected it
+#[derive(Debug, Clone)]
+pub struct RoutedChannel {
+    pub name: String,
+    pub channel: NotificationChannel,
+    pub rule: Option<String>,
+    pub escalation: bool,
+}
+
+// Outcome of sending to a single channel, reported by test alerts
+#[derive(Debug, Clone, Serialize)]
+pub struct ChannelResult {
+    pub name: String,
+    pub channel: String,
+    pub success: bool,
+    pub latency_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status_code: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
 }
 
 #[async_trait::async_trait]
 pub trait NotificationService: Send + Sync {
-    async fn send_alert(&self, token: &Honeytoken) -> anyhow::Result<()>;
+    fn route(&self, token: &Honeytoken) -> Vec<RoutedChannel>;
+
+    // Enabled channel or escalation target with this name, as currently configured
+    fn channel(&self, name: &str) -> Option<NotificationChannel>;
+
+    // Send the token through every enabled channel and escalation target, ignoring routing rules
+    async fn test_channels(&self, token: &Honeytoken) -> Vec<ChannelResult>;
+
+    async fn deliver(
+        &self,
+        channel: &NotificationChannel,
+        token: &Honeytoken,
+    ) -> anyhow::Result<()>;
+
+    async fn resolve(
+        &self,
+        channel: &NotificationChannel,
+        token: &Honeytoken,
+    ) -> anyhow::Result<()>;
+
+    async fn deliver_batch(
+        &self,
+        channel: &NotificationChannel,
+        tokens: &[Honeytoken],
+    ) -> anyhow::Result<()> {
+        for token in tokens {
+            self.deliver(channel, token).await?;
+        }
+        Ok(())
+    }
+}
+
+// Replaces the built-in alert wording for matching channel/severity/language.
+// The file's first line is the title, the rest is the body.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateOverride {
+    #[serde(default)]
+    pub channel: Option<String>,
+    #[serde(default)]
+    pub severity: Option<Severity>,
+    #[serde(default)]
+    pub language: Option<String>,
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateConfig {
+    #[serde(default = "default_language")]
+    pub language: String, // "en" or "id"
+    #[serde(default)]
+    pub overrides: Vec<TemplateOverride>,
+}
+
+fn default_language() -> String {
+    "en".to_string()
+}
+
+impl Default for TemplateConfig {
+    fn default() -> Self {
+        Self {
+            language: default_language(),
+            overrides: Vec::new(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NotificationConfig {
-    pub channels: Vec<NotificationChannel>,
+    pub channels: Vec<ChannelEntry>,
     pub rate_limit: Option<u32>, // Notifications per hour
+    #[serde(default)]
+    pub routes: Vec<RoutingRule>,
+    #[serde(default = "default_channels")]
+    pub default_channels: Vec<String>, // Notified when no routing rule matches; empty sends nothing
+    #[serde(default)]
+    pub templates: TemplateConfig,
+}
+
+pub fn default_channels() -> Vec<String> {
+    vec!["*".to_string()]
 }
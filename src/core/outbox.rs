@@ -0,0 +1,136 @@
+use rand::{thread_rng, Rng};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DeliveryStatus {
+    Pending,
+    Delivered,
+    DeadLetter,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AlertAction {
+    #[default]
+    Trigger,
+    Resolve, // Close the incident opened by an earlier trigger
+}
+
+// A single alert-to-channel delivery waiting in the outbox. The channel is kept by name
+// and looked up when the job runs, so no credentials are written to the outbox.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutboxJob {
+    pub id: Uuid,
+    pub token_id: Uuid,
+    pub channel: String,
+    #[serde(default)]
+    pub action: AlertAction,
+    pub status: DeliveryStatus,
+    pub attempts: u32,
+    pub created_at: SystemTime,
+    pub next_attempt_at: SystemTime,
+    pub last_error: Option<String>,
+}
+
+impl OutboxJob {
+    pub fn new(token_id: Uuid, channel: String, action: AlertAction) -> Self {
+        let now = SystemTime::now();
+        Self {
+            id: Uuid::new_v4(),
+            token_id,
+            channel,
+            action,
+            status: DeliveryStatus::Pending,
+            attempts: 0,
+            created_at: now,
+            next_attempt_at: now,
+            last_error: None,
+        }
+    }
+
+    pub fn is_due(&self, now: SystemTime) -> bool {
+        self.status == DeliveryStatus::Pending && self.next_attempt_at <= now
+    }
+
+    pub fn mark_delivered(&mut self) {
+        self.status = DeliveryStatus::Delivered;
+        self.last_error = None;
+    }
+
+    pub fn schedule_retry(&mut self, delay: Duration, error: String) {
+        self.next_attempt_at = SystemTime::now() + delay;
+        self.last_error = Some(error);
+    }
+
+    pub fn mark_dead(&mut self, error: String) {
+        self.status = DeliveryStatus::DeadLetter;
+        self.last_error = Some(error);
+    }
+
+    pub fn requeue(&mut self) {
+        self.status = DeliveryStatus::Pending;
+        self.attempts = 0;
+        self.created_at = SystemTime::now();
+        self.next_attempt_at = self.created_at;
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay_secs: u64,
+    pub max_delay_secs: u64,
+    pub max_age_secs: u64, // Jobs older than this are dead-lettered
+    pub jitter: f64,       // Fraction of the delay randomised, 0.0 - 1.0
+    pub poll_interval_secs: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 8,
+            base_delay_secs: 5,
+            max_delay_secs: 900,
+            max_age_secs: 24 * 60 * 60,
+            jitter: 0.2,
+            poll_interval_secs: 5,
+        }
+    }
+}
+
+impl RetryPolicy {
+    // Exponential backoff for the given attempt number (1-based), with jitter
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exp = attempt.saturating_sub(1).min(31);
+        let delay = self
+            .base_delay_secs
+            .saturating_mul(1u64 << exp)
+            .min(self.max_delay_secs) as f64;
+
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        let factor = if jitter > 0.0 {
+            thread_rng().gen_range(1.0 - jitter..=1.0 + jitter)
+        } else {
+            1.0
+        };
+
+        Duration::from_secs_f64(delay * factor)
+    }
+
+    pub fn is_expired(&self, job: &OutboxJob, now: SystemTime) -> bool {
+        now.duration_since(job.created_at)
+            .map(|age| age > Duration::from_secs(self.max_age_secs))
+            .unwrap_or(false)
+    }
+}
+
+#[async_trait::async_trait]
+pub trait OutboxRepository: Send + Sync {
+    async fn save(&self, job: &OutboxJob) -> anyhow::Result<()>;
+    async fn find_by_id(&self, id: Uuid) -> anyhow::Result<Option<OutboxJob>>;
+    async fn find_all(&self) -> anyhow::Result<Vec<OutboxJob>>;
+    async fn update(&self, job: &OutboxJob) -> anyhow::Result<()>;
+    async fn delete(&self, id: Uuid) -> anyhow::Result<()>;
+}
@@ -1,7 +1,55 @@
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
 use std::time::SystemTime;
 use uuid::Uuid;
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Low,
+    #[default]
+    Medium,
+    High,
+    Critical,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Severity::Low => "low",
+            Severity::Medium => "medium",
+            Severity::High => "high",
+            Severity::Critical => "critical",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for Severity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "low" => Ok(Severity::Low),
+            "medium" => Ok(Severity::Medium),
+            "high" => Ok(Severity::High),
+            "critical" => Ok(Severity::Critical),
+            other => Err(format!(
+                "Unknown severity '{}' (expected low, medium, high or critical)",
+                other
+            )),
+        }
+    }
+}
+
+// Details about the request that used a token
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TriggerEvent {
+    pub source_ip: Option<String>,
+    pub user_agent: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Honeytoken {
     pub id: Uuid,
@@ -10,6 +58,22 @@ pub struct Honeytoken {
     pub created_at: SystemTime,
     pub last_checked: Option<SystemTime>,
     pub is_triggered: bool,
+    #[serde(default)]
+    pub severity: Severity,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub kind: Option<String>, // File type the token was injected into
+    #[serde(default)]
+    pub last_trigger: Option<TriggerEvent>,
+    #[serde(default)]
+    pub acknowledged_at: Option<SystemTime>,
+    #[serde(default)]
+    pub campaign: Option<String>, // Deployment campaign the token belongs to
+    #[serde(default)]
+    pub created_files: Vec<String>, // Files the injection created; removal deletes them once empty
+    #[serde(default)]
+    pub replaced_lines: Vec<String>, // Lines the injection rewrote, e.g. `users: null`; removal puts them back
 }
 
 impl Honeytoken {
@@ -21,12 +85,27 @@ impl Honeytoken {
             created_at: SystemTime::now(),
             last_checked: None,
             is_triggered: false,
+            severity: Severity::default(),
+            tags: Vec::new(),
+            kind: None,
+            last_trigger: None,
+            acknowledged_at: None,
+            campaign: None,
+            created_files: Vec::new(),
+            replaced_lines: Vec::new(),
         }
     }
 
-    pub fn mark_as_triggered(&mut self) {
+    pub fn mark_as_triggered(&mut self, event: TriggerEvent) {
         self.is_triggered = true;
         self.last_checked = Some(SystemTime::now());
+        self.last_trigger = Some(event);
+    }
+
+    // Re-arm the token once its incident has been dealt with
+    pub fn acknowledge(&mut self) {
+        self.is_triggered = false;
+        self.acknowledged_at = Some(SystemTime::now());
     }
 }
 
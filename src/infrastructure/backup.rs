@@ -0,0 +1,405 @@
+use crate::core::{
+    backup::{BackupOperation, BackupRecord, BackupRepository},
+    error::{RedTokenError, RedTokenResult},
+    injection::{BackupPolicy, WritePolicy},
+};
+use crate::infrastructure::rewrite::{unified_diff, TextFile};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use log::{debug, info, warn};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::io::AsyncWriteExt;
+use uuid::Uuid;
+
+const BACKUP_TIMESTAMP: &str = "%Y%m%d_%H%M%S";
+
+fn gzip(content: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(content)?;
+    encoder.finish()
+}
+
+fn gunzip(content: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut decoded = Vec::new();
+    GzDecoder::new(content).read_to_end(&mut decoded)?;
+    Ok(decoded)
+}
+
+fn sha256_hex(content: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
+
+fn catalog_error(e: anyhow::Error) -> RedTokenError {
+    RedTokenError::DatabaseError(format!("Backup catalog: {}", e))
+}
+
+fn read_error(path: &Path) -> impl FnOnce(std::io::Error) -> RedTokenError + '_ {
+    move |source| RedTokenError::FileReadError {
+        path: path.to_path_buf(),
+        source,
+    }
+}
+
+fn write_error(path: &Path) -> impl FnOnce(std::io::Error) -> RedTokenError + '_ {
+    move |source| RedTokenError::FileWriteError {
+        path: path.to_path_buf(),
+        source,
+    }
+}
+
+// Backup copies plus the catalog that ties each copy to its file and token
+pub struct BackupStore {
+    policy: BackupPolicy,
+    catalog: Arc<dyn BackupRepository>,
+}
+
+impl BackupStore {
+    pub fn new(policy: BackupPolicy, catalog: Arc<dyn BackupRepository>) -> Self {
+        Self { policy, catalog }
+    }
+
+    // Copy the file aside before it is modified; None when backups are disabled
+    pub async fn create(
+        &self,
+        file_path: &Path,
+        token_id: Option<Uuid>,
+        operation: BackupOperation,
+    ) -> RedTokenResult<Option<BackupRecord>> {
+        self.create_keeping(file_path, token_id, operation, None)
+            .await
+    }
+
+    // Like create, but retention never removes the backup `keep`
+    async fn create_keeping(
+        &self,
+        file_path: &Path,
+        token_id: Option<Uuid>,
+        operation: BackupOperation,
+        keep: Option<Uuid>,
+    ) -> RedTokenResult<Option<BackupRecord>> {
+        if !self.policy.enabled {
+            return Ok(None);
+        }
+
+        let original_path = tokio::fs::canonicalize(file_path)
+            .await
+            .map_err(read_error(file_path))?;
+        let metadata = tokio::fs::metadata(&original_path)
+            .await
+            .map_err(read_error(&original_path))?;
+        let content = tokio::fs::read(&original_path)
+            .await
+            .map_err(read_error(&original_path))?;
+
+        let filename = original_path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        // Backups hold the same secrets as the files, so only the owner may read them
+        let backup_dir = self.policy.directory.as_path();
+        tokio::fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(backup_dir)
+            .await
+            .map_err(write_error(backup_dir))?;
+        // Recorded absolute so the catalog works from any working directory
+        let backup_dir = tokio::fs::canonicalize(backup_dir)
+            .await
+            .map_err(read_error(backup_dir))?;
+
+        // The id in the name keeps same-named files from different directories apart
+        let id = Uuid::new_v4();
+        let mut backup_filename = format!(
+            "{}_{}_{}",
+            chrono::Local::now().format(BACKUP_TIMESTAMP),
+            &id.simple().to_string()[..8],
+            filename
+        );
+        if self.policy.compress {
+            backup_filename.push_str(".gz");
+        }
+        let backup_path = backup_dir.join(&backup_filename);
+
+        let stored = if self.policy.compress {
+            gzip(&content).map_err(write_error(&backup_path))?
+        } else {
+            content.clone()
+        };
+        let mut file = tokio::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&backup_path)
+            .await
+            .map_err(write_error(&backup_path))?;
+        file.write_all(&stored)
+            .await
+            .map_err(write_error(&backup_path))?;
+        file.flush().await.map_err(write_error(&backup_path))?;
+
+        let record = BackupRecord {
+            id,
+            token_id,
+            operation,
+            original_path,
+            backup_path,
+            sha256: sha256_hex(&content),
+            size: content.len() as u64,
+            compressed: self.policy.compress,
+            created_at: SystemTime::now(),
+            mode: Some(metadata.mode()),
+            uid: Some(metadata.uid()),
+            gid: Some(metadata.gid()),
+            modified: metadata.modified().ok(),
+        };
+        self.catalog.save(&record).await.map_err(catalog_error)?;
+        debug!(
+            "Created backup {} of {:?} at {:?}",
+            record.id, record.original_path, record.backup_path
+        );
+
+        self.prune_keeping(Some(&record.original_path), keep)
+            .await?;
+        Ok(Some(record))
+    }
+
+    // Newest first
+    pub async fn list(&self) -> RedTokenResult<Vec<BackupRecord>> {
+        let mut records = self.catalog.find_all().await.map_err(catalog_error)?;
+        records.sort_by_key(|record| std::cmp::Reverse(record.created_at));
+        Ok(records)
+    }
+
+    // A backup id, or a token id for the copy taken right before that token was injected
+    pub async fn find(&self, target: Uuid) -> RedTokenResult<BackupRecord> {
+        if let Some(record) = self
+            .catalog
+            .find_by_id(target)
+            .await
+            .map_err(catalog_error)?
+        {
+            return Ok(record);
+        }
+
+        let records: Vec<BackupRecord> = self
+            .list()
+            .await?
+            .into_iter()
+            .filter(|record| record.token_id == Some(target))
+            .collect();
+
+        records
+            .iter()
+            .find(|record| record.operation == BackupOperation::Inject)
+            .or_else(|| records.first())
+            .cloned()
+            .ok_or_else(|| RedTokenError::BackupNotFound(target.to_string()))
+    }
+
+    // Backup content, decompressed and checked against the recorded hash
+    pub async fn read(&self, record: &BackupRecord) -> RedTokenResult<Vec<u8>> {
+        let stored = tokio::fs::read(&record.backup_path)
+            .await
+            .map_err(read_error(&record.backup_path))?;
+        let content = if record.compressed {
+            gunzip(&stored).map_err(read_error(&record.backup_path))?
+        } else {
+            stored
+        };
+
+        if sha256_hex(&content) != record.sha256 {
+            return Err(RedTokenError::InvalidFileFormat(format!(
+                "Backup {:?} does not match its recorded hash",
+                record.backup_path
+            )));
+        }
+        Ok(content)
+    }
+
+    // Unified diff from the file as it is now to the backup; None when they are identical
+    pub async fn preview(&self, record: &BackupRecord) -> RedTokenResult<Option<String>> {
+        let backup = self.read(record).await?;
+        let current = match tokio::fs::read(&record.original_path).await {
+            Ok(current) => current,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(read_error(&record.original_path)(e)),
+        };
+
+        if current == backup {
+            return Ok(None);
+        }
+
+        let (Ok(current), Ok(backup)) =
+            (std::str::from_utf8(&current), std::str::from_utf8(&backup))
+        else {
+            return Ok(Some(format!(
+                "Binary content of {:?} differs\n",
+                record.original_path
+            )));
+        };
+
+        let path = record.original_path.display().to_string();
+        Ok(Some(unified_diff(
+            current,
+            backup,
+            &format!("{} (current)", path),
+            &format!("{} (backup)", path),
+        )))
+    }
+
+    // Put the backup back in place with its original permissions, owner and mtime.
+    // The current content is backed up first so the restore can be undone.
+    pub async fn restore(&self, record: &BackupRecord) -> RedTokenResult<Option<BackupRecord>> {
+        let content = self.read(record).await?;
+        let path = record.original_path.as_path();
+
+        // The backup being restored must survive the retention run of the undo copy
+        let undo = if path.exists() {
+            self.create_keeping(
+                path,
+                record.token_id,
+                BackupOperation::Restore,
+                Some(record.id),
+            )
+            .await?
+        } else {
+            None
+        };
+
+        // Same atomic, link-preserving write as injection; a missing file is recreated
+        TextFile::read_binary(&path.to_string_lossy(), &WritePolicy::default())
+            .await?
+            .write_bytes(content)
+            .await?;
+        Self::apply_metadata(record).map_err(write_error(path))?;
+
+        info!("Restored {:?} from backup {}", path, record.id);
+        Ok(undo)
+    }
+
+    fn apply_metadata(record: &BackupRecord) -> std::io::Result<()> {
+        let path = record.original_path.as_path();
+
+        if let Some(mode) = record.mode {
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode & 0o7777))?;
+        }
+
+        // Changing the owner needs privileges; the content matters more than the owner
+        if let Err(e) = std::os::unix::fs::chown(path, record.uid, record.gid) {
+            warn!("Could not restore ownership of {:?}: {}", path, e);
+        }
+
+        if let Some(modified) = record.modified {
+            std::fs::File::options()
+                .write(true)
+                .open(path)?
+                .set_modified(modified)?;
+        }
+        Ok(())
+    }
+
+    // Apply the retention policy to one file's backups, or to every file when None.
+    // Returns how many backups were removed.
+    pub async fn prune(&self, original_path: Option<&Path>) -> RedTokenResult<usize> {
+        self.prune_keeping(original_path, None).await
+    }
+
+    async fn prune_keeping(
+        &self,
+        original_path: Option<&Path>,
+        keep: Option<Uuid>,
+    ) -> RedTokenResult<usize> {
+        if self.policy.retention_count.is_none() && self.policy.retention_days.is_none() {
+            return Ok(0);
+        }
+
+        let mut by_file: HashMap<PathBuf, Vec<BackupRecord>> = HashMap::new();
+        for record in self.list().await? {
+            if original_path.is_none_or(|path| path == record.original_path) {
+                by_file
+                    .entry(record.original_path.clone())
+                    .or_default()
+                    .push(record);
+            }
+        }
+
+        let cutoff = self
+            .policy
+            .retention_days
+            .map(|days| SystemTime::now() - Duration::from_secs(days * 24 * 60 * 60));
+        let mut removed = 0;
+
+        for records in by_file.values() {
+            for (index, record) in records.iter().enumerate() {
+                let over_count = self
+                    .policy
+                    .retention_count
+                    .is_some_and(|count| index >= count);
+                let too_old = cutoff.is_some_and(|cutoff| record.created_at < cutoff);
+                if (!over_count && !too_old) || keep == Some(record.id) {
+                    continue;
+                }
+
+                match tokio::fs::remove_file(&record.backup_path).await {
+                    Ok(()) => {}
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                    Err(e) => return Err(write_error(&record.backup_path)(e)),
+                }
+                self.catalog
+                    .delete(record.id)
+                    .await
+                    .map_err(catalog_error)?;
+                debug!("Pruned old backup {:?}", record.backup_path);
+                removed += 1;
+            }
+        }
+
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infrastructure::repository::InMemoryBackupRepository;
+
+    fn mode(path: &Path) -> u32 {
+        std::fs::metadata(path).unwrap().mode() & 0o777
+    }
+
+    #[tokio::test]
+    async fn backups_of_private_files_stay_private() {
+        let dir = tempfile::tempdir().unwrap();
+        let credentials = dir.path().join("credentials");
+        std::fs::write(&credentials, "aws_secret_access_key = real\n").unwrap();
+        std::fs::set_permissions(&credentials, std::fs::Permissions::from_mode(0o600)).unwrap();
+
+        for compress in [false, true] {
+            let policy = BackupPolicy {
+                directory: dir.path().join("backups"),
+                compress,
+                ..BackupPolicy::default()
+            };
+            let store = BackupStore::new(policy, Arc::new(InMemoryBackupRepository::new()));
+            let record = store
+                .create(&credentials, None, BackupOperation::Inject)
+                .await
+                .unwrap()
+                .unwrap();
+
+            assert_eq!(mode(&record.backup_path), 0o600);
+            assert_eq!(mode(&dir.path().join("backups")), 0o700);
+            assert_eq!(
+                store.read(&record).await.unwrap(),
+                b"aws_secret_access_key = real\n"
+            );
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use crate::core::token::{Honeytoken, Severity};
+use crate::infrastructure::syslog::{self, trigger_time};
+use chrono::SecondsFormat;
+use serde_json::{json, Map, Value};
+
+// Elastic's detection rule scale for low/medium/high/critical
+fn ecs_severity(severity: Severity) -> u8 {
+    match severity {
+        Severity::Low => 21,
+        Severity::Medium => 47,
+        Severity::High => 73,
+        Severity::Critical => 99,
+    }
+}
+
+// Structured alert event using Elastic Common Schema field names
+pub fn event_document(token: &Honeytoken) -> Value {
+    let trigger = token.last_trigger.clone().unwrap_or_default();
+
+    let mut doc = json!({
+        "@timestamp": trigger_time(token).to_rfc3339_opts(SecondsFormat::Millis, true),
+        "message": format!("Honeytoken {} triggered", token.id),
+        "event": {
+            "kind": "alert",
+            "category": ["intrusion_detection"],
+            "type": ["indicator"],
+            "action": "honeytoken-triggered",
+            "severity": ecs_severity(token.severity),
+        },
+        "file": {
+            "path": token.file_path,
+        },
+        "host": {
+            "hostname": syslog::hostname(),
+        },
+        "labels": {
+            "token_id": token.id.to_string(),
+            "token_severity": token.severity.to_string(),
+        },
+        "observer": {
+            "vendor": "RedToken",
+            "product": "RedToken",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+    });
+
+    let fields = doc.as_object_mut().expect("ECS document is an object");
+    if let Some(source_ip) = trigger.source_ip {
+        fields.insert("source".to_string(), json!({ "ip": source_ip }));
+    }
+    if let Some(user_agent) = trigger.user_agent {
+        fields.insert("user_agent".to_string(), json!({ "original": user_agent }));
+    }
+    if !token.tags.is_empty() {
+        fields.insert("tags".to_string(), json!(token.tags));
+    }
+    if let Some(kind) = &token.kind {
+        if let Some(Value::Object(labels)) = fields.get_mut("labels") {
+            labels.insert("token_kind".to_string(), Value::String(kind.clone()));
+        }
+    }
+
+    doc
+}
+
+// Splunk HEC envelope around the ECS event
+pub fn hec_event(token: &Honeytoken, index: Option<&str>, sourcetype: Option<&str>) -> Value {
+    let mut envelope = Map::new();
+    envelope.insert(
+        "time".to_string(),
+        json!(trigger_time(token).timestamp_millis() as f64 / 1000.0),
+    );
+    envelope.insert("host".to_string(), json!(syslog::hostname()));
+    envelope.insert("source".to_string(), json!("redtoken"));
+    envelope.insert(
+        "sourcetype".to_string(),
+        json!(sourcetype.unwrap_or("redtoken:alert")),
+    );
+    if let Some(index) = index {
+        envelope.insert("index".to_string(), json!(index));
+    }
+    envelope.insert("event".to_string(), event_document(token));
+    Value::Object(envelope)
+}
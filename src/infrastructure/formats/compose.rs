@@ -0,0 +1,311 @@
+use super::{
+    indent_of, indentation, ini::join, pick_name, redact, yaml_block, Rendered, ENV_NAMES,
+};
+use crate::core::{
+    error::{RedTokenError, RedTokenResult},
+    token::Honeytoken,
+};
+
+// A service under `services:`, by line range
+struct Service {
+    name: String,
+    start: usize,
+    end: usize,
+}
+
+// `environment:` of a service; entries are `- NAME=value` or `NAME: value`
+struct Environment {
+    header: usize,
+    end: usize,
+    entries: Vec<(usize, String, String)>,
+}
+
+fn unquote(value: &str) -> &str {
+    value.trim().trim_matches('"').trim_matches('\'')
+}
+
+fn services(lines: &[&str]) -> Vec<Service> {
+    let Some(block) = yaml_block(lines, 0, lines.len(), "services") else {
+        return Vec::new();
+    };
+    let Some(indent) = indent_of(lines, block.header + 1, block.end) else {
+        return Vec::new();
+    };
+
+    let starts: Vec<(usize, String)> = (block.header + 1..block.end)
+        .filter_map(|index| {
+            let line = lines[index];
+            if indentation(line) != indent.len() || line.trim_start().starts_with('#') {
+                return None;
+            }
+            let (name, _) = line.trim().split_once(':')?;
+            Some((index, unquote(name).to_string()))
+        })
+        .collect();
+
+    starts
+        .iter()
+        .enumerate()
+        .map(|(position, (start, name))| {
+            let next = starts
+                .get(position + 1)
+                .map_or(block.end, |(next, _)| *next);
+            // Stop at the last line with content, not at blank lines before the next service
+            let end = (start + 1..next)
+                .rev()
+                .find(|&index| !lines[index].trim().is_empty())
+                .map_or(start + 1, |last| last + 1);
+            Service {
+                name: name.clone(),
+                start: *start,
+                end,
+            }
+        })
+        .collect()
+}
+
+// Indentation of the keys inside a service
+fn key_indent(lines: &[&str], service: &Service) -> String {
+    indent_of(lines, service.start + 1, service.end).unwrap_or_else(|| {
+        let own = &lines[service.start][..indentation(lines[service.start])];
+        format!("{}{}", own, if own.is_empty() { "  " } else { own })
+    })
+}
+
+fn environment(lines: &[&str], service: &Service) -> RedTokenResult<Option<Environment>> {
+    let indent = key_indent(lines, service);
+    let Some(header) = (service.start + 1..service.end).find(|&index| {
+        let line = lines[index];
+        indentation(line) == indent.len() && line.trim_start().starts_with("environment:")
+    }) else {
+        return Ok(None);
+    };
+
+    let inline = lines[header].trim_start()["environment:".len()..].trim();
+    if !inline.is_empty() && !inline.starts_with('#') {
+        return Err(RedTokenError::InvalidFileFormat(format!(
+            "Inline environment of service {} is not supported",
+            service.name
+        )));
+    }
+
+    let mut end = header + 1;
+    let mut entries = Vec::new();
+    for (index, line) in lines.iter().enumerate().take(service.end).skip(header + 1) {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        // List items may sit at the same indentation as `environment:` itself
+        let nested = indentation(line) > indent.len()
+            || (indentation(line) == indent.len() && trimmed.starts_with("- "));
+        if !nested {
+            break;
+        }
+        end = index + 1;
+
+        let entry = match trimmed.strip_prefix("- ") {
+            Some(item) => unquote(item)
+                .split_once('=')
+                .map(|(name, value)| (name.to_string(), value.to_string())),
+            None => trimmed
+                .split_once(':')
+                .map(|(name, value)| (unquote(name).to_string(), unquote(value).to_string())),
+        };
+        if let Some((name, value)) = entry {
+            entries.push((index, name, value));
+        }
+    }
+
+    Ok(Some(Environment {
+        header,
+        end,
+        entries,
+    }))
+}
+
+// A variable in the chosen service's environment, in the style it already uses
+pub fn inject(
+    content: &str,
+    token: &Honeytoken,
+    service_name: Option<&str>,
+) -> RedTokenResult<Rendered> {
+    let lines: Vec<&str> = content.lines().collect();
+    let services = services(&lines);
+    let service = match service_name {
+        Some(name) => services.iter().find(|service| service.name == name),
+        None => services.first(),
+    }
+    .ok_or_else(|| {
+        let available: Vec<&str> = services.iter().map(|s| s.name.as_str()).collect();
+        RedTokenError::InvalidFileFormat(match service_name {
+            Some(name) => format!(
+                "No service {} in the compose file (available: {})",
+                name,
+                available.join(", ")
+            ),
+            None => "No services in the compose file".to_string(),
+        })
+    })?;
+
+    let indent = key_indent(&lines, service);
+    let environment = environment(&lines, service)?;
+    let taken: Vec<String> = environment
+        .iter()
+        .flat_map(|environment| environment.entries.iter().map(|(_, name, _)| name.clone()))
+        .collect();
+    let name = pick_name(&ENV_NAMES, &taken);
+
+    let mut output: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+    let placement = match environment {
+        Some(environment) => {
+            let first = environment
+                .entries
+                .first()
+                .map(|(index, _, _)| lines[*index]);
+            let entry_indent = first.map_or_else(
+                || format!("{}  ", indent),
+                |line| line[..indentation(line)].to_string(),
+            );
+            let entry = match first {
+                Some(line) if line.trim_start().starts_with("- ") => {
+                    format!("{}- {}={}", entry_indent, name, token.value)
+                }
+                _ => format!("{}{}: {}", entry_indent, name, token.value),
+            };
+            output.insert(environment.end, entry);
+            format!(
+                "new variable in the environment of service {}",
+                service.name
+            )
+        }
+        None => {
+            output.splice(
+                service.end..service.end,
+                [
+                    format!("{}environment:", indent),
+                    format!("{}  {}: {}", indent, name, token.value),
+                ],
+            );
+            format!("new environment for service {}", service.name)
+        }
+    };
+
+    Ok(Rendered {
+        content: join(&output, content.ends_with('\n') || content.is_empty()),
+        key: Some(name),
+        placement,
+    })
+}
+
+// Delete the variable holding the token, and an `environment:` left empty
+pub fn remove(content: &str, token: &Honeytoken) -> Rendered {
+    let lines: Vec<&str> = content.lines().collect();
+    for service in services(&lines) {
+        let Ok(Some(environment)) = environment(&lines, &service) else {
+            continue;
+        };
+        let Some((index, name, _)) = environment
+            .entries
+            .iter()
+            .find(|(_, _, value)| *value == token.value)
+        else {
+            continue;
+        };
+
+        let mut output: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+        output.remove(*index);
+        // A header with a comment on it was there before the decoy
+        if environment.entries.len() == 1 && lines[environment.header].trim() == "environment:" {
+            output.remove(environment.header);
+        }
+        return Rendered {
+            content: join(&output, content.ends_with('\n')),
+            key: Some(name.clone()),
+            placement: format!("removed {} from service {}", name, service.name),
+        };
+    }
+    redact(content, token)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMPOSE: &str = "\
+services:
+  web:
+    image: nginx:1.25
+    environment:
+      - NGINX_PORT=80
+      - TZ=UTC
+
+  worker:
+    build: .
+    environment:
+      QUEUE: jobs
+      # retries
+      RETRIES: \"3\"
+    depends_on: [web]
+
+volumes:
+  data: {}
+";
+
+    fn round_trip(original: &str, service: Option<&str>) -> String {
+        let token = Honeytoken::new("RT_compose_decoy".to_string(), "compose.yaml".to_string());
+        let injected = inject(original, &token, service).unwrap();
+        let removed = remove(&injected.content, &token);
+        assert_eq!(removed.content, original);
+        assert_eq!(removed.key, injected.key);
+        injected.content
+    }
+
+    // The service's environment, as compose reads it
+    fn environment(content: &str, service: &str) -> serde_yaml::Value {
+        let compose: serde_yaml::Value = serde_yaml::from_str(content).unwrap();
+        compose["services"][service]["environment"].clone()
+    }
+
+    #[test]
+    fn list_style_environment_gets_a_list_item() {
+        let injected = round_trip(COMPOSE, None);
+        let items = environment(&injected, "web");
+        let items = items.as_sequence().unwrap();
+        assert_eq!(items.len(), 3);
+        assert!(items[2].as_str().unwrap().ends_with("=RT_compose_decoy"));
+    }
+
+    #[test]
+    fn map_style_environment_gets_a_key() {
+        let injected = round_trip(COMPOSE, Some("worker"));
+        let variables = environment(&injected, "worker");
+        let variables = variables.as_mapping().unwrap();
+        assert_eq!(variables.len(), 3);
+        assert!(variables
+            .values()
+            .any(|value| value.as_str() == Some("RT_compose_decoy")));
+        // The next key of the service is untouched
+        assert!(injected.contains("RT_compose_decoy\n    depends_on: [web]"));
+    }
+
+    #[test]
+    fn services_without_environment_get_one() {
+        let compose = "services:\n  db:\n    image: postgres:16\n\n  cache:\n    image: redis:7";
+        let injected = round_trip(compose, Some("db"));
+        assert_eq!(environment(&injected, "db").as_mapping().unwrap().len(), 1);
+        round_trip(compose, Some("cache"));
+        // An empty environment with a comment keeps its header
+        round_trip(
+            "services:\n  app:\n    environment: # set in CI\n    image: app\n",
+            None,
+        );
+    }
+
+    #[test]
+    fn unknown_services_are_reported() {
+        let token = Honeytoken::new("RT_compose_decoy".to_string(), "compose.yaml".to_string());
+        let error = inject(COMPOSE, &token, Some("api")).err().unwrap();
+        assert!(error.to_string().contains("available: web, worker"));
+    }
+}
@@ -0,0 +1,281 @@
+use super::{pick_name, redact, require_canary, Rendered};
+use crate::core::{
+    error::{RedTokenError, RedTokenResult},
+    token::Honeytoken,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use regex::Regex;
+
+const USERS: [&str; 4] = ["ci-robot", "deploy", "svc-registry", "jenkins"];
+
+// Registry key for the canary: docker keys registries by host[:port], without a scheme
+fn registry(canary_url: &str) -> String {
+    let without_scheme = canary_url
+        .split_once("://")
+        .map_or(canary_url, |(_, rest)| rest);
+    without_scheme
+        .split('/')
+        .next()
+        .unwrap_or(without_scheme)
+        .to_string()
+}
+
+// Indentation of the first indented line; the docker CLI writes tabs
+fn indent_unit(content: &str) -> String {
+    content
+        .lines()
+        .find(|line| line.starts_with([' ', '\t']))
+        .map(|line| line[..line.len() - line.trim_start().len()].to_string())
+        .unwrap_or_else(|| "\t".to_string())
+}
+
+// Index of the closing quote of the string opening at `start`
+pub(super) fn string_end(bytes: &[u8], start: usize) -> Option<usize> {
+    let mut index = start + 1;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'\\' => index += 2,
+            b'"' => return Some(index),
+            _ => index += 1,
+        }
+    }
+    None
+}
+
+// Byte offsets of the root object's `{` and of the `{` opening top-level `key`
+fn object_starts(content: &str, key: &str) -> (Option<usize>, Option<usize>) {
+    let bytes = content.as_bytes();
+    let (mut root, mut depth, mut index) = (None, 0, 0);
+    while index < bytes.len() {
+        match bytes[index] {
+            b'"' => {
+                let Some(end) = string_end(bytes, index) else {
+                    break;
+                };
+                if depth == 1 && &content[index + 1..end] == key {
+                    let value = content[end + 1..]
+                        .trim_start()
+                        .strip_prefix(':')
+                        .map(str::trim_start)
+                        .filter(|value| value.starts_with('{'));
+                    if let Some(value) = value {
+                        return (root, Some(content.len() - value.len()));
+                    }
+                }
+                index = end;
+            }
+            b'{' | b'[' => {
+                if depth == 0 {
+                    root = Some(index);
+                }
+                depth += 1;
+            }
+            b'}' | b']' => depth -= 1,
+            _ => {}
+        }
+        index += 1;
+    }
+    (root, None)
+}
+
+// `{` at `open` followed by nothing but whitespace and `}`: offset after the `}`
+fn empty_object_end(content: &str, open: usize) -> Option<usize> {
+    let rest = &content[open + 1..];
+    let trimmed = rest.trim_start();
+    trimmed
+        .starts_with('}')
+        .then(|| content.len() - trimmed.len() + 1)
+}
+
+// An `auths` entry for the canary registry with `user:token` as basic auth.
+// `docker pull <registry>/...` then presents the token to the monitoring server.
+pub fn inject(
+    content: &str,
+    token: &Honeytoken,
+    canary_url: Option<&str>,
+) -> RedTokenResult<Rendered> {
+    let canary_url = require_canary(canary_url, "Docker")?;
+    if !content.trim().is_empty() {
+        let config: serde_json::Value = serde_json::from_str(content)
+            .map_err(|e| RedTokenError::InvalidFileFormat(format!("Invalid JSON: {}", e)))?;
+        if !config.is_object() {
+            return Err(RedTokenError::InvalidFileFormat(
+                "Docker config root is not an object".to_string(),
+            ));
+        }
+    }
+
+    let registry = registry(canary_url);
+    let user = pick_name(&USERS, &[]);
+    let auth = STANDARD.encode(format!("{}:{}", user, token.value));
+    let i = indent_unit(content);
+    let entry = |level: usize| {
+        let outer = i.repeat(level);
+        let inner = i.repeat(level + 1);
+        format!(
+            "\"{}\": {{\n{}\"auth\": \"{}\"\n{}}}",
+            registry, inner, auth, outer
+        )
+    };
+
+    let mut output = content.to_string();
+    let placement = match object_starts(content, "auths") {
+        (_, Some(open)) => match empty_object_end(content, open) {
+            Some(close) => {
+                output.replace_range(
+                    open..close,
+                    &format!("{{\n{}{}\n{}}}", i.repeat(2), entry(2), i),
+                );
+                format!("new auths entry for {}", registry)
+            }
+            None => {
+                output.insert_str(open + 1, &format!("\n{}{},", i.repeat(2), entry(2)));
+                format!("new auths entry for {} before the existing ones", registry)
+            }
+        },
+        (Some(root), None) => {
+            let auths = format!("\"auths\": {{\n{}{}\n{}}}", i.repeat(2), entry(2), i);
+            match empty_object_end(content, root) {
+                Some(close) => {
+                    output.replace_range(root..close, &format!("{{\n{}{}\n}}", i, auths))
+                }
+                None => output.insert_str(root + 1, &format!("\n{}{},", i, auths)),
+            }
+            format!("new auths section with an entry for {}", registry)
+        }
+        (None, None) => {
+            output = format!(
+                "{{\n{}\"auths\": {{\n{}{}\n{}}}\n}}\n",
+                i,
+                i.repeat(2),
+                entry(2),
+                i
+            );
+            format!("new auths section with an entry for {}", registry)
+        }
+    };
+
+    Ok(Rendered {
+        content: output,
+        key: Some(registry),
+        placement,
+    })
+}
+
+// The `auth` value that decodes to `<user>:<token>`
+fn encoded_auth(content: &str, token: &Honeytoken) -> Option<String> {
+    let suffix = format!(":{}", token.value);
+    let auth = Regex::new(r#""auth"\s*:\s*"([A-Za-z0-9+/=]+)""#).expect("valid regex");
+    let found = auth
+        .captures_iter(content)
+        .map(|captures| captures[1].to_string())
+        .find(|encoded| {
+            STANDARD
+                .decode(encoded)
+                .ok()
+                .and_then(|decoded| String::from_utf8(decoded).ok())
+                .is_some_and(|decoded| decoded.ends_with(&suffix))
+        });
+    found
+}
+
+// Undo the shapes `inject` produces; the last one covers an entry docker itself
+// moved to the end of `auths`. An emptied `auths` stays as `{}`, which is how
+// docker writes it too.
+pub fn remove(content: &str, token: &Honeytoken) -> Rendered {
+    let Some(auth) = encoded_auth(content, token) else {
+        return redact(content, token);
+    };
+
+    let entry = format!(
+        r#""(?P<registry>[^"]*)"\s*:\s*\{{\s*"auth"\s*:\s*"{}"\s*\}}"#,
+        regex::escape(&auth)
+    );
+    let shapes = [
+        (format!(r#"\n[ \t]*{},"#, entry), ""),
+        (format!(r#"\{{\s*{}\s*\}}"#, entry), "{}"),
+        (format!(r#",\s*{}"#, entry), ""),
+    ];
+
+    for (pattern, replacement) in shapes {
+        let pattern = Regex::new(&pattern).expect("valid regex");
+        if let Some(captures) = pattern.captures(content) {
+            let registry = captures["registry"].to_string();
+            return Rendered {
+                content: pattern.replace(content, replacement).into_owned(),
+                placement: format!("removed auths entry for {}", registry),
+                key: Some(registry),
+            };
+        }
+    }
+    redact(content, token)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CANARY: Option<&str> = Some("https://canary.example.com:8443/base");
+
+    fn token() -> Honeytoken {
+        Honeytoken::new("RT_docker_decoy".to_string(), "config.json".to_string())
+    }
+
+    // Inject, check docker would send `user:token` to the canary registry, then remove
+    fn round_trip(original: &str) -> String {
+        let token = token();
+        let injected = inject(original, &token, CANARY).unwrap();
+        assert_eq!(injected.key.as_deref(), Some("canary.example.com:8443"));
+
+        let config: serde_json::Value = serde_json::from_str(&injected.content).unwrap();
+        let auth = config["auths"]["canary.example.com:8443"]["auth"]
+            .as_str()
+            .unwrap();
+        let decoded = String::from_utf8(STANDARD.decode(auth).unwrap()).unwrap();
+        assert!(decoded.ends_with(":RT_docker_decoy"));
+
+        remove(&injected.content, &token).content
+    }
+
+    #[test]
+    fn removal_restores_existing_auths() {
+        // As the docker CLI writes it, with tabs
+        let config = "{\n\t\"auths\": {\n\t\t\"ghcr.io\": {\n\t\t\t\"auth\": \"dXNlcjpyZWFs\"\n\t\t}\n\t},\n\t\"credsStore\": \"desktop\"\n}";
+        assert_eq!(round_trip(config), config);
+        let empty = "{\n  \"auths\": {},\n  \"currentContext\": \"default\"\n}\n";
+        assert_eq!(round_trip(empty), empty);
+    }
+
+    #[test]
+    fn a_new_auths_section_is_left_empty() {
+        // Docker itself always writes `auths`, so an emptied one stays
+        let config = "{\n  \"credsStore\": \"osxkeychain\"\n}\n";
+        assert_eq!(
+            round_trip(config),
+            "{\n  \"auths\": {},\n  \"credsStore\": \"osxkeychain\"\n}\n"
+        );
+        assert_eq!(round_trip("{}"), "{\n\t\"auths\": {}\n}");
+        assert_eq!(round_trip(""), "{\n\t\"auths\": {}\n}\n");
+    }
+
+    #[test]
+    fn an_entry_docker_moved_to_the_end_is_found() {
+        let token = token();
+        let auth = STANDARD.encode("deploy:RT_docker_decoy");
+        let moved = format!(
+            "{{\n\t\"auths\": {{\n\t\t\"ghcr.io\": {{\n\t\t\t\"auth\": \"dXNlcjpyZWFs\"\n\t\t}},\n\t\t\"canary.example.com:8443\": {{\n\t\t\t\"auth\": \"{}\"\n\t\t}}\n\t}}\n}}",
+            auth
+        );
+        let removed = remove(&moved, &token);
+        assert_eq!(
+            removed.content,
+            "{\n\t\"auths\": {\n\t\t\"ghcr.io\": {\n\t\t\t\"auth\": \"dXNlcjpyZWFs\"\n\t\t}\n\t}\n}"
+        );
+    }
+
+    #[test]
+    fn docker_configs_need_a_canary() {
+        assert!(inject("{}", &token(), None).is_err());
+        assert!(inject("[]", &token(), CANARY).is_err());
+    }
+}
@@ -0,0 +1,180 @@
+use super::{ini::join, pick_name, redact, Rendered, ENV_NAMES};
+use crate::core::token::Honeytoken;
+
+// One instruction, spanning its `\` continuation lines
+struct Instruction {
+    keyword: String,
+    start: usize,
+    end: usize, // Last line
+}
+
+fn instructions(lines: &[&str]) -> Vec<Instruction> {
+    let mut instructions = Vec::new();
+    let mut index = 0;
+    while index < lines.len() {
+        let trimmed = lines[index].trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            index += 1;
+            continue;
+        }
+        let start = index;
+        while lines[index].trim_end().ends_with('\\') && index + 1 < lines.len() {
+            index += 1;
+        }
+        instructions.push(Instruction {
+            keyword: trimmed
+                .split_whitespace()
+                .next()
+                .unwrap_or_default()
+                .to_ascii_uppercase(),
+            start,
+            end: index,
+        });
+        index += 1;
+    }
+    instructions
+}
+
+// Names set by ENV and ARG, in both `NAME=value` and `ENV NAME value` forms
+fn defined_names(lines: &[&str], instructions: &[Instruction]) -> Vec<String> {
+    instructions
+        .iter()
+        .filter(|instruction| instruction.keyword == "ENV" || instruction.keyword == "ARG")
+        .flat_map(|instruction| {
+            let text = lines[instruction.start..=instruction.end].join(" ");
+            let words: Vec<String> = text
+                .split_whitespace()
+                .skip(1)
+                .filter(|word| *word != "\\")
+                .map(str::to_string)
+                .collect();
+            if words.first().is_some_and(|word| !word.contains('=')) {
+                return words.into_iter().take(1).collect::<Vec<_>>();
+            }
+            words
+                .into_iter()
+                .filter_map(|word| word.split_once('=').map(|(name, _)| name.to_string()))
+                .collect()
+        })
+        .collect()
+}
+
+// An ENV line in the final stage, next to its other ENV/ARG lines; ARG when
+// the stage only uses build arguments
+pub fn inject(content: &str, token: &Honeytoken) -> Rendered {
+    let lines: Vec<&str> = content.lines().collect();
+    let instructions = instructions(&lines);
+    let stage = instructions
+        .iter()
+        .rposition(|instruction| instruction.keyword == "FROM");
+    let stage_instructions = &instructions[stage.unwrap_or(0)..];
+
+    let variables: Vec<&Instruction> = stage_instructions
+        .iter()
+        .filter(|instruction| instruction.keyword == "ENV" || instruction.keyword == "ARG")
+        .collect();
+    let keyword = if !variables.is_empty() && variables.iter().all(|i| i.keyword == "ARG") {
+        "ARG"
+    } else {
+        "ENV"
+    };
+
+    let name = pick_name(&ENV_NAMES, &defined_names(&lines, &instructions));
+    let at = match (variables.last(), stage) {
+        (Some(variable), _) => variable.end + 1,
+        (None, Some(from)) => instructions[from].end + 1,
+        (None, None) => instructions.last().map_or(0, |last| last.end + 1),
+    };
+
+    let mut output: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+    output.insert(at, format!("{} {}={}", keyword, name, token.value));
+
+    let stage_name = stage.map_or_else(
+        || "the file".to_string(),
+        |from| format!("the stage {}", lines[instructions[from].start].trim()),
+    );
+    Rendered {
+        content: join(&output, content.ends_with('\n') || content.is_empty()),
+        key: Some(name),
+        placement: format!("new {} after line {} in {}", keyword, at, stage_name),
+    }
+}
+
+// Delete a single-variable ENV/ARG line setting the token
+pub fn remove(content: &str, token: &Honeytoken) -> Rendered {
+    let lines: Vec<&str> = content.lines().collect();
+    let found = lines.iter().enumerate().find_map(|(index, line)| {
+        let mut words = line.split_whitespace();
+        let keyword = words.next()?.to_ascii_uppercase();
+        if keyword != "ENV" && keyword != "ARG" {
+            return None;
+        }
+        let rest: Vec<&str> = words.collect();
+        let (name, value) = match rest.as_slice() {
+            [assignment] => assignment.split_once('=')?,
+            [name, value] if !name.contains('=') => (*name, *value),
+            _ => return None,
+        };
+        (value.trim_matches('"').trim_matches('\'') == token.value).then_some((index, name))
+    });
+
+    let Some((index, name)) = found else {
+        return redact(content, token);
+    };
+    let mut output: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+    output.remove(index);
+    Rendered {
+        content: join(&output, content.ends_with('\n')),
+        key: Some(name.to_string()),
+        placement: format!("removed {} from line {}", name, index + 1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(original: &str) -> String {
+        let token = Honeytoken::new("RT_dockerfile_decoy".to_string(), "Dockerfile".to_string());
+        let injected = inject(original, &token);
+        let removed = remove(&injected.content, &token);
+        assert_eq!(removed.content, original);
+        assert_eq!(removed.key, injected.key);
+        injected.content
+    }
+
+    // Line the decoy landed on
+    fn decoy_line(content: &str) -> (usize, &str) {
+        content
+            .lines()
+            .enumerate()
+            .find(|(_, line)| line.contains("RT_dockerfile_decoy"))
+            .unwrap()
+    }
+
+    #[test]
+    fn env_goes_after_the_final_stage_variables() {
+        let dockerfile = "FROM golang:1.22 AS build\nENV CGO_ENABLED=0\nRUN go build ./...\n\n\
+                          FROM alpine:3.19\nENV TZ=UTC \\\n    LANG=C.UTF-8\nCOPY --from=build /app /app\n\
+                          CMD [\"/app\"]\n";
+        let injected = round_trip(dockerfile);
+        let (index, line) = decoy_line(&injected);
+        assert_eq!(index, 7);
+        assert!(line.starts_with("ENV "));
+    }
+
+    #[test]
+    fn arg_only_stages_get_an_arg() {
+        let injected = round_trip("FROM node:20\nARG NODE_ENV=production\nRUN npm ci\n");
+        assert_eq!(decoy_line(&injected).0, 2);
+        assert!(decoy_line(&injected).1.starts_with("ARG "));
+    }
+
+    #[test]
+    fn stages_without_variables_get_one_after_from() {
+        let injected =
+            round_trip("# syntax=docker/dockerfile:1\nFROM python:3.12-slim\nRUN pip install app");
+        assert_eq!(decoy_line(&injected).0, 2);
+        round_trip("");
+    }
+}
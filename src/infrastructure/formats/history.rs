@@ -0,0 +1,382 @@
+use super::{
+    commands::{CommandLibrary, HistoryContext},
+    ini::join,
+    redact, Rendered,
+};
+use crate::core::{
+    error::{RedTokenError, RedTokenResult},
+    injection::FileType,
+    token::Honeytoken,
+};
+use chrono::{TimeZone, Utc};
+use rand::{thread_rng, Rng};
+
+// How far back from the most recent entry the decoy may land
+const MAX_DEPTH: usize = 30;
+
+// Header of history files written by libedit (psql and mysql on macOS and Debian)
+const LIBEDIT_HEADER: &str = "_HiStOrY_V2_";
+
+// One history entry by line range, with its timestamp when the format has one
+struct Entry {
+    start: usize,
+    end: usize,
+    time: Option<i64>,
+}
+
+// Whether `line` carries on the entry of the line before it
+fn continues(file_type: &FileType, previous: &str, line: &str) -> bool {
+    match file_type {
+        FileType::ZshHistory => previous.ends_with('\\'),
+        FileType::FishHistory => line.starts_with(' '),
+        FileType::PowerShellHistory => previous.ends_with('`'),
+        _ => false,
+    }
+}
+
+// `: <start>:<elapsed>;command`, zsh's EXTENDED_HISTORY format
+fn zsh_time(line: &str) -> Option<i64> {
+    let (time, _) = line.strip_prefix(": ")?.split_once(':')?;
+    time.parse().ok()
+}
+
+// `#<seconds>` before the command, when bash runs with HISTTIMEFORMAT set
+fn bash_time(line: &str) -> Option<i64> {
+    let time = line.strip_prefix('#')?;
+    (time.len() >= 9 && time.bytes().all(|byte| byte.is_ascii_digit()))
+        .then(|| time.parse().ok())
+        .flatten()
+}
+
+fn entries(lines: &[&str], file_type: &FileType) -> Vec<Entry> {
+    let mut entries: Vec<Entry> = Vec::new();
+    let mut pending_time = None; // A bash timestamp line waiting for its command
+    for (index, line) in lines.iter().enumerate() {
+        if index == 0 && *line == LIBEDIT_HEADER {
+            continue;
+        }
+        if *file_type == FileType::BashHistory {
+            if let Some(time) = bash_time(line) {
+                pending_time = Some((index, time));
+                continue;
+            }
+        }
+        if let Some(last) = entries.last_mut() {
+            if last.end == index && continues(file_type, lines[index - 1], line) {
+                last.end = index + 1;
+                if let Some(when) = line.trim().strip_prefix("when:") {
+                    last.time = when.trim().parse().ok();
+                }
+                continue;
+            }
+        }
+        let (start, time) = match (file_type, pending_time.take()) {
+            (FileType::BashHistory, Some((start, time))) => (start, Some(time)),
+            (FileType::ZshHistory, _) => (index, zsh_time(line)),
+            _ => (index, None),
+        };
+        entries.push(Entry {
+            start,
+            end: index + 1,
+            time,
+        });
+    }
+    entries
+}
+
+// The command an entry holds, without timestamps and encoding
+fn command_text(lines: &[&str], entry: &Entry, file_type: &FileType, libedit: bool) -> String {
+    let lines = &lines[entry.start..entry.end];
+    match file_type {
+        FileType::BashHistory => lines
+            .iter()
+            .filter(|line| bash_time(line).is_none())
+            .copied()
+            .collect::<Vec<_>>()
+            .join("\n"),
+        FileType::ZshHistory => {
+            let text = lines.join("\n");
+            match zsh_time(&text) {
+                Some(_) => text
+                    .split_once(';')
+                    .map_or(text.clone(), |(_, command)| command.to_string()),
+                None => text,
+            }
+        }
+        FileType::FishHistory => lines
+            .first()
+            .and_then(|line| line.strip_prefix("- cmd: "))
+            .map(|command| command.replace("\\n", "\n").replace("\\\\", "\\"))
+            .unwrap_or_default(),
+        _ if libedit => lines
+            .join("\n")
+            .replace("\\040", " ")
+            .replace("\\134", "\\"),
+        _ => lines.join("\n"),
+    }
+}
+
+// fish writes the command on one line, escaping backslashes and newlines
+fn fish_escape(command: &str) -> String {
+    command.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+// libedit's vis(3) encoding of spaces and backslashes
+fn libedit_escape(command: &str) -> String {
+    command.replace('\\', "\\134").replace(' ', "\\040")
+}
+
+// Lines of the new entry in the file's format
+fn render_entry(
+    file_type: &FileType,
+    command: &str,
+    time: Option<i64>,
+    libedit: bool,
+) -> Vec<String> {
+    match (file_type, time) {
+        (FileType::BashHistory, Some(time)) => vec![format!("#{}", time), command.to_string()],
+        (FileType::ZshHistory, Some(time)) => vec![format!(": {}:0;{}", time, command)],
+        (FileType::FishHistory, time) => vec![
+            format!("- cmd: {}", fish_escape(command)),
+            format!("  when: {}", time.unwrap_or_else(|| Utc::now().timestamp())),
+        ],
+        _ if libedit => vec![libedit_escape(command)],
+        _ => vec![command.to_string()],
+    }
+}
+
+// A time between the entries around the decoy, so the history stays in order
+fn plausible_time(older: Option<i64>, newer: Option<i64>) -> i64 {
+    let mut rng = thread_rng();
+    let now = Utc::now().timestamp();
+    match (older, newer) {
+        (Some(older), Some(newer)) if older < newer => rng.gen_range(older..=newer),
+        (Some(older), Some(_)) => older,
+        (None, Some(newer)) => newer - rng.gen_range(60..3600),
+        (Some(older), None) => (older + rng.gen_range(60..3600)).min(now),
+        (None, None) => now - rng.gen_range(3600..7 * 24 * 3600),
+    }
+}
+
+// A command a few entries back from the most recent one, never as the last thing
+// typed; node keeps its history newest first, the other formats oldest first
+pub fn inject(
+    content: &str,
+    file_type: &FileType,
+    token: &Honeytoken,
+    library: &CommandLibrary,
+    canary_url: Option<&str>,
+) -> RedTokenResult<Rendered> {
+    let lines: Vec<&str> = content.lines().collect();
+    let entries = entries(&lines, file_type);
+    let newest_first = *file_type == FileType::NodeHistory;
+    let libedit = lines.first() == Some(&LIBEDIT_HEADER);
+
+    let context = HistoryContext::from_commands(
+        entries
+            .iter()
+            .map(|entry| command_text(&lines, entry, file_type, libedit))
+            .collect(),
+    );
+    let command = library
+        .choose(file_type, &context, &token.value, canary_url)
+        .ok_or_else(|| {
+            RedTokenError::InvalidFileFormat(format!(
+                "No command template fits a {} history",
+                file_type.name()
+            ))
+        })?;
+
+    // Number of entries that end up more recent than the decoy
+    let count = entries.len();
+    let depth = match count {
+        0 => 0,
+        _ => thread_rng().gen_range(1..=count.min(MAX_DEPTH)),
+    };
+    let (at, older, newer) = if newest_first {
+        let at = match depth {
+            0 => usize::from(libedit),
+            _ => entries[depth - 1].end,
+        };
+        (
+            at,
+            entries.get(depth),
+            depth.checked_sub(1).map(|i| &entries[i]),
+        )
+    } else {
+        let index = count - depth;
+        let at = entries.get(index).map_or(lines.len(), |entry| entry.start);
+        (
+            at,
+            index.checked_sub(1).map(|i| &entries[i]),
+            entries.get(index),
+        )
+    };
+
+    let timed = match file_type {
+        FileType::FishHistory => true,
+        FileType::BashHistory | FileType::ZshHistory => {
+            entries.iter().any(|entry| entry.time.is_some())
+        }
+        _ => false,
+    };
+    let time =
+        timed.then(|| plausible_time(older.and_then(|e| e.time), newer.and_then(|e| e.time)));
+
+    let mut output: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+    output.splice(at..at, render_entry(file_type, &command, time, libedit));
+
+    let mut placement = match depth {
+        0 => "first entry of an empty history".to_string(),
+        _ => format!("followed by {} newer entries", depth),
+    };
+    if let Some(time) = time.and_then(|time| Utc.timestamp_opt(time, 0).single()) {
+        placement.push_str(&format!(", dated {}", time.format("%Y-%m-%d %H:%M:%S UTC")));
+    }
+
+    Ok(Rendered {
+        content: join(&output, content.ends_with('\n') || content.is_empty()),
+        key: None,
+        placement,
+    })
+}
+
+// Delete the entry holding the token, with its timestamp line
+pub fn remove(content: &str, file_type: &FileType, token: &Honeytoken) -> Rendered {
+    let lines: Vec<&str> = content.lines().collect();
+    let entry = entries(&lines, file_type).into_iter().find(|entry| {
+        lines[entry.start..entry.end]
+            .iter()
+            .any(|line| line.contains(&token.value))
+    });
+    let Some(entry) = entry else {
+        return redact(content, token);
+    };
+
+    let mut output: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+    output.drain(entry.start..entry.end);
+    Rendered {
+        content: join(&output, content.ends_with('\n')),
+        key: None,
+        placement: format!("removed the history entry on line {}", entry.start + 1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Inject into `original`, check the decoy is in the file's format, dated between
+    // its neighbours and never the newest entry, then remove it again
+    fn round_trip(original: &str, file_type: FileType, check: impl Fn(&[&str])) {
+        let library = CommandLibrary::load(&[]).unwrap();
+        let token = Honeytoken::new("rt_7fK2pQx9LmN4vB8sW3".to_string(), "history".to_string());
+        for _ in 0..20 {
+            let injected = inject(original, &file_type, &token, &library, None).unwrap();
+            let lines: Vec<&str> = injected.content.lines().collect();
+            let entries = entries(&lines, &file_type);
+            let decoy = entries
+                .iter()
+                .position(|entry| {
+                    lines[entry.start..entry.end]
+                        .iter()
+                        .any(|line| line.contains(&token.value))
+                })
+                .unwrap();
+            check(&lines[entries[decoy].start..entries[decoy].end]);
+
+            if entries.len() > 1 {
+                let newest = match file_type {
+                    FileType::NodeHistory => 0,
+                    _ => entries.len() - 1,
+                };
+                assert_ne!(decoy, newest, "decoy typed last in\n{}", injected.content);
+                assert!(decoy.abs_diff(newest) <= MAX_DEPTH);
+            }
+            let times: Vec<i64> = entries.iter().filter_map(|entry| entry.time).collect();
+            assert!(
+                times.windows(2).all(|pair| pair[0] <= pair[1]),
+                "out of order: {:?}",
+                times
+            );
+
+            assert_eq!(
+                remove(&injected.content, &file_type, &token).content,
+                original
+            );
+        }
+    }
+
+    #[test]
+    fn zsh_entries_keep_the_extended_format() {
+        round_trip(
+            ": 1700000000:0;cd ~/src/billing\n: 1700000300:2;git pull\n\
+             : 1700000600:0;make \\\n  test\n",
+            FileType::ZshHistory,
+            |decoy| {
+                let (time, rest) = decoy[0][2..].split_once(':').unwrap();
+                // In the past, before the newest entry
+                assert!(time.parse::<i64>().unwrap() <= 1700000600);
+                assert!(rest.starts_with("0;"));
+            },
+        );
+        round_trip("ls\ncd app\n", FileType::ZshHistory, |decoy| {
+            assert!(!decoy[0].starts_with(": "));
+        });
+    }
+
+    #[test]
+    fn bash_entries_follow_histtimeformat() {
+        round_trip(
+            "#1700000000\ncd ~/src/billing\n#1700000300\ngit pull\n#1700000600\nmake test\n",
+            FileType::BashHistory,
+            |decoy| {
+                assert_eq!(decoy.len(), 2);
+                assert!(bash_time(decoy[0]).is_some());
+                assert!(bash_time(decoy[1]).is_none());
+            },
+        );
+        round_trip("ls\ncd app\ngit status", FileType::BashHistory, |decoy| {
+            assert_eq!(decoy.len(), 1);
+        });
+        round_trip("", FileType::BashHistory, |decoy| {
+            assert_eq!(decoy.len(), 1)
+        });
+    }
+
+    #[test]
+    fn fish_entries_carry_when() {
+        round_trip(
+            "- cmd: cd ~/src/billing\n  when: 1700000000\n  paths:\n    - ~/src/billing\n\
+             - cmd: git pull\n  when: 1700000300\n- cmd: make test\n  when: 1700000600\n",
+            FileType::FishHistory,
+            |decoy| {
+                assert!(decoy[0].starts_with("- cmd: "));
+                let when = decoy[1].trim().strip_prefix("when: ").unwrap();
+                assert!(when.parse::<i64>().unwrap() <= 1700000600);
+            },
+        );
+    }
+
+    #[test]
+    fn libedit_entries_are_escaped() {
+        round_trip(
+            "_HiStOrY_V2_\nselect\\0401;\n\\\\dt\nselect\\040*\\040from\\040users;\n",
+            FileType::PsqlHistory,
+            |decoy| {
+                assert_eq!(decoy.len(), 1);
+                assert!(!decoy[0].contains(' '));
+                assert!(decoy[0].contains("\\040"));
+            },
+        );
+    }
+
+    #[test]
+    fn node_history_is_newest_first() {
+        round_trip(
+            "const users = await db.query('select 1')\nconst db = require('./db')\n.help\n",
+            FileType::NodeHistory,
+            |decoy| assert_eq!(decoy.len(), 1),
+        );
+    }
+}
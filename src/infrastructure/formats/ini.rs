@@ -0,0 +1,317 @@
+use super::{file_name, free_key, looks_secret, redact, Rendered};
+use crate::core::token::Honeytoken;
+
+// A `[section]` and the lines that belong to it
+pub(super) struct Section {
+    pub name: String,
+    pub header: Option<usize>, // None for keys before the first header
+    pub last_key: Option<usize>,
+    pub keys: Vec<String>,
+}
+
+fn is_comment(trimmed: &str) -> bool {
+    trimmed.starts_with(';') || trimmed.starts_with('#')
+}
+
+pub(super) fn header_name(trimmed: &str) -> Option<&str> {
+    trimmed
+        .strip_prefix('[')
+        .and_then(|rest| rest.strip_suffix(']'))
+        .map(str::trim)
+}
+
+pub(super) fn key_value(trimmed: &str) -> Option<(&str, &str)> {
+    if is_comment(trimmed) {
+        return None;
+    }
+    let (key, value) = trimmed.split_once('=')?;
+    Some((
+        key.trim(),
+        value.trim().trim_matches('"').trim_matches('\''),
+    ))
+}
+
+pub(super) fn sections(lines: &[&str]) -> Vec<Section> {
+    let mut sections = vec![Section {
+        name: String::new(),
+        header: None,
+        last_key: None,
+        keys: Vec::new(),
+    }];
+
+    for (index, line) in lines.iter().enumerate() {
+        let trimmed = line.trim();
+        if let Some(name) = header_name(trimmed) {
+            sections.push(Section {
+                name: name.to_string(),
+                header: Some(index),
+                last_key: None,
+                keys: Vec::new(),
+            });
+        } else if let Some((key, _)) = key_value(trimmed) {
+            let section = sections.last_mut().expect("global section");
+            section.last_key = Some(index);
+            section.keys.push(key.to_ascii_lowercase());
+        }
+    }
+    sections
+}
+
+// Section and key that look natural for well-known files, used when the file has no
+// credential section to join; clients read only the groups they know, so the MySQL and
+// PostgreSQL entries are extra groups
+fn target(file_name: &str) -> Option<(&'static str, &'static str)> {
+    match file_name {
+        name if name.ends_with(".cnf") => Some(("client_replica", "password")),
+        ".gitconfig" | "gitconfig" => Some(("github", "token")),
+        "pg_service.conf" | ".pg_service.conf" => Some(("replica", "password")),
+        _ => None,
+    }
+}
+
+pub(super) fn join(lines: &[String], trailing_newline: bool) -> String {
+    let mut content = lines.join("\n");
+    // An emptied file stays empty
+    if trailing_newline && !lines.is_empty() {
+        content.push('\n');
+    }
+    content
+}
+
+// End of the value of the key at `index`, past its indented continuation lines
+pub(super) fn value_end(lines: &[&str], index: usize) -> usize {
+    lines[index + 1..]
+        .iter()
+        .position(|line| {
+            let trimmed = line.trim();
+            !line.starts_with([' ', '\t']) || trimmed.is_empty() || is_comment(trimmed)
+        })
+        .map_or(lines.len(), |offset| index + 1 + offset)
+}
+
+// `block` after a blank line, or as the whole file when it is empty. It goes before
+// any trailing blank lines so dropping it again leaves the file as it was.
+pub(super) fn append_block(content: &str, block: &str) -> String {
+    let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+    let at = lines
+        .iter()
+        .rposition(|line| !line.trim().is_empty())
+        .map_or(0, |last| last + 1);
+    let mut inserted: Vec<String> = block.lines().map(str::to_string).collect();
+    if at > 0 {
+        inserted.insert(0, String::new());
+    }
+    lines.splice(at..at, inserted);
+    join(&lines, content.ends_with('\n') || content.is_empty())
+}
+
+pub fn inject(content: &str, file_path: &str, token: &Honeytoken) -> Rendered {
+    let lines: Vec<&str> = content.lines().collect();
+    let sections = sections(&lines);
+
+    // Copy the file's own spacing around `=` and key indentation
+    let first_key = lines
+        .iter()
+        .find(|line| key_value(line.trim()).is_some() && header_name(line.trim()).is_none());
+    let delimiter = match first_key {
+        Some(line) if line.contains(" = ") => " = ",
+        Some(_) => "=",
+        None => " = ",
+    };
+    let indent: String = first_key
+        .map(|line| line.chars().take_while(|c| c.is_whitespace()).collect())
+        .unwrap_or_default();
+
+    let mut secret_sections = sections
+        .iter()
+        .filter(|section| section.header.is_some() && looks_secret(&section.name));
+    let (section_name, preferred_key) = match target(&file_name(file_path)) {
+        // An existing group such as my.cnf's [client] reads more naturally than a new one,
+        // as long as the key is still free: clients reject unknown keys like `password_2`
+        Some((section, key)) => secret_sections
+            .find(|section| !section.keys.iter().any(|k| k == key))
+            .map_or_else(
+                || (section.to_string(), key),
+                |section| (section.name.clone(), key),
+            ),
+        None => secret_sections
+            .next()
+            .map(|section| (section.name.clone(), "api_key"))
+            .unwrap_or_else(|| ("credentials".to_string(), "api_key")),
+    };
+
+    let existing = sections
+        .iter()
+        .find(|section| section.header.is_some() && section.name == section_name);
+    let key = free_key(preferred_key, |key| {
+        existing.is_some_and(|section| section.keys.iter().any(|k| k == key))
+    });
+    let entry = format!("{}{}{}{}", indent, key, delimiter, token.value);
+
+    let mut output: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+    let placement = match existing {
+        Some(section) => {
+            let after = section.last_key.or(section.header).expect("named section");
+            output.insert(after + 1, entry);
+            format!("new key in existing section [{}]", section_name)
+        }
+        None => {
+            // Before any trailing blank lines, so removal leaves them as they were
+            let at = output
+                .iter()
+                .rposition(|line| !line.trim().is_empty())
+                .map_or(0, |last| last + 1);
+            let mut block = vec![format!("[{}]", section_name), entry];
+            if at > 0 {
+                block.insert(0, String::new());
+            }
+            output.splice(at..at, block);
+            format!("new section [{}] at the end of the file", section_name)
+        }
+    };
+
+    Rendered {
+        content: join(&output, content.ends_with('\n') || content.is_empty()),
+        key: Some(key),
+        placement,
+    }
+}
+
+// Delete the lines holding the token, and section headers left without keys
+pub fn remove(content: &str, token: &Honeytoken) -> Rendered {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut keep = vec![true; lines.len()];
+    let mut removed = Vec::new();
+
+    for section in sections(&lines) {
+        let start = section.header.map_or(0, |header| header + 1);
+        let end = section.last_key.map_or(start, |last| last + 1);
+        let mut remaining = 0;
+
+        for index in start..end {
+            let Some((key, value)) = key_value(lines[index].trim()) else {
+                continue;
+            };
+            if value == token.value {
+                keep[index] = false;
+                removed.push(if section.name.is_empty() {
+                    key.to_string()
+                } else {
+                    format!("[{}] {}", section.name, key)
+                });
+            } else {
+                remaining += 1;
+            }
+        }
+
+        if let Some(header) = section.header {
+            if remaining == 0 && end > start && (start..end).any(|index| !keep[index]) {
+                keep[header] = false;
+                // Along with the blank line that separated it
+                if header > 0 && lines[header - 1].trim().is_empty() {
+                    keep[header - 1] = false;
+                }
+            }
+        }
+    }
+
+    if removed.is_empty() {
+        return redact(content, token);
+    }
+
+    let output: Vec<String> = lines
+        .iter()
+        .zip(keep)
+        .filter(|(_, keep)| *keep)
+        .map(|(line, _)| line.to_string())
+        .collect();
+
+    Rendered {
+        content: join(&output, content.ends_with('\n')),
+        key: removed.first().cloned(),
+        placement: format!("removed {}", removed.join(", ")),
+    }
+}
+
+// Drop the first section matching `matches` (given its name and body lines), with the
+// blank line before it. Returns the new content and the section's name.
+pub(super) fn drop_section(
+    content: &str,
+    matches: impl Fn(&str, &[&str]) -> bool,
+) -> Option<(String, String)> {
+    let lines: Vec<&str> = content.lines().collect();
+    let section = sections(&lines).into_iter().find(|section| {
+        section.header.is_some_and(|header| {
+            let end = section.last_key.map_or(header + 1, |last| last + 1);
+            matches(&section.name, &lines[header + 1..end])
+        })
+    })?;
+
+    let mut start = section.header.expect("named section");
+    let end = section.last_key.map_or(start + 1, |last| last + 1);
+    if start > 0 && lines[start - 1].trim().is_empty() {
+        start -= 1;
+    }
+
+    let output: Vec<String> = lines[..start]
+        .iter()
+        .chain(&lines[end..])
+        .map(|line| line.to_string())
+        .collect();
+    Some((join(&output, content.ends_with('\n')), section.name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Inject, hand back the result for placement checks, and check removal restores
+    // the original byte for byte
+    fn round_trip(original: &str, file_path: &str) -> String {
+        let token = Honeytoken::new("RT_ini_decoy".to_string(), file_path.to_string());
+        let injected = inject(original, file_path, &token);
+        assert_eq!(remove(&injected.content, &token).content, original);
+        injected.content
+    }
+
+    #[test]
+    fn my_cnf_gets_a_password_its_clients_accept() {
+        // Joins [client] while it has no password
+        let cnf = "[mysqld]\ndatadir = /var/lib/mysql\n\n[client]\nuser = app\n";
+        assert_eq!(
+            round_trip(cnf, "/home/dev/.my.cnf"),
+            "[mysqld]\ndatadir = /var/lib/mysql\n\n[client]\nuser = app\npassword = RT_ini_decoy\n"
+        );
+        // Otherwise an extra group clients skip
+        let cnf = "[client]\nuser=root\npassword=real\n";
+        assert_eq!(
+            round_trip(cnf, "/etc/mysql/my.cnf"),
+            "[client]\nuser=root\npassword=real\n\n[client_replica]\npassword=RT_ini_decoy\n"
+        );
+    }
+
+    #[test]
+    fn gitconfig_gets_a_github_section() {
+        let gitconfig = "[user]\n\tname = Dev\n\temail = dev@example.com\n[core]\n\teditor = vim\n";
+        assert_eq!(
+            round_trip(gitconfig, "/home/dev/.gitconfig"),
+            format!("{}\n[github]\n\ttoken = RT_ini_decoy\n", gitconfig)
+        );
+    }
+
+    #[test]
+    fn removal_preserves_the_rest_of_the_file() {
+        for (original, file_path) in [
+            ("", "settings.ini"),
+            ("; generated\n[app]\nname=demo\n\n\n", "settings.ini"),
+            ("[app]\nname = demo", "settings.ini"),
+            (
+                "[database_credentials]\n  user = app\n  # rotated\n\n[app]\nname = demo\n",
+                "app.cfg",
+            ),
+            ("[client]\nuser = app\n\n# trailing comment\n", ".my.cnf"),
+        ] {
+            round_trip(original, file_path);
+        }
+    }
+}
@@ -0,0 +1,284 @@
+use super::{
+    indent_of, ini::join, pick_name, redact, replaced_line, yaml_block, Rendered, YamlBlock,
+};
+use crate::core::{
+    error::{RedTokenError, RedTokenResult},
+    token::Honeytoken,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+const KEYS: [&str; 8] = [
+    "database-password",
+    "aws-secret-access-key",
+    "api-token",
+    "stripe-secret-key",
+    "github-token",
+    "admin-password",
+    "slack-bot-token",
+    "jwt-signing-key",
+];
+
+// Line ranges of the documents in a multi-document stream
+fn documents(lines: &[&str]) -> Vec<(usize, usize)> {
+    let mut documents = Vec::new();
+    let mut start = 0;
+    for (index, line) in lines.iter().enumerate() {
+        if line.trim_end() == "---" || line.starts_with("--- ") || line.trim_end() == "..." {
+            documents.push((start, index));
+            start = index + 1;
+        }
+    }
+    documents.push((start, lines.len()));
+    documents
+}
+
+fn unquote(value: &str) -> &str {
+    value.trim().trim_matches('"').trim_matches('\'')
+}
+
+fn is_secret(lines: &[&str], (start, end): (usize, usize)) -> bool {
+    yaml_block(lines, start, end, "kind").is_some_and(|kind| unquote(&kind.inline) == "Secret")
+}
+
+// `key: value` pairs of a block mapping, by line
+fn block_entries<'a>(lines: &[&'a str], block: &YamlBlock) -> Vec<(usize, &'a str, &'a str)> {
+    (block.header + 1..block.end)
+        .filter_map(|index| {
+            let trimmed = lines[index].trim();
+            if trimmed.starts_with('#') {
+                return None;
+            }
+            let (key, value) = trimmed.split_once(':')?;
+            Some((index, key.trim(), unquote(value)))
+        })
+        .collect()
+}
+
+// Entries of a flow mapping such as `{a: b, c: d}`
+fn flow_entries(inline: &str) -> Option<Vec<(String, String)>> {
+    let body = inline.strip_prefix('{')?.strip_suffix('}')?.trim();
+    if body.is_empty() {
+        return Some(Vec::new());
+    }
+    body.split(',')
+        .map(|entry| {
+            let (key, value) = entry.split_once(':')?;
+            Some((unquote(key).to_string(), unquote(value).to_string()))
+        })
+        .collect()
+}
+
+fn flow_mapping(entries: &[(String, String)]) -> String {
+    let entries: Vec<String> = entries
+        .iter()
+        .map(|(key, value)| format!("{}: {}", key, value))
+        .collect();
+    format!("{{{}}}", entries.join(", "))
+}
+
+// A new key in the first Secret: base64 under `data`, or plain under `stringData`
+// when that is what the manifest already uses
+pub fn inject(content: &str, token: &Honeytoken) -> RedTokenResult<Rendered> {
+    let lines: Vec<&str> = content.lines().collect();
+    let (start, end) = documents(&lines)
+        .into_iter()
+        .find(|&document| is_secret(&lines, document))
+        .ok_or_else(|| {
+            RedTokenError::InvalidFileFormat("No `kind: Secret` document found".to_string())
+        })?;
+
+    let data = yaml_block(&lines, start, end, "data");
+    let string_data = yaml_block(&lines, start, end, "stringData");
+    let (field, block) = match (data, string_data) {
+        (None, Some(block)) => ("stringData", Some(block)),
+        (data, _) => ("data", data),
+    };
+    let value = if field == "data" {
+        STANDARD.encode(&token.value)
+    } else {
+        token.value.clone()
+    };
+
+    let taken: Vec<String> = match &block {
+        Some(block) => match flow_entries(&block.inline) {
+            Some(entries) => entries.into_iter().map(|(key, _)| key).collect(),
+            None => block_entries(&lines, block)
+                .into_iter()
+                .map(|(_, key, _)| key.to_string())
+                .collect(),
+        },
+        None => Vec::new(),
+    };
+    let key = pick_name(&KEYS, &taken);
+
+    let mut output: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+    let placement = match block {
+        Some(block) => {
+            match flow_entries(&block.inline) {
+                Some(mut entries) => {
+                    entries.push((key.clone(), value));
+                    output[block.header] = format!("{}: {}", field, flow_mapping(&entries));
+                }
+                None => {
+                    let indent = indent_of(&lines, block.header + 1, block.end)
+                        .unwrap_or_else(|| "  ".to_string());
+                    if !block.inline.is_empty() {
+                        output[block.header] = format!("{}:", field);
+                    }
+                    output.insert(block.end, format!("{}{}: {}", indent, key, value));
+                }
+            }
+            format!("new key in existing {}", field)
+        }
+        None => {
+            let at = (start..end)
+                .rev()
+                .find(|&index| !lines[index].trim().is_empty())
+                .map_or(start, |last| last + 1);
+            output.splice(
+                at..at,
+                [format!("{}:", field), format!("  {}: {}", key, value)],
+            );
+            format!("new {} section", field)
+        }
+    };
+
+    Ok(Rendered {
+        content: join(&output, content.ends_with('\n') || lines.is_empty()),
+        key: Some(key),
+        placement: format!(
+            "{} of document {}",
+            placement,
+            document_number(&lines, start)
+        ),
+    })
+}
+
+fn document_number(lines: &[&str], start: usize) -> usize {
+    documents(lines)
+        .iter()
+        .position(|&(document_start, _)| document_start == start)
+        .map_or(1, |position| position + 1)
+}
+
+// Delete the key holding the token from whichever Secret has it, along with a
+// `data:`/`stringData:` header left without keys
+pub fn remove(content: &str, token: &Honeytoken) -> Rendered {
+    let lines: Vec<&str> = content.lines().collect();
+    let encoded = STANDARD.encode(&token.value);
+
+    for document in documents(&lines) {
+        if !is_secret(&lines, document) {
+            continue;
+        }
+        let (start, end) = document;
+        for (field, value) in [("data", &encoded), ("stringData", &token.value)] {
+            let Some(block) = yaml_block(&lines, start, end, field) else {
+                continue;
+            };
+            let mut output: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+
+            let key = if let Some(mut entries) = flow_entries(&block.inline) {
+                let Some(position) = entries.iter().position(|(_, v)| v == value) else {
+                    continue;
+                };
+                let (key, _) = entries.remove(position);
+                output[block.header] = format!("{}: {}", field, flow_mapping(&entries));
+                key
+            } else {
+                let entries = block_entries(&lines, &block);
+                let Some(&(index, key, _)) = entries.iter().find(|(_, _, v)| v == value) else {
+                    continue;
+                };
+                output.remove(index);
+                if entries.len() == 1 && block.inline.is_empty() {
+                    match replaced_line(token, field) {
+                        Some(header) => output[block.header] = header.to_string(),
+                        None => {
+                            output.remove(block.header);
+                        }
+                    }
+                }
+                key.to_string()
+            };
+
+            return Rendered {
+                content: join(&output, content.ends_with('\n')),
+                placement: format!(
+                    "removed {}.{} from document {}",
+                    field,
+                    key,
+                    document_number(&lines, start)
+                ),
+                key: Some(key),
+            };
+        }
+    }
+
+    redact(content, token)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infrastructure::formats::replaced_lines;
+    use serde::Deserialize;
+
+    // Every YAML document in the stream, parsed
+    fn parse(content: &str) -> Vec<serde_yaml::Value> {
+        serde_yaml::Deserializer::from_str(content)
+            .map(|document| serde_yaml::Value::deserialize(document).unwrap())
+            .collect()
+    }
+
+    // Inject, check the token landed in the document's `field`, then remove
+    fn round_trip(manifest: &str, document: usize, field: &str) {
+        let mut token = Honeytoken::new("RT_k8s_secret".to_string(), "secret.yaml".to_string());
+        let injected = inject(manifest, &token).unwrap();
+        token.replaced_lines = replaced_lines(manifest, &injected.content);
+        let key = injected.key.clone().unwrap();
+
+        let value = parse(&injected.content)[document][field][key.as_str()]
+            .as_str()
+            .unwrap()
+            .to_string();
+        match field {
+            "data" => assert_eq!(STANDARD.decode(value).unwrap(), token.value.as_bytes()),
+            _ => assert_eq!(value, token.value),
+        }
+
+        let removed = remove(&injected.content, &token);
+        assert_eq!(removed.content, manifest, "after removing {}", key);
+    }
+
+    #[test]
+    fn removal_restores_block_data_in_a_multi_document_stream() {
+        round_trip(
+            "apiVersion: v1\nkind: ConfigMap\nmetadata:\n  name: app\ndata:\n  mode: prod\n---\n\
+             apiVersion: v1\nkind: Secret\nmetadata:\n  name: app\ntype: Opaque\ndata:\n    \
+             username: YWRtaW4=\n    # rotated monthly\n    password: aHVudGVyMg==\n",
+            1,
+            "data",
+        );
+    }
+
+    #[test]
+    fn removal_restores_string_data() {
+        round_trip(
+            "apiVersion: v1\nkind: Secret\nmetadata:\n  name: app\nstringData:\n  user: admin\n",
+            0,
+            "stringData",
+        );
+    }
+
+    #[test]
+    fn removal_restores_flow_and_missing_data() {
+        let header = "apiVersion: v1\nkind: Secret\nmetadata:\n  name: app\n";
+        round_trip(&format!("{}data: {{user: YWRtaW4=}}\n", header), 0, "data");
+        round_trip(&format!("{}data: {{}}\n", header), 0, "data");
+        round_trip(header, 0, "data");
+        round_trip(header.trim_end(), 0, "data");
+        round_trip(&format!("{}data: null\n", header), 0, "data");
+        round_trip(&format!("{}data: ~\ntype: Opaque\n", header), 0, "data");
+    }
+}
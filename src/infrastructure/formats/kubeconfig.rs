@@ -0,0 +1,320 @@
+use super::{
+    indent_of, ini::join, pick_name, redact, replaced_line, require_canary, yaml_block, Rendered,
+};
+use crate::core::{error::RedTokenResult, injection::KubeAuth, token::Honeytoken};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use rand::{thread_rng, Rng};
+
+const NAMES: [&str; 6] = [
+    "prod-eks-admin",
+    "gke-prod-europe-west1",
+    "aks-payments-prod",
+    "k8s-prod-break-glass",
+    "prod-cluster-admin",
+    "legacy-prod-us-east-1",
+];
+
+// In the order kubectl writes them
+const SECTIONS: [&str; 3] = ["clusters", "contexts", "users"];
+
+// One `- ` entry of a block sequence, with the name it is referenced by
+struct Item {
+    start: usize,
+    end: usize,
+    name: Option<String>,
+}
+
+fn items(lines: &[&str], section: &str) -> Vec<Item> {
+    let Some(block) = yaml_block(lines, 0, lines.len(), section) else {
+        return Vec::new();
+    };
+    let Some(indent) = indent_of(lines, block.header + 1, block.end) else {
+        return Vec::new();
+    };
+
+    let starts: Vec<usize> = (block.header + 1..block.end)
+        .filter(|&index| {
+            let line = lines[index];
+            line.starts_with(&indent) && line[indent.len()..].starts_with('-')
+        })
+        .collect();
+
+    starts
+        .iter()
+        .enumerate()
+        .map(|(position, &start)| {
+            let end = starts.get(position + 1).copied().unwrap_or(block.end);
+            // `name:` sits two columns in from the dash, on the dash line or its own
+            let name_indent = format!("{}  ", indent);
+            let name = (start..end).find_map(|index| {
+                let line = lines[index];
+                let rest = if index == start {
+                    line[indent.len()..].strip_prefix("- ")?
+                } else {
+                    line.strip_prefix(&name_indent)
+                        .filter(|rest| !rest.starts_with(' '))?
+                };
+                rest.strip_prefix("name:")
+                    .map(|name| name.trim().trim_matches('"').trim_matches('\'').to_string())
+            });
+            Item { start, end, name }
+        })
+        .collect()
+}
+
+// Random bytes wrapped as PEM, then base64 again as kubeconfig `*-data` fields are
+fn fake_pem_data(label: &str, size: usize) -> String {
+    let bytes: Vec<u8> = (0..size).map(|_| thread_rng().gen()).collect();
+    let body = STANDARD.encode(bytes);
+    let wrapped: Vec<&str> = body
+        .as_bytes()
+        .chunks(64)
+        .map(|chunk| std::str::from_utf8(chunk).expect("base64 is ascii"))
+        .collect();
+    let pem = format!(
+        "-----BEGIN {}-----\n{}\n-----END {}-----\n",
+        label,
+        wrapped.join("\n"),
+        label
+    );
+    STANDARD.encode(pem)
+}
+
+// Append an entry to `section`, adding the section when the file has none
+fn add_item(content: &str, section: &str, entry: impl Fn(&str) -> Vec<String>) -> String {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut output: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+
+    match yaml_block(&lines, 0, lines.len(), section) {
+        Some(block) => {
+            let indent = indent_of(&lines, block.header + 1, block.end).unwrap_or_default();
+            // `users: null` or `users: []` becomes a block sequence
+            if !block.inline.is_empty() {
+                output[block.header] = format!("{}:", section);
+            }
+            output.splice(block.end..block.end, entry(&indent));
+        }
+        None => {
+            let end = lines
+                .iter()
+                .rposition(|line| !line.trim().is_empty())
+                .map_or(0, |last| last + 1);
+            let mut added = vec![format!("{}:", section)];
+            added.extend(entry(""));
+            output.splice(end..end, added);
+        }
+    }
+    join(&output, true)
+}
+
+fn server_url(canary_url: &str, token: &Honeytoken) -> String {
+    format!("{}/k8s/clusters/{}", canary_url, token.id.simple())
+}
+
+// A cluster, user and context sharing one name; the current context is left alone
+pub fn inject(
+    content: &str,
+    token: &Honeytoken,
+    auth: KubeAuth,
+    canary_url: Option<&str>,
+) -> RedTokenResult<Rendered> {
+    let canary_url = require_canary(canary_url, "kubeconfig")?;
+
+    let lines: Vec<&str> = content.lines().collect();
+    let taken: Vec<String> = SECTIONS
+        .iter()
+        .flat_map(|section| items(&lines, section))
+        .filter_map(|item| item.name)
+        .collect();
+    let name = pick_name(&NAMES, &taken);
+
+    // kubectl sends neither bearer tokens nor certificates over plain HTTP, so the
+    // server URL identifies the token whichever way the user authenticates. It is
+    // the token's id, in the shape of a Rancher-proxied cluster URL.
+    let server = server_url(canary_url, token);
+
+    let content = add_item(content, "clusters", |i| {
+        vec![
+            format!("{}- cluster:", i),
+            format!("{}    server: {}", i, server),
+            format!("{}  name: {}", i, name),
+        ]
+    });
+    let content = add_item(&content, "contexts", |i| {
+        vec![
+            format!("{}- context:", i),
+            format!("{}    cluster: {}", i, name),
+            format!("{}    user: {}", i, name),
+            format!("{}  name: {}", i, name),
+        ]
+    });
+    let credentials = match auth {
+        KubeAuth::Token => vec![format!("token: {}", token.value)],
+        KubeAuth::Cert => vec![
+            format!(
+                "client-certificate-data: {}",
+                fake_pem_data("CERTIFICATE", 800)
+            ),
+            format!(
+                "client-key-data: {}",
+                fake_pem_data("RSA PRIVATE KEY", 1190)
+            ),
+        ],
+    };
+    let content = add_item(&content, "users", |i| {
+        let mut entry = vec![format!("{}- name: {}", i, name), format!("{}  user:", i)];
+        entry.extend(credentials.iter().map(|line| format!("{}    {}", i, line)));
+        entry
+    });
+
+    Ok(Rendered {
+        content,
+        key: Some(name.clone()),
+        placement: format!(
+            "new cluster, context and user '{}' with server {}",
+            name, server
+        ),
+    })
+}
+
+// Drop the cluster, context and user named after the entry that holds the token
+pub fn remove(content: &str, token: &Honeytoken) -> Rendered {
+    let lines: Vec<&str> = content.lines().collect();
+    // Older versions put the value in the server URL
+    let path = format!("/k8s/clusters/{}", token.id.simple());
+    let name = ["users", "clusters"].iter().find_map(|section| {
+        items(&lines, section)
+            .into_iter()
+            .find(|item| {
+                lines[item.start..item.end]
+                    .iter()
+                    .any(|line| line.contains(&token.value) || line.contains(&path))
+            })
+            .and_then(|item| item.name)
+    });
+    let Some(name) = name else {
+        return redact(content, token);
+    };
+
+    let mut content = content.to_string();
+    for section in SECTIONS {
+        let lines: Vec<&str> = content.lines().collect();
+        let all = items(&lines, section);
+        let Some(item) = all.iter().find(|item| item.name.as_deref() == Some(&name)) else {
+            continue;
+        };
+
+        let mut output: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+        output.drain(item.start..item.end);
+        // A section that held only the decoy goes too, or gets its `null`/`[]` back
+        if all.len() == 1 {
+            if let Some(block) = yaml_block(&lines, 0, lines.len(), section) {
+                match replaced_line(token, section) {
+                    Some(header) => output[block.header] = header.to_string(),
+                    None => {
+                        output.remove(block.header);
+                    }
+                }
+            }
+        }
+        content = join(&output, content.ends_with('\n'));
+    }
+
+    Rendered {
+        content,
+        placement: format!("removed cluster, context and user '{}'", name),
+        key: Some(name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infrastructure::formats::replaced_lines;
+
+    const KUBECONFIG: &str = "\
+apiVersion: v1
+kind: Config
+clusters:
+- cluster:
+    certificate-authority-data: LS0tLS1CRUdJTg==
+    server: https://10.0.0.1:6443
+  name: staging
+contexts:
+- context:
+    cluster: staging
+    user: staging-admin
+  name: staging
+current-context: staging
+preferences: {}
+users:
+- name: staging-admin
+  user:
+    token: real-token
+";
+
+    fn round_trip(kubeconfig: &str, auth: KubeAuth) {
+        let mut token = Honeytoken::new("RT_kube".to_string(), "config".to_string());
+        let injected =
+            inject(kubeconfig, &token, auth, Some("https://canary.example.com")).unwrap();
+        token.replaced_lines = replaced_lines(kubeconfig, &injected.content);
+        let name = injected.key.clone().unwrap();
+
+        let config: serde_yaml::Value = serde_yaml::from_str(&injected.content).unwrap();
+        let named = |section: &str| {
+            config[section]
+                .as_sequence()
+                .unwrap()
+                .iter()
+                .find(|item| item["name"].as_str() == Some(name.as_str()))
+                .cloned()
+                .unwrap()
+        };
+        assert_eq!(
+            named("clusters")["cluster"]["server"],
+            format!(
+                "https://canary.example.com/k8s/clusters/{}",
+                token.id.simple()
+            )
+            .as_str()
+        );
+        // The value only appears as the bearer token
+        let expected = match auth {
+            KubeAuth::Token => 1,
+            KubeAuth::Cert => 0,
+        };
+        assert_eq!(injected.content.matches(&token.value).count(), expected);
+        assert_eq!(named("contexts")["context"]["user"], name.as_str());
+        match auth {
+            KubeAuth::Token => assert_eq!(named("users")["user"]["token"], "RT_kube"),
+            KubeAuth::Cert => assert!(named("users")["user"]["client-key-data"].is_string()),
+        }
+        // The user's own context stays current
+        if kubeconfig.contains("current-context: staging") {
+            assert_eq!(config["current-context"], "staging");
+        }
+
+        assert_eq!(remove(&injected.content, &token).content, kubeconfig);
+    }
+
+    #[test]
+    fn removal_restores_the_kubeconfig() {
+        round_trip(KUBECONFIG, KubeAuth::Token);
+        round_trip(KUBECONFIG, KubeAuth::Cert);
+        // Sequences indented under their key, as some tools write them
+        let indented: Vec<String> = KUBECONFIG
+            .lines()
+            .map(|line| match line.starts_with([' ', '-']) {
+                true => format!("  {}", line),
+                false => line.to_string(),
+            })
+            .collect();
+        round_trip(&format!("{}\n", indented.join("\n")), KubeAuth::Token);
+        round_trip("", KubeAuth::Token);
+        // Empty sections as kubectl writes them
+        let empty = "apiVersion: v1\nclusters: null\ncontexts: []\ncurrent-context: \"\"\n\
+                     kind: Config\npreferences: {}\nusers: null\n";
+        round_trip(empty, KubeAuth::Token);
+        round_trip(empty, KubeAuth::Cert);
+    }
+}
@@ -0,0 +1,195 @@
+// Format-aware editors used by the file injectors. Each works on text in memory
+// (Office documents on their bytes) and returns the new content; reading and
+// writing is left to the injector.
+pub mod aws;
+pub mod commands;
+pub mod compose;
+pub mod docker;
+pub mod dockerfile;
+pub mod git_credentials;
+pub mod history;
+pub mod ini;
+pub mod k8s_secret;
+pub mod kubeconfig;
+pub mod netrc;
+pub mod npmrc;
+pub mod ooxml;
+pub mod pip_conf;
+pub mod pypirc;
+pub mod source;
+pub mod tfstate;
+pub mod tfvars;
+pub mod toml;
+
+use crate::core::{
+    error::{RedTokenError, RedTokenResult},
+    token::Honeytoken,
+};
+use rand::{seq::SliceRandom, thread_rng};
+
+// Base URL for decoys that call home; there is none when web.public_url is unset and
+// the server only listens on a loopback or wildcard address
+pub fn require_canary<'a>(canary_url: Option<&'a str>, decoys: &str) -> RedTokenResult<&'a str> {
+    canary_url.ok_or_else(|| {
+        RedTokenError::ConfigError(format!(
+            "{} decoys call home, but web.host is a loopback or wildcard address that other \
+             machines cannot reach. Set web.public_url to the address decoys should call, \
+             e.g. https://canary.example.com",
+            decoys
+        ))
+    })
+}
+
+// Result of rendering an injection or removal in memory
+pub struct Rendered {
+    pub content: String,
+    pub key: Option<String>,
+    pub placement: String,
+}
+
+// Top-level YAML lines of `before` the injection rewrote, such as a `users: null`
+// header that had to become a block to hold the decoy
+pub fn replaced_lines(before: &str, after: &str) -> Vec<String> {
+    let kept: Vec<&str> = after.lines().collect();
+    before
+        .lines()
+        .filter(|line| indentation(line) == 0 && line.contains(':') && !kept.contains(line))
+        .map(str::to_string)
+        .collect()
+}
+
+// The line `token.replaced_lines` holds for the top-level `key`
+pub fn replaced_line<'a>(token: &'a Honeytoken, key: &str) -> Option<&'a str> {
+    token
+        .replaced_lines
+        .iter()
+        .find(|line| {
+            line.strip_prefix(key)
+                .is_some_and(|rest| rest.starts_with(':'))
+        })
+        .map(String::as_str)
+}
+
+// Replace the token with a placeholder, for formats without a structured removal
+// or when the token is not where the injector put it
+pub fn redact(content: &str, token: &Honeytoken) -> Rendered {
+    let occurrences = content.matches(&token.value).count();
+    Rendered {
+        content: content.replace(&token.value, "[REDACTED]"),
+        key: None,
+        placement: format!("{} occurrence(s) replaced with [REDACTED]", occurrences),
+    }
+}
+
+// Environment variable names that look worth stealing
+pub const ENV_NAMES: [&str; 8] = [
+    "STRIPE_SECRET_KEY",
+    "AWS_SECRET_ACCESS_KEY",
+    "SENTRY_AUTH_TOKEN",
+    "GITHUB_TOKEN",
+    "REGISTRY_PASSWORD",
+    "JWT_SECRET",
+    "SMTP_PASSWORD",
+    "DATABASE_PASSWORD",
+];
+
+// Names that make a section look like it holds credentials
+const SECRET_HINTS: [&str; 8] = [
+    "secret",
+    "credential",
+    "auth",
+    "token",
+    "api",
+    "password",
+    "database",
+    "client",
+];
+
+pub fn looks_secret(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    SECRET_HINTS.iter().any(|hint| name.contains(hint))
+}
+
+// `preferred`, or `preferred_2`, `preferred_3`... when the name is taken
+pub fn free_key(preferred: &str, taken: impl Fn(&str) -> bool) -> String {
+    if !taken(preferred) {
+        return preferred.to_string();
+    }
+    (2..)
+        .map(|n| format!("{}_{}", preferred, n))
+        .find(|key| !taken(key))
+        .expect("unbounded range")
+}
+
+// A random name from `candidates` that is not taken yet, or the first one with
+// `_2`, `_3`... once they all are
+pub fn pick_name(candidates: &[&str], taken: &[String]) -> String {
+    let is_taken = |name: &str| taken.iter().any(|taken| taken == name);
+    let free: Vec<&str> = candidates
+        .iter()
+        .copied()
+        .filter(|name| !is_taken(name))
+        .collect();
+    match free.choose(&mut thread_rng()) {
+        Some(name) => name.to_string(),
+        None => free_key(candidates[0], is_taken),
+    }
+}
+
+// Lowercased file name, for the per-file placement rules
+pub fn file_name(file_path: &str) -> String {
+    std::path::Path::new(file_path)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default()
+}
+
+// A top-level `key:` of a YAML document edited as text, so everything around the
+// change keeps its exact formatting
+pub struct YamlBlock {
+    pub header: usize,  // Line of `key:`
+    pub end: usize,     // One past the last line belonging to the key
+    pub inline: String, // Anything after `key:` on the header line, e.g. `[]` or `{}`
+}
+
+pub fn indentation(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+// The block under `key:` within lines[start..end]. A block sequence may sit at
+// column 0 under its key, so `- ` lines do not end it.
+pub fn yaml_block(lines: &[&str], start: usize, end: usize, key: &str) -> Option<YamlBlock> {
+    let header = (start..end).find(|&index| {
+        let line = lines[index];
+        indentation(line) == 0
+            && line
+                .strip_prefix(key)
+                .is_some_and(|rest| rest.starts_with(':'))
+    })?;
+
+    let mut block_end = header + 1;
+    for (index, line) in lines.iter().enumerate().take(end).skip(header + 1) {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        if indentation(line) == 0 && !trimmed.starts_with('-') {
+            break;
+        }
+        block_end = index + 1;
+    }
+
+    Some(YamlBlock {
+        header,
+        end: block_end,
+        inline: lines[header][key.len() + 1..].trim().to_string(),
+    })
+}
+
+// Leading whitespace of the first non-blank line in lines[start..end]
+pub fn indent_of(lines: &[&str], start: usize, end: usize) -> Option<String> {
+    lines[start..end]
+        .iter()
+        .find(|line| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(|line| line[..indentation(line)].to_string())
+}
@@ -0,0 +1,253 @@
+use super::{ini::join, pick_name, redact, Rendered};
+use crate::core::{
+    error::{RedTokenError, RedTokenResult},
+    token::Honeytoken,
+};
+
+// The heroku CLI logs in with one entry for the API and one for git pushes
+const MACHINES: [&str; 2] = ["api.heroku.com", "git.heroku.com"];
+const LOGINS: [&str; 3] = [
+    "ops.deploy@gmail.com",
+    "heroku-admin@outlook.com",
+    "release.bot@gmail.com",
+];
+
+// A `machine`/`default` entry by line range, with its words
+struct Entry {
+    start: usize,
+    end: usize,
+    words: Vec<String>,
+}
+
+impl Entry {
+    fn value(&self, name: &str) -> Option<&str> {
+        self.words
+            .iter()
+            .position(|word| word == name)
+            .and_then(|index| self.words.get(index + 1))
+            .map(String::as_str)
+    }
+}
+
+fn entries(lines: &[&str]) -> Vec<Entry> {
+    let first_word = |line: &str| {
+        line.split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_string()
+    };
+    let starts: Vec<usize> = (0..lines.len())
+        .filter(|&index| {
+            matches!(
+                first_word(lines[index]).as_str(),
+                "machine" | "default" | "macdef"
+            )
+        })
+        .collect();
+
+    starts
+        .iter()
+        .enumerate()
+        .filter(|(_, &start)| first_word(lines[start]) != "macdef")
+        .map(|(position, &start)| {
+            let next = starts.get(position + 1).copied().unwrap_or(lines.len());
+            // Stop at the last line with content, leaving blank lines and comments in between
+            let end = (start..next)
+                .rev()
+                .find(|&index| {
+                    let trimmed = lines[index].trim();
+                    !trimmed.is_empty() && !trimmed.starts_with('#')
+                })
+                .map_or(start + 1, |last| last + 1);
+            let words = lines[start..end]
+                .iter()
+                .flat_map(|line| line.split_whitespace())
+                .map(str::to_string)
+                .collect();
+            Entry { start, end, words }
+        })
+        .collect()
+}
+
+// Heroku entries at the end of the file, in the file's own layout. Clients differ
+// on whether the first or last entry for a host wins, so hosts that already have
+// one are left out and existing logins keep working.
+pub fn inject(content: &str, token: &Honeytoken) -> RedTokenResult<Rendered> {
+    let lines: Vec<&str> = content.lines().collect();
+    let entries = entries(&lines);
+    let machines: Vec<&str> = MACHINES
+        .into_iter()
+        .filter(|machine| {
+            !entries
+                .iter()
+                .any(|entry| entry.value("machine") == Some(machine))
+        })
+        .collect();
+    if machines.is_empty() {
+        return Err(RedTokenError::InvalidFileFormat(format!(
+            "The netrc file already has entries for {}",
+            MACHINES.join(" and ")
+        )));
+    }
+
+    // Reuse the owner's address when there is one
+    let login = entries
+        .iter()
+        .filter_map(|entry| entry.value("login"))
+        .find(|login| login.contains('@'))
+        .map(str::to_string)
+        .unwrap_or_else(|| pick_name(&LOGINS, &[]));
+
+    // One line per entry, or `login`/`password` on their own indented lines
+    let layout = entries
+        .iter()
+        .find(|entry| entry.end - entry.start > 1)
+        .map(|entry| {
+            let line = lines[entry.start + 1];
+            line[..line.len() - line.trim_start().len()].to_string()
+        });
+
+    let mut added: Vec<String> = Vec::new();
+    for machine in &machines {
+        match (&layout, entries.is_empty()) {
+            (Some(indent), _) => added.extend([
+                format!("machine {}", machine),
+                format!("{}login {}", indent, login),
+                format!("{}password {}", indent, token.value),
+            ]),
+            (None, false) => added.push(format!(
+                "machine {} login {} password {}",
+                machine, login, token.value
+            )),
+            // How the heroku CLI writes a new file
+            (None, true) => added.extend([
+                format!("machine {}", machine),
+                format!("  login {}", login),
+                format!("  password {}", token.value),
+            ]),
+        }
+    }
+    // Before any trailing blank lines, so removal leaves them as they were
+    let mut output: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+    let at = lines
+        .iter()
+        .rposition(|line| !line.trim().is_empty())
+        .map_or(0, |last| last + 1);
+    output.splice(at..at, added);
+
+    Ok(Rendered {
+        content: join(&output, content.ends_with('\n') || content.is_empty()),
+        key: Some(machines[0].to_string()),
+        placement: format!(
+            "new entries for {} with login {} at the end",
+            machines.join(" and "),
+            login
+        ),
+    })
+}
+
+// Delete every entry whose password is the token
+pub fn remove(content: &str, token: &Honeytoken) -> Rendered {
+    let lines: Vec<&str> = content.lines().collect();
+    let matching: Vec<Entry> = entries(&lines)
+        .into_iter()
+        .filter(|entry| entry.value("password") == Some(token.value.as_str()))
+        .collect();
+    if matching.is_empty() {
+        return redact(content, token);
+    }
+
+    let machines: Vec<String> = matching
+        .iter()
+        .map(|entry| entry.value("machine").unwrap_or("default").to_string())
+        .collect();
+    let output: Vec<String> = lines
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| {
+            !matching
+                .iter()
+                .any(|entry| (entry.start..entry.end).contains(index))
+        })
+        .map(|(_, line)| line.to_string())
+        .collect();
+
+    Rendered {
+        content: join(&output, content.ends_with('\n')),
+        key: machines.first().cloned(),
+        placement: format!("removed the entries for {}", machines.join(", ")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(original: &str) -> String {
+        let token = Honeytoken::new(
+            "2f1c6d0e-8b7a-4c3d-9e5f-a1b2c3d4e5f6".to_string(),
+            ".netrc".to_string(),
+        );
+        let injected = inject(original, &token).unwrap();
+        assert_eq!(remove(&injected.content, &token).content, original);
+        injected.content
+    }
+
+    // machine -> (login, password) of the entries curl and git would read
+    fn logins(content: &str) -> Vec<(String, String, String)> {
+        let lines: Vec<&str> = content.lines().collect();
+        entries(&lines)
+            .iter()
+            .map(|entry| {
+                let value = |name| entry.value(name).unwrap_or_default().to_string();
+                (value("machine"), value("login"), value("password"))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn a_new_file_is_laid_out_like_the_heroku_cli() {
+        let content = round_trip("");
+        assert!(content.starts_with("machine api.heroku.com\n  login "));
+        let logins = logins(&content);
+        assert_eq!(logins.len(), 2);
+        assert_eq!(logins[1].0, "git.heroku.com");
+        assert!(logins
+            .iter()
+            .all(|(_, login, password)| LOGINS.contains(&login.as_str())
+                && password == "2f1c6d0e-8b7a-4c3d-9e5f-a1b2c3d4e5f6"));
+    }
+
+    #[test]
+    fn entries_follow_the_file_layout_and_owner() {
+        let one_line = "machine github.com login dev@example.com password ghp_real\n\n";
+        let content = round_trip(one_line);
+        assert!(content.contains("\nmachine api.heroku.com login dev@example.com password 2f1c"));
+
+        let indented = "# work\nmachine gitlab.example.com\n\tlogin dev\n\tpassword real\n";
+        let content = round_trip(indented);
+        // Without an address to reuse, a made-up one
+        let added: Vec<&str> = content.lines().skip(4).collect();
+        assert_eq!(added.len(), 6);
+        assert_eq!(added[3], "machine git.heroku.com");
+        assert!(LOGINS.contains(&added[4].strip_prefix("\tlogin ").unwrap()));
+        assert_eq!(added[5], "\tpassword 2f1c6d0e-8b7a-4c3d-9e5f-a1b2c3d4e5f6");
+    }
+
+    #[test]
+    fn existing_heroku_logins_are_left_alone() {
+        let original = "machine api.heroku.com\n  login me@example.com\n  password real\n";
+        let content = round_trip(original);
+        let logins = logins(&content);
+        assert_eq!(logins[0].2, "real");
+        assert_eq!(logins.len(), 2);
+        assert_eq!(logins[1].0, "git.heroku.com");
+
+        let token = Honeytoken::new("x".to_string(), ".netrc".to_string());
+        let both = format!(
+            "{}machine git.heroku.com login me password real\n",
+            original
+        );
+        assert!(inject(&both, &token).is_err());
+    }
+}
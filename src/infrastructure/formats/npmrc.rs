@@ -0,0 +1,167 @@
+use super::{ini::join, pick_name, redact, require_canary, Rendered};
+use crate::core::{error::RedTokenResult, token::Honeytoken};
+
+const AUTH_KEY: &str = "//registry.npmjs.org/:_authToken";
+
+// Scopes of an in-house registry, for files without a public registry token
+const SCOPES: [&str; 4] = ["@internal", "@platform", "@infra", "@shared"];
+
+// `key=value`, as npm writes it
+fn entry(line: &str) -> Option<(&str, &str)> {
+    let trimmed = line.trim();
+    if trimmed.starts_with('#') || trimmed.starts_with(';') {
+        return None;
+    }
+    let (key, value) = trimmed.split_once('=')?;
+    Some((key.trim(), value.trim().trim_matches('"')))
+}
+
+// An auth token for the public registry. npm keeps the last value of a repeated
+// key, so the decoy goes before an existing token and never replaces it. Without
+// one, npm would send the decoy on every install and fail with E401, so it goes
+// to a scoped registry on the canary host that the user's own installs never use.
+pub fn inject(
+    content: &str,
+    token: &Honeytoken,
+    canary_url: Option<&str>,
+) -> RedTokenResult<Rendered> {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut output: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+
+    let existing = lines
+        .iter()
+        .position(|line| entry(line).is_some_and(|(key, _)| key == AUTH_KEY));
+    if let Some(index) = existing {
+        output.insert(index, format!("{}={}", AUTH_KEY, token.value));
+        return Ok(Rendered {
+            content: join(&output, true),
+            key: Some(AUTH_KEY.to_string()),
+            placement: format!(
+                "new line {} before the existing registry.npmjs.org token",
+                index + 1
+            ),
+        });
+    }
+
+    let canary_url = require_canary(canary_url, "npm registry")?;
+    let registry = format!("{}/npm/", canary_url.trim_end_matches('/'));
+    let auth_key = format!("{}:_authToken", without_scheme(&registry));
+    let taken: Vec<String> = lines
+        .iter()
+        .filter_map(|line| entry(line)?.0.strip_suffix(":registry"))
+        .map(str::to_string)
+        .collect();
+    let scope = pick_name(&SCOPES, &taken);
+
+    // After the last setting, before any trailing blank lines
+    let at = lines
+        .iter()
+        .rposition(|line| !line.trim().is_empty())
+        .map_or(0, |last| last + 1);
+    output.splice(
+        at..at,
+        [
+            format!("{}:registry={}", scope, registry),
+            format!("{}={}", auth_key, token.value),
+        ],
+    );
+    Ok(Rendered {
+        content: join(&output, true),
+        placement: format!(
+            "new lines {}-{}, a {} registry at {}",
+            at + 1,
+            at + 2,
+            scope,
+            registry
+        ),
+        key: Some(auth_key),
+    })
+}
+
+// `//host/path/`, as npm keys per-registry settings
+fn without_scheme(url: &str) -> &str {
+    url.split_once(':').map_or(url, |(_, rest)| rest)
+}
+
+// Delete the `_authToken` line holding the token, with the scoped registry added for it
+pub fn remove(content: &str, token: &Honeytoken) -> Rendered {
+    let lines: Vec<&str> = content.lines().collect();
+    let found = lines.iter().enumerate().find_map(|(index, line)| {
+        entry(line)
+            .filter(|(key, value)| key.ends_with(":_authToken") && *value == token.value)
+            .map(|(key, _)| (index, key.to_string()))
+    });
+    let Some((index, key)) = found else {
+        return redact(content, token);
+    };
+
+    let mut output: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+    output.remove(index);
+    // The scoped registry added along with a token for it
+    let scoped = index.checked_sub(1).filter(|&previous| {
+        entry(lines[previous]).is_some_and(|(name, registry)| {
+            name.ends_with(":registry") && format!("{}:_authToken", without_scheme(registry)) == key
+        })
+    });
+    if let Some(previous) = scoped {
+        output.remove(previous);
+    }
+    Rendered {
+        content: join(&output, content.ends_with('\n')),
+        placement: format!("removed {} from line {}", key, index + 1),
+        key: Some(key),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CANARY: Option<&str> = Some("https://canary.example.com");
+
+    fn round_trip(npmrc: &str) -> String {
+        let token = Honeytoken::new("npm_decoy".to_string(), ".npmrc".to_string());
+        let injected = inject(npmrc, &token, CANARY).unwrap();
+        assert_eq!(remove(&injected.content, &token).content, npmrc);
+        injected.content
+    }
+
+    #[test]
+    fn decoy_goes_before_the_public_registry_token() {
+        let npmrc =
+            "registry=https://registry.npmjs.org/\n//registry.npmjs.org/:_authToken=npm_real\n";
+        assert_eq!(
+            round_trip(npmrc),
+            "registry=https://registry.npmjs.org/\n//registry.npmjs.org/:_authToken=npm_decoy\n\
+             //registry.npmjs.org/:_authToken=npm_real\n"
+        );
+    }
+
+    #[test]
+    fn without_a_public_token_the_decoy_is_scoped_to_the_canary() {
+        for npmrc in [
+            "",
+            "save-exact=true\n",
+            "@acme:registry=https://npm.acme.dev/\n\n",
+        ] {
+            let injected = round_trip(npmrc);
+            assert!(!injected.contains("//registry.npmjs.org/:_authToken"));
+            let lines: Vec<&str> = injected
+                .lines()
+                .rev()
+                .skip_while(|line| line.is_empty())
+                .take(2)
+                .collect();
+            assert_eq!(lines[0], "//canary.example.com/npm/:_authToken=npm_decoy");
+            assert!(lines[1].ends_with(":registry=https://canary.example.com/npm/"));
+            assert!(!lines[1].starts_with("@acme:"));
+        }
+    }
+
+    #[test]
+    fn without_a_canary_only_a_public_token_can_be_shadowed() {
+        let token = Honeytoken::new("npm_decoy".to_string(), ".npmrc".to_string());
+        assert!(inject("save-exact=true\n", &token, None).is_err());
+        assert!(inject("//registry.npmjs.org/:_authToken=npm_real\n", &token, None).is_ok());
+    }
+}
@@ -0,0 +1,1321 @@
+use super::{pick_name, require_canary};
+use crate::core::{
+    error::{RedTokenError, RedTokenResult},
+    injection::FileType,
+    token::Honeytoken,
+};
+use chrono::{Datelike, Local, Timelike};
+use rand::{seq::SliceRandom, thread_rng, Rng};
+use regex::Regex;
+use std::io::{Cursor, Read, Write};
+use std::ops::Range;
+use zip::{write::SimpleFileOptions, CompressionMethod, DateTime, ZipArchive, ZipWriter};
+
+const DECLARATION: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#;
+const RELATIONSHIPS: &str = "http://schemas.openxmlformats.org/officeDocument/2006/relationships";
+const PACKAGE_RELATIONSHIPS: &str = "http://schemas.openxmlformats.org/package/2006/relationships";
+const SPREADSHEET: &str = "http://schemas.openxmlformats.org/spreadsheetml/2006/main";
+const WORDPROCESSING: &str = "http://schemas.openxmlformats.org/wordprocessingml/2006/main";
+const CONTENT_TYPES: &str = "[Content_Types].xml";
+
+// Accounts the decoys list: system, address and user name
+const ACCOUNTS: [(&str, &str, &str); 6] = [
+    (
+        "Domain admin (break-glass)",
+        "https://adfs.corp.internal",
+        "svc-breakglass",
+    ),
+    (
+        "Production database",
+        "postgres://db-prod-01.internal:5432",
+        "postgres",
+    ),
+    (
+        "AWS root account",
+        "https://signin.aws.amazon.com/console",
+        "aws-root@corp.internal",
+    ),
+    ("Firewall admin", "https://fw01.corp.internal", "admin"),
+    (
+        "Backup server",
+        "https://backup01.corp.internal:8443",
+        "backupadmin",
+    ),
+    (
+        "VPN service account",
+        "https://vpn.corp.internal",
+        "vpn-svc",
+    ),
+];
+const NOTES: [&str; 5] = [
+    "MFA exempt",
+    "rotate quarterly",
+    "shared with on-call",
+    "do not change, used by cron",
+    "temporary, remove after migration",
+];
+const SHEETS: [&str; 4] = ["Passwords", "Credentials", "Accounts", "Logins"];
+
+// Settings that come before w:attachedTemplate in the schema's sequence
+const BEFORE_TEMPLATE: [&str; 24] = [
+    "writeProtection",
+    "view",
+    "zoom",
+    "removePersonalInformation",
+    "removeDateAndTime",
+    "doNotDisplayPageBoundaries",
+    "displayBackgroundShape",
+    "printPostScriptOverText",
+    "printFractionalCharacterWidth",
+    "printFormsData",
+    "embedTrueTypeFonts",
+    "embedSystemFonts",
+    "saveSubsetFonts",
+    "saveFormsData",
+    "mirrorMargins",
+    "alignBordersAndEdges",
+    "bordersDoNotSurroundHeader",
+    "bordersDoNotSurroundFooter",
+    "gutterAtTop",
+    "hideSpellingErrors",
+    "hideGrammaticalErrors",
+    "activeWritingStyle",
+    "proofState",
+    "formsDesign",
+];
+
+// Result of rendering an injection or removal of an Office document
+pub struct RenderedDocument {
+    pub bytes: Vec<u8>,
+    pub key: Option<String>,
+    pub placement: String,
+}
+
+fn invalid(message: impl std::fmt::Display) -> RedTokenError {
+    RedTokenError::InvalidFileFormat(format!("Not an Office document: {}", message))
+}
+
+// One zip entry, kept with its compression and time so untouched parts stay as they were
+struct Part {
+    name: String,
+    data: Vec<u8>,
+    compression: CompressionMethod,
+    modified: Option<DateTime>,
+}
+
+// An OOXML package: the zip entries in their original order
+struct Package {
+    parts: Vec<Part>,
+}
+
+impl Package {
+    fn read(bytes: &[u8]) -> RedTokenResult<Self> {
+        let mut archive = ZipArchive::new(Cursor::new(bytes)).map_err(invalid)?;
+        let mut parts = Vec::with_capacity(archive.len());
+        for index in 0..archive.len() {
+            let mut file = archive.by_index(index).map_err(invalid)?;
+            let mut data = Vec::new();
+            file.read_to_end(&mut data).map_err(invalid)?;
+            // Parts are edited as text, so every XML part must be UTF-8
+            let name = file.name().to_string();
+            if (name.ends_with(".xml") || name.ends_with(".rels"))
+                && std::str::from_utf8(&data).is_err()
+            {
+                return Err(invalid(format!("{} is not UTF-8 XML", name)));
+            }
+            parts.push(Part {
+                name,
+                data,
+                compression: file.compression(),
+                modified: file.last_modified(),
+            });
+        }
+
+        let package = Self { parts };
+        match package.xml(CONTENT_TYPES) {
+            Some(_) => Ok(package),
+            None => Err(invalid(format!("no {}", CONTENT_TYPES))),
+        }
+    }
+
+    fn write(&self) -> RedTokenResult<Vec<u8>> {
+        let failed = |e: zip::result::ZipError| {
+            RedTokenError::InvalidFileFormat(format!("Could not build the document: {}", e))
+        };
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for part in &self.parts {
+            let mut options =
+                SimpleFileOptions::default().compression_method(match part.compression {
+                    CompressionMethod::Stored => CompressionMethod::Stored,
+                    _ => CompressionMethod::Deflated,
+                });
+            if let Some(modified) = part.modified {
+                options = options.last_modified_time(modified);
+            }
+            if part.name.ends_with('/') {
+                writer
+                    .add_directory(part.name.as_str(), options)
+                    .map_err(failed)?;
+            } else {
+                writer
+                    .start_file(part.name.as_str(), options)
+                    .map_err(failed)?;
+                writer.write_all(&part.data).map_err(|e| failed(e.into()))?;
+            }
+        }
+        Ok(writer.finish().map_err(failed)?.into_inner())
+    }
+
+    fn has(&self, name: &str) -> bool {
+        self.parts.iter().any(|part| part.name == name)
+    }
+
+    fn xml(&self, name: &str) -> Option<String> {
+        self.parts
+            .iter()
+            .find(|part| part.name == name)
+            .map(|part| String::from_utf8_lossy(&part.data).into_owned())
+    }
+
+    // Replace a part, or add it at the end stamped with the current time
+    fn set(&mut self, name: &str, xml: String) {
+        match self.parts.iter_mut().find(|part| part.name == name) {
+            Some(part) => part.data = xml.into_bytes(),
+            None => {
+                let now = Local::now();
+                self.parts.push(Part {
+                    name: name.to_string(),
+                    data: xml.into_bytes(),
+                    compression: CompressionMethod::Deflated,
+                    modified: DateTime::from_date_and_time(
+                        now.year() as u16,
+                        now.month() as u8,
+                        now.day() as u8,
+                        now.hour() as u8,
+                        now.minute() as u8,
+                        now.second() as u8,
+                    )
+                    .ok(),
+                });
+            }
+        }
+    }
+
+    fn remove(&mut self, name: &str) {
+        self.parts.retain(|part| part.name != name);
+    }
+
+    // The part `_rels/.rels` names as the main document
+    fn main_part(&self, default: &str) -> String {
+        self.xml("_rels/.rels")
+            .and_then(|rels| {
+                relationships(&rels)
+                    .into_iter()
+                    .find(|relationship| relationship.kind.ends_with("/officeDocument"))
+            })
+            .map(|relationship| resolve("", &relationship.target))
+            .unwrap_or_else(|| default.to_string())
+    }
+
+    // Relationships of a part; an empty list when it has none yet
+    fn rels(&self, part: &str) -> String {
+        self.xml(&rels_name(part)).unwrap_or_else(|| {
+            format!(
+                "{}\n<Relationships xmlns=\"{}\"></Relationships>",
+                DECLARATION, PACKAGE_RELATIONSHIPS
+            )
+        })
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+// The token as a URL path segment; the web server decodes it again
+fn segment(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+// Where opening the document fetches from: `{canary}/doc/{token id}/{file}`. The id
+// keeps the password shown in the document out of the links and of proxy logs.
+fn canary_link(canary_url: &str, token: &Honeytoken, file: &str) -> String {
+    format!("{}/doc/{}/{}", canary_url, token.id.simple(), file)
+}
+
+// Link paths of the token's decoys; documents from older versions carry the value
+fn link_paths(token: &Honeytoken) -> [String; 2] {
+    [
+        format!("/doc/{}/", token.id.simple()),
+        format!("/doc/{}/", escape(&segment(&token.value))),
+    ]
+}
+
+fn password() -> String {
+    let mut rng = thread_rng();
+    let charset = b"ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz23456789";
+    let length = rng.gen_range(14..20);
+    (0..length)
+        .map(|_| charset[rng.gen_range(0..charset.len())] as char)
+        .collect()
+}
+
+// `dir/_rels/name.rels` for `dir/name`
+fn rels_name(part: &str) -> String {
+    match part.rsplit_once('/') {
+        Some((dir, name)) => format!("{}/_rels/{}.rels", dir, name),
+        None => format!("_rels/{}.rels", part),
+    }
+}
+
+fn directory(part: &str) -> &str {
+    part.rsplit_once('/').map_or("", |(dir, _)| dir)
+}
+
+// A relationship target as a part name, relative to the source part's directory
+fn resolve(source: &str, target: &str) -> String {
+    let mut path: Vec<&str> = match target.strip_prefix('/') {
+        Some(_) => Vec::new(),
+        None => directory(source)
+            .split('/')
+            .filter(|name| !name.is_empty())
+            .collect(),
+    };
+    for name in target.split('/') {
+        match name {
+            "" | "." => {}
+            ".." => {
+                path.pop();
+            }
+            name => path.push(name),
+        }
+    }
+    path.join("/")
+}
+
+fn join(dir: &str, name: &str) -> String {
+    match dir {
+        "" => name.to_string(),
+        dir => format!("{}/{}", dir, name),
+    }
+}
+
+// First `{dir}/{stem}{n}.xml` not in the package
+fn free_part(package: &Package, dir: &str, stem: &str) -> (String, usize) {
+    (1..)
+        .map(|n| (join(dir, &format!("{}{}.xml", stem, n)), n))
+        .find(|(name, _)| !package.has(name))
+        .expect("unbounded range")
+}
+
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    Regex::new(&format!(
+        r#"\s{}\s*=\s*["']([^"']*)["']"#,
+        regex::escape(name)
+    ))
+    .expect("valid pattern")
+    .captures(tag)
+    .map(|captures| captures[1].to_string())
+}
+
+struct Relationship {
+    id: String,
+    kind: String,
+    target: String,
+    range: Range<usize>,
+}
+
+fn relationships(rels: &str) -> Vec<Relationship> {
+    Regex::new(r"<Relationship\b[^>]*?(?:/>|>\s*</Relationship>)")
+        .expect("valid pattern")
+        .find_iter(rels)
+        .map(|found| Relationship {
+            id: attribute(found.as_str(), "Id").unwrap_or_default(),
+            kind: attribute(found.as_str(), "Type").unwrap_or_default(),
+            target: attribute(found.as_str(), "Target").unwrap_or_default(),
+            range: found.range(),
+        })
+        .collect()
+}
+
+// `rIdN` one past the highest number in use
+fn next_id(rels: &str) -> String {
+    let highest = relationships(rels)
+        .iter()
+        .filter_map(|relationship| relationship.id.strip_prefix("rId")?.parse::<u32>().ok())
+        .max()
+        .unwrap_or(0);
+    format!("rId{}", highest + 1)
+}
+
+// `fragment` as the last child of the last `qname` element, expanding `<qname/>`
+fn append_child(xml: &str, qname: &str, fragment: &str) -> Option<String> {
+    let mut output = xml.to_string();
+    if let Some(close) = xml.rfind(&format!("</{}>", qname)) {
+        output.insert_str(close, fragment);
+        return Some(output);
+    }
+    let empty = Regex::new(&format!(r"<{}\b[^>]*?/>", regex::escape(qname)))
+        .expect("valid pattern")
+        .find_iter(xml)
+        .last()?;
+    let open = &empty.as_str()[..empty.as_str().len() - 2];
+    output.replace_range(
+        empty.range(),
+        &format!("{}>{}</{}>", open.trim_end(), fragment, qname),
+    );
+    Some(output)
+}
+
+fn add_relationship(rels: &str, id: &str, kind: &str, target: &str, external: bool) -> String {
+    let element = format!(
+        r#"<Relationship Id="{}" Type="{}/{}" Target="{}"{}/>"#,
+        id,
+        RELATIONSHIPS,
+        kind,
+        escape(target),
+        if external {
+            r#" TargetMode="External""#
+        } else {
+            ""
+        }
+    );
+    append_child(rels, "Relationships", &element).unwrap_or_else(|| rels.to_string())
+}
+
+fn remove_relationship(rels: &str, id: &str) -> String {
+    match relationships(rels)
+        .into_iter()
+        .find(|relationship| relationship.id == id)
+    {
+        Some(relationship) => cut(rels, relationship.range),
+        None => rels.to_string(),
+    }
+}
+
+fn cut(xml: &str, range: Range<usize>) -> String {
+    let mut output = xml.to_string();
+    output.replace_range(range, "");
+    output
+}
+
+fn add_override(types: &str, part: &str, content_type: &str) -> RedTokenResult<String> {
+    let element = format!(
+        r#"<Override PartName="/{}" ContentType="application/vnd.openxmlformats-officedocument.{}"/>"#,
+        part, content_type
+    );
+    append_child(types, "Types", &element)
+        .ok_or_else(|| invalid(format!("{} without <Types>", CONTENT_TYPES)))
+}
+
+fn remove_override(types: &str, part: &str) -> String {
+    let pattern = format!(
+        r#"<Override\b[^>]*\bPartName\s*=\s*["']/{}["'][^>]*?/>"#,
+        regex::escape(part)
+    );
+    match Regex::new(&pattern).expect("valid pattern").find(types) {
+        Some(found) => cut(types, found.range()),
+        None => types.to_string(),
+    }
+}
+
+// `w:` or `` for the namespace prefix the root element `local` is written with
+fn prefix(xml: &str, local: &str) -> String {
+    Regex::new(&format!(r"<(?:(\w+):)?{}[\s/>]", regex::escape(local)))
+        .expect("valid pattern")
+        .captures(xml)
+        .and_then(|captures| captures.get(1))
+        .map(|prefix| format!("{}:", prefix.as_str()))
+        .unwrap_or_default()
+}
+
+// Prefix bound to the relationships namespace, or `r` with a declaration to add
+fn relationships_prefix(xml: &str) -> (String, String) {
+    match Regex::new(r#"xmlns:(\w+)\s*=\s*["'][^"']*/officeDocument/(?:2006/)?relationships["']"#)
+        .expect("valid pattern")
+        .captures(xml)
+    {
+        Some(captures) => (captures[1].to_string(), String::new()),
+        None => ("r".to_string(), format!(r#" xmlns:r="{}""#, RELATIONSHIPS)),
+    }
+}
+
+// Every `<qname ...>` or `<qname>` start tag
+fn tags(xml: &str, qname: &str) -> Vec<Range<usize>> {
+    Regex::new(&format!(r"<{}[\s/>][^>]*>", regex::escape(qname)))
+        .expect("valid pattern")
+        .find_iter(xml)
+        .map(|found| found.range())
+        .collect()
+}
+
+// End of the element whose start tag is at `tag`
+fn element_end(xml: &str, tag: &Range<usize>, qname: &str) -> Option<usize> {
+    if xml[tag.clone()].ends_with("/>") {
+        return Some(tag.end);
+    }
+    let close = format!("</{}>", qname);
+    xml[tag.end..]
+        .find(&close)
+        .map(|offset| tag.end + offset + close.len())
+}
+
+// Skeleton of a new document, before the decoy is added
+fn skeleton(file_type: &FileType) -> Package {
+    let (main, main_type, application, document) = match file_type {
+        FileType::Xlsx => (
+            "xl/workbook.xml",
+            "spreadsheetml.sheet.main+xml",
+            "Microsoft Excel",
+            format!(
+                r#"<workbook xmlns="{}" xmlns:r="{}"><sheets></sheets></workbook>"#,
+                SPREADSHEET, RELATIONSHIPS
+            ),
+        ),
+        _ => (
+            "word/document.xml",
+            "wordprocessingml.document.main+xml",
+            "Microsoft Office Word",
+            format!(
+                r#"<w:document xmlns:w="{}" xmlns:r="{}"><w:body><w:sectPr><w:pgSz w:w="12240" w:h="15840"/><w:pgMar w:top="1440" w:right="1440" w:bottom="1440" w:left="1440" w:header="720" w:footer="720" w:gutter="0"/></w:sectPr></w:body></w:document>"#,
+                WORDPROCESSING, RELATIONSHIPS
+            ),
+        ),
+    };
+    let created = (Local::now() - chrono::Duration::days(thread_rng().gen_range(30..400)))
+        .format("%Y-%m-%dT%H:%M:%SZ");
+
+    let mut package = Package { parts: Vec::new() };
+    package.set(
+        CONTENT_TYPES,
+        format!(
+            "{}\n<Types xmlns=\"http://schemas.openxmlformats.org/package/2006/content-types\">\
+             <Default Extension=\"rels\" ContentType=\"application/vnd.openxmlformats-package.relationships+xml\"/>\
+             <Default Extension=\"xml\" ContentType=\"application/xml\"/>\
+             <Override PartName=\"/{}\" ContentType=\"application/vnd.openxmlformats-officedocument.{}\"/>\
+             <Override PartName=\"/docProps/core.xml\" ContentType=\"application/vnd.openxmlformats-package.core-properties+xml\"/>\
+             <Override PartName=\"/docProps/app.xml\" ContentType=\"application/vnd.openxmlformats-officedocument.extended-properties+xml\"/>\
+             </Types>",
+            DECLARATION, main, main_type
+        ),
+    );
+    package.set(
+        "_rels/.rels",
+        format!(
+            "{}\n<Relationships xmlns=\"{}\">\
+             <Relationship Id=\"rId1\" Type=\"{}/officeDocument\" Target=\"{}\"/>\
+             <Relationship Id=\"rId2\" Type=\"{}/metadata/core-properties\" Target=\"docProps/core.xml\"/>\
+             <Relationship Id=\"rId3\" Type=\"{}/extended-properties\" Target=\"docProps/app.xml\"/>\
+             </Relationships>",
+            DECLARATION, PACKAGE_RELATIONSHIPS, RELATIONSHIPS, main, PACKAGE_RELATIONSHIPS, RELATIONSHIPS
+        ),
+    );
+    package.set(main, format!("{}\n{}", DECLARATION, document));
+    package.set(
+        "docProps/core.xml",
+        format!(
+            "{}\n<cp:coreProperties xmlns:cp=\"http://schemas.openxmlformats.org/package/2006/metadata/core-properties\" \
+             xmlns:dc=\"http://purl.org/dc/elements/1.1/\" xmlns:dcterms=\"http://purl.org/dc/terms/\" \
+             xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\">\
+             <dc:creator>Administrator</dc:creator><cp:lastModifiedBy>Administrator</cp:lastModifiedBy>\
+             <dcterms:created xsi:type=\"dcterms:W3CDTF\">{}</dcterms:created>\
+             <dcterms:modified xsi:type=\"dcterms:W3CDTF\">{}</dcterms:modified>\
+             </cp:coreProperties>",
+            DECLARATION, created, created
+        ),
+    );
+    package.set(
+        "docProps/app.xml",
+        format!(
+            "{}\n<Properties xmlns=\"http://schemas.openxmlformats.org/officeDocument/2006/extended-properties\">\
+             <Application>{}</Application></Properties>",
+            DECLARATION, application
+        ),
+    );
+    package
+}
+
+fn inline_cell(reference: &str, text: &str) -> String {
+    format!(
+        r#"<c r="{}" t="inlineStr"><is><t>{}</t></is></c>"#,
+        reference,
+        escape(text)
+    )
+}
+
+// A worksheet of accounts with the token as one of the passwords, and a drawing
+// whose picture is an external image on the canary server
+fn inject_xlsx(
+    package: &mut Package,
+    token: &Honeytoken,
+    canary_url: &str,
+) -> RedTokenResult<(String, String)> {
+    let workbook = package.main_part("xl/workbook.xml");
+    let workbook_xml = package
+        .xml(&workbook)
+        .ok_or_else(|| invalid(format!("no workbook part {}", workbook)))?;
+    let p = prefix(&workbook_xml, "workbook");
+    let sheet_tags = tags(&workbook_xml, &format!("{}sheet", p));
+    let names: Vec<String> = sheet_tags
+        .iter()
+        .filter_map(|tag| attribute(&workbook_xml[tag.clone()], "name"))
+        .collect();
+    let sheet_id = sheet_tags
+        .iter()
+        .filter_map(|tag| {
+            attribute(&workbook_xml[tag.clone()], "sheetId")?
+                .parse::<u32>()
+                .ok()
+        })
+        .max()
+        .unwrap_or(0)
+        + 1;
+    let name = pick_name(&SHEETS, &names);
+
+    let dir = directory(&workbook);
+    let (sheet, n) = free_part(package, &join(dir, "worksheets"), "sheet");
+    let (drawing, k) = free_part(package, &join(dir, "drawings"), "drawing");
+
+    // The workbook lists the sheet through a relationship
+    let workbook_rels = package.rels(&workbook);
+    let id = next_id(&workbook_rels);
+    let (r, declaration) = relationships_prefix(&workbook_xml);
+    let element = format!(
+        r#"<{}sheet name="{}" sheetId="{}" {}:id="{}"{}/>"#,
+        p,
+        escape(&name),
+        sheet_id,
+        r,
+        id,
+        declaration
+    );
+    let workbook_xml = append_child(&workbook_xml, &format!("{}sheets", p), &element)
+        .ok_or_else(|| invalid("workbook without <sheets>"))?;
+    let workbook_rels = add_relationship(
+        &workbook_rels,
+        &id,
+        "worksheet",
+        &format!("worksheets/sheet{}.xml", n),
+        false,
+    );
+
+    let mut rng = thread_rng();
+    let mut accounts: Vec<_> = ACCOUNTS.choose_multiple(&mut rng, 3).collect();
+    accounts.shuffle(&mut rng);
+    let row = rng.gen_range(0..accounts.len());
+    let mut rows = vec![format!(
+        r#"<row r="1">{}</row>"#,
+        ["System", "URL", "Username", "Password", "Notes"]
+            .iter()
+            .zip('A'..)
+            .map(|(header, column)| inline_cell(&format!("{}1", column), header))
+            .collect::<String>()
+    )];
+    for (index, (system, url, user)) in accounts.iter().enumerate() {
+        let secret = match index == row {
+            true => token.value.clone(),
+            false => password(),
+        };
+        let number = index + 2;
+        let cells = [
+            *system,
+            *url,
+            *user,
+            secret.as_str(),
+            NOTES.choose(&mut rng).unwrap(),
+        ]
+        .iter()
+        .zip('A'..)
+        .map(|(text, column)| inline_cell(&format!("{}{}", column, number), text))
+        .collect::<String>();
+        rows.push(format!(r#"<row r="{}">{}</row>"#, number, cells));
+    }
+    let widths = [28, 38, 24, 24, 32]
+        .iter()
+        .enumerate()
+        .map(|(index, width)| {
+            format!(
+                r#"<col min="{0}" max="{0}" width="{1}" customWidth="1"/>"#,
+                index + 1,
+                width
+            )
+        })
+        .collect::<String>();
+    package.set(
+        &sheet,
+        format!(
+            r#"{}
+<worksheet xmlns="{}" xmlns:r="{}"><dimension ref="A1:E{}"/><sheetFormatPr defaultRowHeight="15"/><cols>{}</cols><sheetData>{}</sheetData><pageMargins left="0.7" right="0.7" top="0.75" bottom="0.75" header="0.3" footer="0.3"/><drawing r:id="rId1"/></worksheet>"#,
+            DECLARATION,
+            SPREADSHEET,
+            RELATIONSHIPS,
+            rows.len(),
+            widths,
+            rows.join("")
+        ),
+    );
+    package.set(
+        &rels_name(&sheet),
+        add_relationship(
+            &package.rels(&sheet),
+            "rId1",
+            "drawing",
+            &format!("../drawings/drawing{}.xml", k),
+            false,
+        ),
+    );
+
+    // A one-pixel picture next to the table, linked rather than embedded
+    package.set(
+        &drawing,
+        format!(
+            r#"{}
+<xdr:wsDr xmlns:xdr="http://schemas.openxmlformats.org/drawingml/2006/spreadsheetDrawing" xmlns:a="http://schemas.openxmlformats.org/drawingml/2006/main" xmlns:r="{}"><xdr:oneCellAnchor><xdr:from><xdr:col>6</xdr:col><xdr:colOff>0</xdr:colOff><xdr:row>0</xdr:row><xdr:rowOff>0</xdr:rowOff></xdr:from><xdr:ext cx="9525" cy="9525"/><xdr:pic><xdr:nvPicPr><xdr:cNvPr id="2" name="Picture 1"/><xdr:cNvPicPr><a:picLocks noChangeAspect="1"/></xdr:cNvPicPr></xdr:nvPicPr><xdr:blipFill><a:blip r:link="rId1"/><a:stretch><a:fillRect/></a:stretch></xdr:blipFill><xdr:spPr><a:xfrm><a:off x="0" y="0"/><a:ext cx="9525" cy="9525"/></a:xfrm><a:prstGeom prst="rect"><a:avLst/></a:prstGeom></xdr:spPr></xdr:pic><xdr:clientData/></xdr:oneCellAnchor></xdr:wsDr>"#,
+            DECLARATION, RELATIONSHIPS
+        ),
+    );
+    let link = canary_link(canary_url, token, "image.png");
+    package.set(
+        &rels_name(&drawing),
+        add_relationship(&package.rels(&drawing), "rId1", "image", &link, true),
+    );
+
+    let types = package.xml(CONTENT_TYPES).unwrap_or_default();
+    let types = add_override(&types, &sheet, "spreadsheetml.worksheet+xml")?;
+    let types = add_override(&types, &drawing, "drawing+xml")?;
+    package.set(CONTENT_TYPES, types);
+    package.set(&rels_name(&workbook), workbook_rels);
+    package.set(&workbook, workbook_xml);
+
+    let system = accounts[row].0;
+    Ok((
+        name.clone(),
+        format!(
+            "new sheet {:?} ({}) with the token as the {} password, opening fetches {} as a linked picture",
+            name, sheet, system, link
+        ),
+    ))
+}
+
+// Drop the decoy sheet with its drawing; the workbook view's active and first
+// tab move with it so Excel does not open on a missing sheet
+fn remove_xlsx(package: &mut Package, token: &Honeytoken) -> RedTokenResult<Option<String>> {
+    let needle = escape(&token.value);
+    let Some(sheet) = package
+        .parts
+        .iter()
+        .find(|part| {
+            part.name.contains("/worksheets/")
+                && part.name.ends_with(".xml")
+                && String::from_utf8_lossy(&part.data).contains(&needle)
+        })
+        .map(|part| part.name.clone())
+    else {
+        return Ok(None);
+    };
+
+    let workbook = package.main_part("xl/workbook.xml");
+    let workbook_xml = package
+        .xml(&workbook)
+        .ok_or_else(|| invalid(format!("no workbook part {}", workbook)))?;
+    let workbook_rels = package.rels(&workbook);
+    let Some(relationship) = relationships(&workbook_rels)
+        .into_iter()
+        .find(|relationship| resolve(&workbook, &relationship.target) == sheet)
+    else {
+        return Ok(None);
+    };
+
+    let p = prefix(&workbook_xml, "workbook");
+    let sheet_tags = tags(&workbook_xml, &format!("{}sheet", p));
+    let (r, _) = relationships_prefix(&workbook_xml);
+    let Some(position) = sheet_tags.iter().position(|tag| {
+        attribute(&workbook_xml[tag.clone()], &format!("{}:id", r)).as_deref()
+            == Some(relationship.id.as_str())
+    }) else {
+        return Ok(None);
+    };
+    // A workbook needs a sheet; the only one is redacted instead
+    if sheet_tags.len() == 1 {
+        return Ok(None);
+    }
+    let tag = &sheet_tags[position];
+    let name = attribute(&workbook_xml[tag.clone()], "name").unwrap_or_default();
+    let end = element_end(&workbook_xml, tag, &format!("{}sheet", p)).unwrap_or(tag.end);
+    let mut workbook_xml = cut(&workbook_xml, tag.start..end);
+
+    for view in ["activeTab", "firstSheet"] {
+        let pattern = Regex::new(&format!(r#"\b{}="(\d+)""#, view)).expect("valid pattern");
+        let shifted = pattern.captures(&workbook_xml).and_then(|captures| {
+            let index: usize = captures[1].parse().ok()?;
+            let range = captures.get(1)?.range();
+            match index {
+                index if index > position => Some((range, index - 1)),
+                index if index == position => Some((range, position.saturating_sub(1))),
+                _ => None,
+            }
+        });
+        if let Some((range, index)) = shifted {
+            workbook_xml.replace_range(range, &index.to_string());
+        }
+    }
+
+    // The sheet, its drawings and their relationships, and their content types
+    let mut removed = vec![sheet.clone()];
+    if let Some(sheet_rels) = package.xml(&rels_name(&sheet)) {
+        removed.extend(
+            relationships(&sheet_rels)
+                .iter()
+                .filter(|relationship| relationship.kind.ends_with("/drawing"))
+                .map(|relationship| resolve(&sheet, &relationship.target)),
+        );
+    }
+    let mut types = package.xml(CONTENT_TYPES).unwrap_or_default();
+    for part in &removed {
+        types = remove_override(&types, part);
+        package.remove(part);
+        package.remove(&rels_name(part));
+    }
+    package.set(CONTENT_TYPES, types);
+    package.set(
+        &rels_name(&workbook),
+        remove_relationship(&workbook_rels, &relationship.id),
+    );
+    package.set(&workbook, workbook_xml);
+    Ok(Some(name))
+}
+
+// A paragraph of credentials before the section properties, and the canary as
+// the document's template, which Word fetches on open
+fn inject_docx(
+    package: &mut Package,
+    token: &Honeytoken,
+    canary_url: &str,
+) -> RedTokenResult<(String, String)> {
+    let document = package.main_part("word/document.xml");
+    let document_xml = package
+        .xml(&document)
+        .ok_or_else(|| invalid(format!("no document part {}", document)))?;
+    let w = prefix(&document_xml, "document");
+    let (r, declaration) = relationships_prefix(&document_xml);
+    let document_rels = package.rels(&document);
+
+    // Settings hold the template; a document without them gets a settings part
+    let settings = relationships(&document_rels)
+        .into_iter()
+        .find(|relationship| relationship.kind.ends_with("/settings"))
+        .map(|relationship| resolve(&document, &relationship.target));
+    let (settings, mut document_rels) = match settings {
+        Some(settings) => (settings, document_rels),
+        None => {
+            let settings = join(directory(&document), "settings.xml");
+            let id = next_id(&document_rels);
+            package.set(
+                &settings,
+                format!(
+                    r#"{}
+<w:settings xmlns:w="{}" xmlns:r="{}"><w:zoom w:percent="100"/><w:defaultTabStop w:val="720"/><w:characterSpacingControl w:val="doNotCompress"/><w:compat><w:compatSetting w:name="compatibilityMode" w:uri="http://schemas.microsoft.com/office/word" w:val="15"/></w:compat></w:settings>"#,
+                    DECLARATION, WORDPROCESSING, RELATIONSHIPS
+                ),
+            );
+            let types = package.xml(CONTENT_TYPES).unwrap_or_default();
+            package.set(
+                CONTENT_TYPES,
+                add_override(&types, &settings, "wordprocessingml.settings+xml")?,
+            );
+            let rels = add_relationship(&document_rels, &id, "settings", "settings.xml", false);
+            (settings, rels)
+        }
+    };
+    let settings_xml = package
+        .xml(&settings)
+        .ok_or_else(|| invalid(format!("no settings part {}", settings)))?;
+    let s = prefix(&settings_xml, "settings");
+
+    let (system, url, user) = *ACCOUNTS.choose(&mut thread_rng()).expect("not empty");
+    let mut paragraph = format!(
+        r#"<{w}p><{w}r><{w}rPr><{w}b/></{w}rPr><{w}t>{}</{w}t></{w}r><{w}r><{w}br/><{w}t xml:space="preserve">URL: {}</{w}t><{w}br/><{w}t xml:space="preserve">Username: {}</{w}t><{w}br/><{w}t xml:space="preserve">Password: {}</{w}t></{w}r>"#,
+        escape(system),
+        escape(url),
+        escape(user),
+        escape(&token.value),
+        w = w
+    );
+
+    let (link, reference) = if settings_xml.contains(&format!("<{}attachedTemplate", s)) {
+        // The document already has a template; a linked picture loads on open too
+        let link = canary_link(canary_url, token, "image.png");
+        let id = next_id(&document_rels);
+        document_rels = add_relationship(&document_rels, &id, "image", &link, true);
+        let docpr = thread_rng().gen_range(1000..100000);
+        paragraph.push_str(&format!(
+            r#"<{w}r><{w}drawing><wp:inline distT="0" distB="0" distL="0" distR="0" xmlns:wp="http://schemas.openxmlformats.org/drawingml/2006/wordprocessingDrawing"><wp:extent cx="9525" cy="9525"/><wp:docPr id="{}" name="Picture {}"/><a:graphic xmlns:a="http://schemas.openxmlformats.org/drawingml/2006/main"><a:graphicData uri="http://schemas.openxmlformats.org/drawingml/2006/picture"><pic:pic xmlns:pic="http://schemas.openxmlformats.org/drawingml/2006/picture"><pic:nvPicPr><pic:cNvPr id="0" name="image.png"/><pic:cNvPicPr/></pic:nvPicPr><pic:blipFill><a:blip {}:link="{}"{}/><a:stretch><a:fillRect/></a:stretch></pic:blipFill><pic:spPr><a:xfrm><a:off x="0" y="0"/><a:ext cx="9525" cy="9525"/></a:xfrm><a:prstGeom prst="rect"><a:avLst/></a:prstGeom></pic:spPr></pic:pic></a:graphicData></a:graphic></wp:inline></{w}drawing></{w}r>"#,
+            docpr,
+            docpr,
+            r,
+            id,
+            declaration,
+            w = w
+        ));
+        (link, "a linked picture")
+    } else {
+        let link = canary_link(canary_url, token, "template.dotx");
+        let settings_rels = package.rels(&settings);
+        let id = next_id(&settings_rels);
+        let (sr, declaration) = relationships_prefix(&settings_xml);
+        let element = format!(
+            r#"<{}attachedTemplate {}:id="{}"{}/>"#,
+            s, sr, id, declaration
+        );
+        // After the last setting the schema puts before it, else first
+        let position = BEFORE_TEMPLATE
+            .iter()
+            .flat_map(|local| {
+                let qname = format!("{}{}", s, local);
+                tags(&settings_xml, &qname)
+                    .into_iter()
+                    .filter_map(|tag| element_end(&settings_xml, &tag, &qname))
+                    .collect::<Vec<_>>()
+            })
+            .max()
+            .or_else(|| {
+                tags(&settings_xml, &format!("{}settings", s))
+                    .first()
+                    .map(|tag| tag.end)
+            })
+            .ok_or_else(|| invalid("settings without <w:settings>"))?;
+        let mut settings_xml = settings_xml;
+        settings_xml.insert_str(position, &element);
+        package.set(&settings, settings_xml);
+        package.set(
+            &rels_name(&settings),
+            add_relationship(&settings_rels, &id, "attachedTemplate", &link, true),
+        );
+        (link, "the attached template")
+    };
+    paragraph.push_str(&format!("</{}p>", w));
+
+    // The body's own section properties end it, so the paragraph goes before them
+    let body_end = document_xml
+        .rfind(&format!("</{}body>", w))
+        .ok_or_else(|| invalid("document without <w:body>"))?;
+    let position = document_xml[..body_end]
+        .rfind(&format!("<{}sectPr", w))
+        .filter(|&start| !document_xml[start..body_end].contains(&format!("</{}p>", w)))
+        .unwrap_or(body_end);
+    let mut document_xml = document_xml;
+    document_xml.insert_str(position, &paragraph);
+
+    package.set(&rels_name(&document), document_rels);
+    package.set(&document, document_xml);
+    Ok((
+        system.to_string(),
+        format!(
+            "new paragraph with {} credentials at the end of the body, opening fetches {} as {}",
+            system, link, reference
+        ),
+    ))
+}
+
+// Drop the paragraph holding the token and the template or picture pointing at the canary
+fn remove_docx(package: &mut Package, token: &Honeytoken) -> RedTokenResult<Option<String>> {
+    let document = package.main_part("word/document.xml");
+    let Some(document_xml) = package.xml(&document) else {
+        return Ok(None);
+    };
+    let w = prefix(&document_xml, "document");
+    let Some(found) = document_xml.find(&format!("Password: {}", escape(&token.value))) else {
+        return Ok(None);
+    };
+    let Some(start) = tags(&document_xml[..found], &format!("{}p", w))
+        .last()
+        .map(|tag| tag.start)
+    else {
+        return Ok(None);
+    };
+    let close = format!("</{}p>", w);
+    let Some(end) = document_xml[found..]
+        .find(&close)
+        .map(|offset| found + offset + close.len())
+    else {
+        return Ok(None);
+    };
+    let paragraph = document_xml[start..end].to_string();
+    let system = Regex::new(&format!(r"<{0}t>([^<]*)</{0}t>", regex::escape(&w)))
+        .expect("valid pattern")
+        .captures(&paragraph)
+        .map(|captures| captures[1].to_string());
+
+    let links = link_paths(token);
+    let is_decoy = |target: &str| links.iter().any(|link| target.contains(link.as_str()));
+    let mut document_rels = package.rels(&document);
+    for relationship in relationships(&document_rels) {
+        if is_decoy(&relationship.target) && paragraph.contains(&format!("\"{}\"", relationship.id))
+        {
+            document_rels = remove_relationship(&document_rels, &relationship.id);
+        }
+    }
+
+    let settings = relationships(&document_rels)
+        .into_iter()
+        .find(|relationship| relationship.kind.ends_with("/settings"))
+        .map(|relationship| resolve(&document, &relationship.target));
+    if let Some((settings, mut settings_xml)) =
+        settings.and_then(|settings| Some((settings.clone(), package.xml(&settings)?)))
+    {
+        let s = prefix(&settings_xml, "settings");
+        let (sr, _) = relationships_prefix(&settings_xml);
+        let mut settings_rels = package.rels(&settings);
+        for relationship in relationships(&settings_rels) {
+            if !is_decoy(&relationship.target) {
+                continue;
+            }
+            let qname = format!("{}attachedTemplate", s);
+            let template = tags(&settings_xml, &qname).into_iter().find(|tag| {
+                attribute(&settings_xml[tag.clone()], &format!("{}:id", sr)).as_deref()
+                    == Some(relationship.id.as_str())
+            });
+            if let Some(tag) = template {
+                let end = element_end(&settings_xml, &tag, &qname).unwrap_or(tag.end);
+                settings_xml = cut(&settings_xml, tag.start..end);
+            }
+            settings_rels = remove_relationship(&settings_rels, &relationship.id);
+        }
+        package.set(&settings, settings_xml);
+        match relationships(&settings_rels).is_empty() {
+            true => package.remove(&rels_name(&settings)),
+            false => package.set(&rels_name(&settings), settings_rels),
+        }
+    }
+
+    package.set(&rels_name(&document), document_rels);
+    package.set(&document, cut(&document_xml, start..end));
+    Ok(Some(system.unwrap_or_default()))
+}
+
+// Replace the token, in text and in links, in every XML part
+fn redact(package: &mut Package, token: &Honeytoken) -> usize {
+    let needles = [
+        escape(&token.value),
+        escape(&segment(&token.value)),
+        token.id.simple().to_string(),
+    ];
+    let mut occurrences = 0;
+    for part in package
+        .parts
+        .iter_mut()
+        .filter(|part| part.name.ends_with(".xml") || part.name.ends_with(".rels"))
+    {
+        let mut xml = String::from_utf8_lossy(&part.data).into_owned();
+        let before = occurrences;
+        for needle in needles.iter().filter(|needle| !needle.is_empty()) {
+            occurrences += xml.matches(needle.as_str()).count();
+            xml = xml.replace(needle.as_str(), "[REDACTED]");
+        }
+        if occurrences > before {
+            part.data = xml.into_bytes();
+        }
+    }
+    occurrences
+}
+
+// The decoy added to a document, or to a new one when the file does not exist yet
+pub fn inject(
+    bytes: &[u8],
+    file_type: &FileType,
+    token: &Honeytoken,
+    canary_url: Option<&str>,
+) -> RedTokenResult<RenderedDocument> {
+    let canary_url = require_canary(canary_url, "Document")?;
+    let mut package = match bytes.is_empty() {
+        true => skeleton(file_type),
+        false => Package::read(bytes)?,
+    };
+    let (key, placement) = match file_type {
+        FileType::Xlsx => inject_xlsx(&mut package, token, canary_url)?,
+        _ => inject_docx(&mut package, token, canary_url)?,
+    };
+
+    let bytes = package.write()?;
+    Package::read(&bytes)?;
+    Ok(RenderedDocument {
+        bytes,
+        key: Some(key),
+        placement,
+    })
+}
+
+pub fn remove(
+    bytes: &[u8],
+    file_type: &FileType,
+    token: &Honeytoken,
+) -> RedTokenResult<RenderedDocument> {
+    let mut package = Package::read(bytes)?;
+    let removed = match file_type {
+        FileType::Xlsx => remove_xlsx(&mut package, token)?,
+        _ => remove_docx(&mut package, token)?,
+    };
+    let (key, placement) = match (removed, file_type) {
+        (Some(name), FileType::Xlsx) => {
+            let placement = format!("removed the sheet {:?} and its linked image", name);
+            (Some(name), placement)
+        }
+        (Some(system), _) => (
+            Some(system.clone()),
+            format!("removed the {} paragraph and its canary link", system),
+        ),
+        (None, _) => {
+            let occurrences = redact(&mut package, token);
+            (
+                None,
+                format!("{} occurrence(s) replaced with [REDACTED]", occurrences),
+            )
+        }
+    };
+
+    let bytes = package.write()?;
+    Package::read(&bytes)?;
+    Ok(RenderedDocument {
+        bytes,
+        key,
+        placement,
+    })
+}
+
+// Part names with their XML one tag per line, so a preview can diff documents
+pub fn outline(bytes: &[u8]) -> String {
+    if bytes.is_empty() {
+        return String::new();
+    }
+    let package = match Package::read(bytes) {
+        Ok(package) => package,
+        Err(e) => return format!("{}\n", e),
+    };
+    package
+        .parts
+        .iter()
+        .map(|part| {
+            let body = match std::str::from_utf8(&part.data) {
+                Ok(xml) if part.name.ends_with(".xml") || part.name.ends_with(".rels") => xml
+                    .replace("><", ">\n<")
+                    .lines()
+                    .map(|line| format!("  {}\n", line))
+                    .collect(),
+                _ => format!("  ({} bytes)\n", part.data.len()),
+            };
+            format!("{}\n{}", part.name, body)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    const CANARY: &str = "https://canary.example.com";
+
+    fn token(value: &str) -> Honeytoken {
+        Honeytoken::new(value.to_string(), "report".to_string())
+    }
+
+    // Every part of the zip as text, checking each XML part is well-formed
+    fn unzip(bytes: &[u8]) -> BTreeMap<String, String> {
+        let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+        let mut parts = BTreeMap::new();
+        for index in 0..archive.len() {
+            let mut file = archive.by_index(index).unwrap();
+            let mut xml = String::new();
+            file.read_to_string(&mut xml).unwrap();
+            assert_well_formed(file.name(), &xml);
+            parts.insert(file.name().to_string(), xml);
+        }
+        parts
+    }
+
+    // One root element and every start tag closed in order
+    fn assert_well_formed(name: &str, xml: &str) {
+        let body = xml.strip_prefix(DECLARATION).unwrap_or(xml);
+        let mut open: Vec<&str> = Vec::new();
+        let mut roots = 0;
+        for tag in Regex::new(r"<(/?)([\w:.-]+)[^>]*?(/?)>")
+            .unwrap()
+            .captures_iter(body)
+        {
+            let qname = tag.get(2).unwrap().as_str();
+            match (&tag[1], &tag[3]) {
+                ("/", _) => assert_eq!(open.pop(), Some(qname), "{}: unbalanced", name),
+                (_, close) => {
+                    if open.is_empty() {
+                        roots += 1;
+                    }
+                    if close.is_empty() {
+                        open.push(qname);
+                    }
+                }
+            }
+        }
+        assert!(open.is_empty(), "{}: {:?} left open", name, open);
+        assert_eq!(roots, 1, "{}: one root element", name);
+    }
+
+    // The target of the relationship `id` in a .rels part, if it is external
+    fn external_target(rels: &str, id: &str) -> String {
+        let relationship = relationships(rels)
+            .into_iter()
+            .find(|relationship| relationship.id == id)
+            .unwrap();
+        assert!(rels[relationship.range].contains(r#"TargetMode="External""#));
+        relationship.target
+    }
+
+    fn has_override(types: &str, part: &str) -> bool {
+        types.contains(&format!(r#"PartName="/{}""#, part))
+    }
+
+    #[test]
+    fn new_docx_attaches_the_canary_as_its_template() {
+        let token = token("Tk3&<pass>");
+        let rendered = inject(&[], &FileType::Docx, &token, Some(CANARY)).unwrap();
+        let parts = unzip(&rendered.bytes);
+
+        let document = &parts["word/document.xml"];
+        let paragraph = document.find("Password: Tk3&amp;&lt;pass&gt;").unwrap();
+        assert!(paragraph < document.find("<w:sectPr").unwrap());
+
+        let document_rels = &parts["word/_rels/document.xml.rels"];
+        assert!(document_rels.contains(r#"/settings" Target="settings.xml""#));
+        assert!(has_override(&parts[CONTENT_TYPES], "word/settings.xml"));
+
+        let id = attribute(&parts["word/settings.xml"], "r:id").unwrap();
+        assert_eq!(
+            external_target(&parts["word/_rels/settings.xml.rels"], &id),
+            format!("{}/doc/{}/template.dotx", CANARY, token.id.simple())
+        );
+        // The password is only in the paragraph, not in the links
+        let value = ["Tk3", "Tk3%26"];
+        for (name, xml) in &parts {
+            if name != "word/document.xml" {
+                assert!(!value.iter().any(|v| xml.contains(v)), "{}", name);
+            }
+        }
+    }
+
+    #[test]
+    fn docx_linked_by_token_value_is_still_removed() {
+        let token = token("Legacy-1");
+        let rendered = inject(&[], &FileType::Docx, &token, Some(CANARY)).unwrap();
+        // As written before links carried the id
+        let mut package = Package::read(&rendered.bytes).unwrap();
+        for part in &mut package.parts {
+            let xml = String::from_utf8(part.data.clone()).unwrap();
+            part.data = xml
+                .replace(&token.id.simple().to_string(), &token.value)
+                .into_bytes();
+        }
+        let legacy = package.write().unwrap();
+
+        let removed = remove(&legacy, &FileType::Docx, &token).unwrap();
+        assert!(removed.key.is_some());
+        let parts = unzip(&removed.bytes);
+        assert!(!parts["word/settings.xml"].contains("attachedTemplate"));
+        assert!(!parts.values().any(|xml| xml.contains("Legacy-1")));
+    }
+
+    #[test]
+    fn docx_with_a_template_gets_a_linked_picture_and_is_restored() {
+        let first = inject(&[], &FileType::Docx, &token("Rt-one"), Some(CANARY)).unwrap();
+        let second = token("Rt-two");
+        let rendered = inject(&first.bytes, &FileType::Docx, &second, Some(CANARY)).unwrap();
+        let parts = unzip(&rendered.bytes);
+
+        // Still the one template, now with a picture linked from the new paragraph
+        assert_eq!(
+            parts["word/settings.xml"]
+                .matches("attachedTemplate")
+                .count(),
+            1
+        );
+        let document = &parts["word/document.xml"];
+        let id = Regex::new(r#"<a:blip r:link="(rId\d+)""#)
+            .unwrap()
+            .captures(document)
+            .unwrap()[1]
+            .to_string();
+        assert_eq!(
+            external_target(&parts["word/_rels/document.xml.rels"], &id),
+            format!("{}/doc/{}/image.png", CANARY, second.id.simple())
+        );
+
+        let removed = remove(&rendered.bytes, &FileType::Docx, &second).unwrap();
+        assert_eq!(unzip(&removed.bytes), unzip(&first.bytes));
+    }
+
+    #[test]
+    fn new_xlsx_has_a_linked_sheet_and_drawing() {
+        let token = token("Xl5-token");
+        let rendered = inject(&[], &FileType::Xlsx, &token, Some(CANARY)).unwrap();
+        let parts = unzip(&rendered.bytes);
+        let name = rendered.key.unwrap();
+
+        // Workbook -> sheet -> drawing -> canary picture
+        let workbook = &parts["xl/workbook.xml"];
+        assert!(workbook.contains(&format!(
+            r#"<sheet name="{}" sheetId="1" r:id="rId1"/>"#,
+            name
+        )));
+        assert!(parts["xl/_rels/workbook.xml.rels"]
+            .contains(r#"Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet" Target="worksheets/sheet1.xml""#));
+
+        let sheet = &parts["xl/worksheets/sheet1.xml"];
+        assert!(sheet.contains(r#"<t>Password</t>"#));
+        assert!(sheet.contains("<t>Xl5-token</t>"));
+        assert!(sheet.contains(r#"<dimension ref="A1:E4"/>"#));
+        assert!(sheet.contains(r#"<drawing r:id="rId1"/>"#));
+        assert!(parts["xl/worksheets/_rels/sheet1.xml.rels"]
+            .contains(r#"Target="../drawings/drawing1.xml""#));
+
+        assert!(parts["xl/drawings/drawing1.xml"].contains(r#"<a:blip r:link="rId1"/>"#));
+        assert_eq!(
+            external_target(&parts["xl/drawings/_rels/drawing1.xml.rels"], "rId1"),
+            format!("{}/doc/{}/image.png", CANARY, token.id.simple())
+        );
+
+        let types = &parts[CONTENT_TYPES];
+        assert!(has_override(types, "xl/worksheets/sheet1.xml"));
+        assert!(has_override(types, "xl/drawings/drawing1.xml"));
+    }
+
+    #[test]
+    fn xlsx_sheet_removal_restores_the_workbook() {
+        let first = inject(&[], &FileType::Xlsx, &token("Rt-one"), Some(CANARY)).unwrap();
+        let second = token("Rt-two");
+        let rendered = inject(&first.bytes, &FileType::Xlsx, &second, Some(CANARY)).unwrap();
+        let parts = unzip(&rendered.bytes);
+        assert!(parts["xl/workbook.xml"].contains(r#"sheetId="2" r:id="rId2""#));
+        assert!(parts.contains_key("xl/worksheets/sheet2.xml"));
+        assert!(parts.contains_key("xl/drawings/_rels/drawing2.xml.rels"));
+
+        let removed = remove(&rendered.bytes, &FileType::Xlsx, &second).unwrap();
+        assert_eq!(unzip(&removed.bytes), unzip(&first.bytes));
+
+        // The last sheet stays, with the token redacted
+        let redacted = remove(&first.bytes, &FileType::Xlsx, &token("Rt-one")).unwrap();
+        let parts = unzip(&redacted.bytes);
+        assert!(redacted.key.is_none());
+        assert!(parts["xl/worksheets/sheet1.xml"].contains("<t>[REDACTED]</t>"));
+        assert!(!parts.values().any(|xml| xml.contains("Rt-one")));
+    }
+}
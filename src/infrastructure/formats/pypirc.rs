@@ -0,0 +1,199 @@
+use super::ini::{append_block, drop_section, join, key_value, sections, value_end};
+use super::{pick_name, redact, Rendered};
+use crate::core::token::Honeytoken;
+
+// Section names when [pypi] is already taken; they upload to PyPI all the same
+const NAMES: [&str; 3] = ["pypi-release", "pypi-ci", "pypi-upload"];
+
+// The `index-servers` line in [distutils] and where its value ends
+fn index_servers(lines: &[&str]) -> Option<(usize, usize)> {
+    let distutils = sections(lines)
+        .into_iter()
+        .find(|section| section.name == "distutils")?;
+    let start = distutils.header? + 1;
+    let end = distutils.last_key.map_or(start, |last| last + 1);
+    let line = (start..end).find(|&index| {
+        key_value(lines[index].trim()).is_some_and(|(key, _)| key == "index-servers")
+    })?;
+    Some((line, value_end(lines, line)))
+}
+
+// Add `name` to `index-servers`, which twine reads to find the sections it uses
+fn list_server(content: &str, name: &str) -> Option<String> {
+    let lines: Vec<&str> = content.lines().collect();
+    let (line, end) = index_servers(&lines)?;
+    let inline = key_value(lines[line].trim()).map_or("", |(_, value)| value);
+    let listed = inline
+        .split_whitespace()
+        .chain(lines[line + 1..end].iter().map(|line| line.trim()))
+        .any(|server| server == name);
+    if listed {
+        return None;
+    }
+
+    let mut output: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+    if end > line + 1 {
+        let continuation = lines[line + 1];
+        let indent = &continuation[..continuation.len() - continuation.trim_start().len()];
+        output.insert(end, format!("{}{}", indent, name));
+    } else if !inline.is_empty() {
+        output[line] = format!("{} {}", lines[line].trim_end(), name);
+    } else {
+        output.insert(end, format!("    {}", name));
+    }
+    Some(join(&output, content.ends_with('\n')))
+}
+
+// Undo `list_server`
+fn unlist_server(content: &str, name: &str) -> Option<String> {
+    let lines: Vec<&str> = content.lines().collect();
+    let (line, end) = index_servers(&lines)?;
+
+    let mut output: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+    if let Some(index) = (line + 1..end).find(|&index| lines[index].trim() == name) {
+        output.remove(index);
+    } else {
+        let suffix = format!(" {}", name);
+        output[line] = lines[line].trim_end().strip_suffix(&suffix)?.to_string();
+    }
+    Some(join(&output, content.ends_with('\n')))
+}
+
+// A repository section with an API token, as `twine upload` expects it
+pub fn inject(content: &str, token: &Honeytoken) -> Rendered {
+    let lines: Vec<&str> = content.lines().collect();
+    let taken: Vec<String> = sections(&lines)
+        .into_iter()
+        .filter(|section| section.header.is_some())
+        .map(|section| section.name)
+        .collect();
+    let name = if taken.iter().any(|name| name == "pypi") {
+        pick_name(&NAMES, &taken)
+    } else {
+        "pypi".to_string()
+    };
+
+    let mut block = vec![format!("[{}]", name)];
+    if name != "pypi" {
+        block.push("repository = https://upload.pypi.org/legacy/".to_string());
+    }
+    block.push("username = __token__".to_string());
+    block.push(format!("password = {}", token.value));
+
+    let listed = list_server(content, &name);
+    let mut placement = format!("new section [{}] at the end of the file", name);
+    if listed.is_some() {
+        placement.push_str(", listed in [distutils] index-servers");
+    }
+
+    Rendered {
+        content: append_block(listed.as_deref().unwrap_or(content), &block.join("\n")),
+        key: Some(name),
+        placement,
+    }
+}
+
+// Drop the section whose password is the token, and its index-servers entry
+pub fn remove(content: &str, token: &Honeytoken) -> Rendered {
+    let dropped = drop_section(content, |_, body| {
+        body.iter().any(|line| {
+            key_value(line.trim())
+                .is_some_and(|(key, value)| key == "password" && value == token.value)
+        })
+    });
+    let Some((content, name)) = dropped else {
+        return redact(content, token);
+    };
+
+    // Only when no other section by that name is left to need it
+    let lines: Vec<&str> = content.lines().collect();
+    let still_used = sections(&lines)
+        .iter()
+        .any(|section| section.header.is_some() && section.name == name);
+    let content = if still_used {
+        content
+    } else {
+        unlist_server(&content, &name).unwrap_or(content)
+    };
+
+    Rendered {
+        content,
+        placement: format!("removed section [{}]", name),
+        key: Some(name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(original: &str) -> (String, String) {
+        let token = Honeytoken::new("pypi-AgEIcHlwaS5vcmc".to_string(), ".pypirc".to_string());
+        let injected = inject(original, &token);
+        assert_eq!(remove(&injected.content, &token).content, original);
+        (injected.content, injected.key.unwrap())
+    }
+
+    // The options of `section`, as twine reads them
+    fn options(content: &str, section: &str) -> Vec<(String, String)> {
+        let lines: Vec<&str> = content.lines().collect();
+        let found = sections(&lines)
+            .into_iter()
+            .find(|found| found.name == section)
+            .unwrap();
+        let start = found.header.unwrap() + 1;
+        lines[start..found.last_key.unwrap() + 1]
+            .iter()
+            .filter_map(|line| key_value(line.trim()))
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn a_new_file_gets_a_pypi_section() {
+        let (content, name) = round_trip("");
+        assert_eq!(name, "pypi");
+        assert_eq!(
+            content,
+            "[pypi]\nusername = __token__\npassword = pypi-AgEIcHlwaS5vcmc\n"
+        );
+    }
+
+    #[test]
+    fn the_section_is_listed_in_index_servers() {
+        for original in [
+            "[distutils]\nindex-servers =\n    testpypi\n\n[testpypi]\nrepository = https://test.pypi.org/legacy/\n",
+            "[distutils]\nindex-servers = testpypi\n\n[testpypi]\nusername = __token__\n\n\n",
+            "[distutils]\nindex-servers =\n\n[internal]\nrepository = https://pypi.internal/",
+        ] {
+            let (content, name) = round_trip(original);
+            let lines: Vec<&str> = content.lines().collect();
+            let (line, end) = index_servers(&lines).unwrap();
+            let listed: Vec<&str> = lines[line..end]
+                .iter()
+                .flat_map(|line| line.split_whitespace())
+                .collect();
+            assert!(listed.contains(&name.as_str()), "{} not listed in\n{}", name, content);
+            assert_eq!(
+                options(&content, &name).last().unwrap(),
+                &("password".to_string(), "pypi-AgEIcHlwaS5vcmc".to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn a_taken_pypi_section_gets_a_named_repository() {
+        let original = "[distutils]\nindex-servers =\n    pypi\n\n[pypi]\nusername = __token__\npassword = pypi-real\n";
+        let (content, name) = round_trip(original);
+        assert!(NAMES.contains(&name.as_str()));
+        assert_eq!(
+            options(&content, &name)[0],
+            (
+                "repository".to_string(),
+                "https://upload.pypi.org/legacy/".to_string()
+            )
+        );
+        // The real section is untouched
+        assert_eq!(options(&content, "pypi"), options(original, "pypi"));
+    }
+}
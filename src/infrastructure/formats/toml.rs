@@ -0,0 +1,209 @@
+use super::{file_name, free_key, looks_secret, redact, Rendered};
+use crate::core::{
+    error::{RedTokenError, RedTokenResult},
+    token::Honeytoken,
+};
+use toml_edit::{value, DocumentMut, Item, Table, TableLike};
+
+fn parse(content: &str) -> RedTokenResult<DocumentMut> {
+    content
+        .parse::<DocumentMut>()
+        .map_err(|e| RedTokenError::InvalidFileFormat(format!("Invalid TOML: {}", e)))
+}
+
+// Well-known files get the table their tools reserve for extra settings; otherwise
+// the first table that already looks like it holds secrets, or a new [credentials]
+fn target_table(doc: &DocumentMut, file_name: &str) -> Vec<String> {
+    let path: &[&str] = match file_name {
+        "cargo.toml" if doc.contains_key("package") => &["package", "metadata", "deploy"],
+        "cargo.toml" => &["workspace", "metadata", "deploy"],
+        "pyproject.toml" => &["tool", "deploy"],
+        _ => {
+            return find_secret_table(doc.as_table(), &mut Vec::new())
+                .unwrap_or_else(|| vec!["credentials".to_string()])
+        }
+    };
+    path.iter().map(|segment| segment.to_string()).collect()
+}
+
+fn find_secret_table(table: &Table, path: &mut Vec<String>) -> Option<Vec<String>> {
+    for (key, item) in table.iter() {
+        if let Item::Table(child) = item {
+            path.push(key.to_string());
+            if looks_secret(key) && !child.is_implicit() {
+                return Some(path.clone());
+            }
+            if let Some(found) = find_secret_table(child, path) {
+                return Some(found);
+            }
+            path.pop();
+        }
+    }
+    None
+}
+
+// Walk to the table at `path`, creating missing ones; intermediate tables stay implicit
+fn ensure_table<'a>(root: &'a mut Table, path: &[String]) -> RedTokenResult<&'a mut Table> {
+    let mut current = root;
+    for (index, segment) in path.iter().enumerate() {
+        let item = current.entry(segment).or_insert_with(|| {
+            let mut table = Table::new();
+            table.set_implicit(index + 1 < path.len());
+            Item::Table(table)
+        });
+        current = item.as_table_mut().ok_or_else(|| {
+            RedTokenError::InvalidFileFormat(format!(
+                "TOML key {} is not a table",
+                path[..=index].join(".")
+            ))
+        })?;
+    }
+    Ok(current)
+}
+
+pub fn inject(content: &str, file_path: &str, token: &Honeytoken) -> RedTokenResult<Rendered> {
+    let mut doc = parse(content)?;
+    let path = target_table(&doc, &file_name(file_path));
+    let existed = doc.as_table().get(&path[0]).is_some_and(|item| {
+        path[1..]
+            .iter()
+            .try_fold(item, |item, key| item.get(key))
+            .is_some()
+    });
+
+    let table = ensure_table(doc.as_table_mut(), &path)?;
+    let key = free_key("api_key", |key| table.contains_key(key));
+    table.insert(&key, value(token.value.clone()));
+
+    Ok(Rendered {
+        content: doc.to_string(),
+        key: Some(key),
+        placement: format!(
+            "{} [{}]",
+            if existed {
+                "new key in existing table"
+            } else {
+                "new table"
+            },
+            path.join(".")
+        ),
+    })
+}
+
+// Delete the keys holding the token, and tables left empty by that
+pub fn remove(content: &str, token: &Honeytoken) -> RedTokenResult<Rendered> {
+    let mut doc = parse(content)?;
+    let mut removed = Vec::new();
+    remove_from(doc.as_table_mut(), "", &token.value, &mut removed);
+
+    if removed.is_empty() {
+        return Ok(redact(content, token));
+    }
+    Ok(Rendered {
+        content: doc.to_string(),
+        key: removed.first().cloned(),
+        placement: format!("removed {}", removed.join(", ")),
+    })
+}
+
+fn remove_from(table: &mut dyn TableLike, path: &str, token: &str, removed: &mut Vec<String>) {
+    let join = |key: &str| {
+        if path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", path, key)
+        }
+    };
+
+    let matching: Vec<String> = table
+        .iter()
+        .filter(|(_, item)| item.as_str() == Some(token))
+        .map(|(key, _)| key.to_string())
+        .collect();
+    for key in matching {
+        table.remove(&key);
+        removed.push(join(&key));
+    }
+
+    let children: Vec<String> = table
+        .iter()
+        .filter(|(_, item)| item.is_table_like())
+        .map(|(key, _)| key.to_string())
+        .collect();
+    for key in children {
+        let before = removed.len();
+        let now_empty = match table.get_mut(&key).and_then(Item::as_table_like_mut) {
+            Some(child) => {
+                remove_from(child, &join(&key), token, removed);
+                child.is_empty()
+            }
+            None => false,
+        };
+        if removed.len() > before && now_empty {
+            table.remove(&key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(original: &str, file_path: &str) -> DocumentMut {
+        let token = Honeytoken::new("RT_toml_decoy".to_string(), file_path.to_string());
+        let injected = inject(original, file_path, &token).unwrap();
+        assert_eq!(remove(&injected.content, &token).unwrap().content, original);
+        parse(&injected.content).unwrap()
+    }
+
+    #[test]
+    fn cargo_toml_gets_package_metadata() {
+        let cargo = "[package]\nname = \"billing\"  # the service\nversion = \"0.3.1\"\n\n\
+                     [dependencies]\nserde = { version = \"1\", features = [\"derive\"] }\n";
+        let doc = round_trip(cargo, "Cargo.toml");
+        assert_eq!(
+            doc["package"]["metadata"]["deploy"]["api_key"].as_str(),
+            Some("RT_toml_decoy")
+        );
+
+        let workspace = "[workspace]\nmembers = [\"api\", \"worker\"]\n";
+        let doc = round_trip(workspace, "/src/Cargo.toml");
+        assert_eq!(
+            doc["workspace"]["metadata"]["deploy"]["api_key"].as_str(),
+            Some("RT_toml_decoy")
+        );
+    }
+
+    #[test]
+    fn pyproject_gets_a_tool_table() {
+        let pyproject = "[project]\nname = \"billing\"\n\n[tool.black]\nline-length = 100\n";
+        let doc = round_trip(pyproject, "pyproject.toml");
+        assert_eq!(
+            doc["tool"]["deploy"]["api_key"].as_str(),
+            Some("RT_toml_decoy")
+        );
+        // An existing [tool.deploy] gets a second key
+        let deploy = "[tool.deploy]\napi_key = \"real\"\n";
+        let doc = round_trip(deploy, "pyproject.toml");
+        assert_eq!(
+            doc["tool"]["deploy"]["api_key_2"].as_str(),
+            Some("RT_toml_decoy")
+        );
+    }
+
+    #[test]
+    fn other_files_use_a_secret_looking_table() {
+        let config =
+            "title = \"app\"\n\n[server]\nport = 8080\n\n[database.credentials]\nuser = \"app\"\n";
+        let doc = round_trip(config, "config.toml");
+        assert_eq!(
+            doc["database"]["credentials"]["api_key"].as_str(),
+            Some("RT_toml_decoy")
+        );
+        let doc = round_trip("", "config.toml");
+        assert_eq!(
+            doc["credentials"]["api_key"].as_str(),
+            Some("RT_toml_decoy")
+        );
+    }
+}
@@ -1,3 +1,12 @@
+pub mod backup;
+pub mod ecs;
+pub mod formats;
 pub mod injection;
 pub mod notification;
 pub mod repository;
+pub mod rewrite;
+pub mod smtp;
+pub mod syslog;
+pub mod template;
+#[cfg(test)]
+pub mod test_server;
@@ -1,43 +1,167 @@
 use crate::core::{
     error::{RedTokenError, RedTokenResult},
-    notification::{NotificationChannel, NotificationConfig, NotificationService},
-    token::Honeytoken,
+    notification::{
+        ChannelResult, NotificationChannel, NotificationConfig, NotificationService, RoutedChannel,
+        SiemFormat, TlsOptions,
+    },
+    token::{Honeytoken, Severity},
 };
+use crate::infrastructure::{ecs, smtp, syslog, template::TemplateEngine};
 use async_trait::async_trait;
-use log::{error, info};
+use chrono::Timelike;
+use log::info;
 use reqwest::{self, Client};
-use serde_json::json;
-use std::time::Duration;
+use serde_json::{json, Value};
+use std::time::{Duration, Instant};
+
+const PAGERDUTY_EVENTS_URL: &str = "https://events.pagerduty.com/v2/enqueue";
+const OPSGENIE_API_URL: &str = "https://api.opsgenie.com";
+
+// Stable per-token key so repeat triggers and resolves hit the same incident
+fn dedup_key(token: &Honeytoken) -> String {
+    format!("redtoken-{}", token.id)
+}
+
+fn validate_http_url(field: &str, value: &str) -> RedTokenResult<()> {
+    let url = reqwest::Url::parse(value)
+        .map_err(|e| RedTokenError::ConfigError(format!("Invalid {} '{}': {}", field, value, e)))?;
+
+    if !matches!(url.scheme(), "http" | "https") || url.host_str().is_none() {
+        return Err(RedTokenError::ConfigError(format!(
+            "Invalid {} '{}': expected an http(s) URL",
+            field, value
+        )));
+    }
+    Ok(())
+}
+
+fn require(field: &str, value: &str) -> RedTokenResult<()> {
+    if value.trim().is_empty() {
+        return Err(RedTokenError::ConfigError(format!(
+            "{} must not be empty",
+            field
+        )));
+    }
+    Ok(())
+}
+
+// Static checks run before a channel is saved to the config
+pub fn validate_channel(channel: &NotificationChannel) -> RedTokenResult<()> {
+    match channel {
+        NotificationChannel::Telegram { webhook_url }
+        | NotificationChannel::Discord { webhook_url } => {
+            validate_http_url("webhook URL", webhook_url)
+        }
+        NotificationChannel::Email {
+            smtp_server,
+            from,
+            to,
+        } => {
+            require("SMTP server", smtp_server)?;
+            smtp::validate(smtp_server, from, to)
+        }
+        NotificationChannel::Syslog { address, .. } => syslog::validate_address(address),
+        NotificationChannel::SplunkHec { url, token, .. } => {
+            validate_http_url("Splunk HEC URL", url)?;
+            require("HEC token", token)
+        }
+        NotificationChannel::Elasticsearch { url, index, .. } => {
+            validate_http_url("Elasticsearch URL", url)?;
+            require("index", index)
+        }
+        NotificationChannel::PagerDuty { routing_key, url } => {
+            require("routing key", routing_key)?;
+            url.as_deref()
+                .map_or(Ok(()), |url| validate_http_url("PagerDuty URL", url))
+        }
+        NotificationChannel::Opsgenie { api_key, url } => {
+            require("API key", api_key)?;
+            url.as_deref()
+                .map_or(Ok(()), |url| validate_http_url("Opsgenie URL", url))
+        }
+    }
+}
+
+// Optional dry run: check the endpoint answers without sending an alert
+pub async fn check_reachable(channel: &NotificationChannel) -> RedTokenResult<()> {
+    let url = match channel {
+        NotificationChannel::Telegram { webhook_url }
+        | NotificationChannel::Discord { webhook_url } => webhook_url.clone(),
+        NotificationChannel::SplunkHec { url, .. }
+        | NotificationChannel::Elasticsearch { url, .. } => url.clone(),
+        NotificationChannel::PagerDuty { url, .. } => url
+            .clone()
+            .unwrap_or_else(|| PAGERDUTY_EVENTS_URL.to_string()),
+        NotificationChannel::Opsgenie { url, .. } => {
+            url.clone().unwrap_or_else(|| OPSGENIE_API_URL.to_string())
+        }
+        NotificationChannel::Syslog { address, .. } => return syslog::probe(address).await,
+        NotificationChannel::Email { smtp_server, .. } => return smtp::probe(smtp_server).await,
+    };
+
+    // Any HTTP response means the host is reachable; the status is irrelevant here
+    Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .map_err(|e| RedTokenError::ConfigError(format!("Failed to create HTTP client: {}", e)))?
+        .head(&url)
+        .send()
+        .await
+        .map_err(|e| RedTokenError::NotificationError(format!("{} unreachable: {}", url, e)))?;
+    Ok(())
+}
 
 // Composite notification service that can send to multiple channels
 pub struct CompositeNotificationService {
     config: NotificationConfig,
     http_client: Client,
+    templates: TemplateEngine,
 }
 
 impl CompositeNotificationService {
-    pub fn new(config: NotificationConfig) -> Self {
+    pub fn new(config: NotificationConfig) -> RedTokenResult<Self> {
         let http_client = Client::builder()
             .timeout(Duration::from_secs(10))
             .build()
             .expect("Failed to create HTTP client");
+        let templates = TemplateEngine::load(&config.templates)?;
 
-        Self {
+        Ok(Self {
             config,
             http_client,
+            templates,
+        })
+    }
+
+    // Channels with custom TLS settings get their own client
+    fn client_for(&self, tls: &TlsOptions) -> RedTokenResult<Client> {
+        if *tls == TlsOptions::default() {
+            return Ok(self.http_client.clone());
         }
+
+        let mut builder = Client::builder()
+            .timeout(Duration::from_secs(10))
+            .danger_accept_invalid_certs(tls.insecure_skip_verify);
+
+        if let Some(ca_cert) = &tls.ca_cert {
+            let pem = std::fs::read(ca_cert).map_err(|e| RedTokenError::FileReadError {
+                path: ca_cert.clone(),
+                source: e,
+            })?;
+            let certificate = reqwest::Certificate::from_pem(&pem).map_err(|e| {
+                RedTokenError::ConfigError(format!("Invalid CA certificate {:?}: {}", ca_cert, e))
+            })?;
+            builder = builder.add_root_certificate(certificate);
+        }
+
+        builder
+            .build()
+            .map_err(|e| RedTokenError::ConfigError(format!("Failed to create HTTP client: {}", e)))
     }
 
     async fn send_telegram(&self, webhook_url: &str, token: &Honeytoken) -> RedTokenResult<()> {
-        let message = format!(
-            "🚨 ALERT: Honeytoken triggered!\n\n\
-            Token ID: {}\n\
-            File Path: {}\n\
-            Triggered: {}",
-            token.id,
-            token.file_path,
-            chrono::Local::now().format("%Y-%m-%d %H:%M:%S")
-        );
+        let alert = self.templates.render("telegram", token);
+        let message = format!("<b>{}</b>\n\n{}", alert.title, alert.body);
 
         let response = self
             .http_client
@@ -54,11 +178,13 @@ impl CompositeNotificationService {
             })?;
 
         if !response.status().is_success() {
-            return Err(RedTokenError::NotificationError(format!(
-                "Telegram API error: {} - {}",
-                response.status(),
-                response.text().await.unwrap_or_default()
-            )));
+            return Err(RedTokenError::ApiError {
+                status_code: response.status().as_u16(),
+                message: format!(
+                    "Telegram API error: {}",
+                    response.text().await.unwrap_or_default()
+                ),
+            });
         }
 
         info!("Telegram notification sent for token {}", token.id);
@@ -66,31 +192,16 @@ impl CompositeNotificationService {
     }
 
     async fn send_discord(&self, webhook_url: &str, token: &Honeytoken) -> RedTokenResult<()> {
+        let alert = self.templates.render("discord", token);
+
         let response = self
             .http_client
             .post(webhook_url)
             .json(&json!({
                 "embeds": [{
-                    "title": "🚨 Honeytoken Alert",
-                    "description": "A honeytoken has been triggered!",
+                    "title": alert.title,
+                    "description": alert.body,
                     "color": 16711680, // Red
-                    "fields": [
-                        {
-                            "name": "Token ID",
-                            "value": token.id.to_string(),
-                            "inline": true
-                        },
-                        {
-                            "name": "File Path",
-                            "value": token.file_path,
-                            "inline": true
-                        },
-                        {
-                            "name": "Triggered At",
-                            "value": chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
-                            "inline": false
-                        }
-                    ],
                     "footer": {
                         "text": "RedToken Intrusion Detection"
                     }
@@ -103,11 +214,13 @@ impl CompositeNotificationService {
             })?;
 
         if !response.status().is_success() {
-            return Err(RedTokenError::NotificationError(format!(
-                "Discord API error: {} - {}",
-                response.status(),
-                response.text().await.unwrap_or_default()
-            )));
+            return Err(RedTokenError::ApiError {
+                status_code: response.status().as_u16(),
+                message: format!(
+                    "Discord API error: {}",
+                    response.text().await.unwrap_or_default()
+                ),
+            });
         }
 
         info!("Discord notification sent for token {}", token.id);
@@ -119,64 +232,684 @@ impl CompositeNotificationService {
         config: &NotificationChannel,
         token: &Honeytoken,
     ) -> RedTokenResult<()> {
-        if let NotificationChannel::Email {
+        let NotificationChannel::Email {
             smtp_server,
             from,
             to,
         } = config
-        {
-            // For simplicity in this version, we'll just log that we would send an email
-            // In a real implementation, you would use lettre or another email library
+        else {
+            return Err(RedTokenError::NotificationError(
+                "Invalid email configuration".to_string(),
+            ));
+        };
+
+        let alert = self.templates.render("email", token);
+        smtp::send(smtp_server, from, to, &alert.title, &alert.body).await?;
+
+        info!("Email notification sent to {} for token {}", to, token.id);
+        Ok(())
+    }
+
+    async fn send_syslog(
+        &self,
+        address: &str,
+        format: SiemFormat,
+        token: &Honeytoken,
+    ) -> RedTokenResult<()> {
+        let message = syslog::format_message(token, format);
+        syslog::send(address, &message).await?;
+
+        info!("Syslog notification sent for token {}", token.id);
+        Ok(())
+    }
+
+    async fn send_splunk_hec(
+        &self,
+        channel: &NotificationChannel,
+        tokens: &[Honeytoken],
+    ) -> RedTokenResult<()> {
+        let NotificationChannel::SplunkHec {
+            url,
+            token: hec_token,
+            index,
+            sourcetype,
+            tls,
+            ..
+        } = channel
+        else {
+            return Err(RedTokenError::NotificationError(
+                "Invalid Splunk HEC configuration".to_string(),
+            ));
+        };
+
+        let endpoint = if url.contains("/services/collector") {
+            url.clone()
+        } else {
+            format!("{}/services/collector/event", url.trim_end_matches('/'))
+        };
+
+        // HEC accepts several events concatenated in one body
+        let body = tokens
+            .iter()
+            .map(|token| ecs::hec_event(token, index.as_deref(), sourcetype.as_deref()).to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let response = self
+            .client_for(tls)?
+            .post(&endpoint)
+            .header("Authorization", format!("Splunk {}", hec_token))
+            .header("Content-Type", "application/json")
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| {
+                RedTokenError::NotificationError(format!("Splunk HEC request failed: {}", e))
+            })?;
+
+        if !response.status().is_success() {
+            return Err(RedTokenError::ApiError {
+                status_code: response.status().as_u16(),
+                message: format!(
+                    "Splunk HEC error: {}",
+                    response.text().await.unwrap_or_default()
+                ),
+            });
+        }
+
+        info!("Splunk HEC received {} alert event(s)", tokens.len());
+        Ok(())
+    }
+
+    async fn send_elasticsearch(
+        &self,
+        channel: &NotificationChannel,
+        tokens: &[Honeytoken],
+    ) -> RedTokenResult<()> {
+        let NotificationChannel::Elasticsearch {
+            url,
+            index,
+            api_key,
+            username,
+            password,
+            tls,
+            ..
+        } = channel
+        else {
+            return Err(RedTokenError::NotificationError(
+                "Invalid Elasticsearch configuration".to_string(),
+            ));
+        };
+
+        // `create` works for both regular indices and data streams
+        let mut body = String::new();
+        for token in tokens {
+            body.push_str(&json!({ "create": { "_index": index } }).to_string());
+            body.push('\n');
+            body.push_str(&ecs::event_document(token).to_string());
+            body.push('\n');
+        }
+
+        let mut request = self
+            .client_for(tls)?
+            .post(format!("{}/_bulk", url.trim_end_matches('/')))
+            .header("Content-Type", "application/x-ndjson")
+            .body(body);
+
+        if let Some(api_key) = api_key {
+            request = request.header("Authorization", format!("ApiKey {}", api_key));
+        } else if let Some(username) = username {
+            request = request.basic_auth(username, password.as_ref());
+        }
+
+        let response = request.send().await.map_err(|e| {
+            RedTokenError::NotificationError(format!("Elasticsearch request failed: {}", e))
+        })?;
+
+        let status = response.status();
+        let text = response.text().await.unwrap_or_default();
+        if !status.is_success() {
+            return Err(RedTokenError::ApiError {
+                status_code: status.as_u16(),
+                message: format!("Elasticsearch error: {}", text),
+            });
+        }
+
+        // A 200 response can still carry per-document failures; the other documents
+        // are indexed, so only the rejected ones are reported
+        let result: Value = serde_json::from_str(&text).unwrap_or(Value::Null);
+        if result["errors"].as_bool().unwrap_or(false) {
+            let items = result["items"].as_array().cloned().unwrap_or_default();
+            let failures: Vec<(usize, u16, String)> = items
+                .iter()
+                .enumerate()
+                .filter_map(|(index, item)| {
+                    let status = item["create"]["status"].as_u64().unwrap_or(0) as u16;
+                    let error = &item["create"]["error"];
+                    (!error.is_null() || !(200..300).contains(&status)).then(|| {
+                        let reason = error["reason"].as_str().unwrap_or("unknown error");
+                        (
+                            index,
+                            status,
+                            format!("Elasticsearch rejected alert document: {}", reason),
+                        )
+                    })
+                })
+                .collect();
+            // Without a usable item list, nothing is known to be indexed
+            if items.len() != tokens.len() || failures.is_empty() {
+                return Err(RedTokenError::NotificationError(format!(
+                    "Elasticsearch rejected alert documents: {}",
+                    text
+                )));
+            }
             info!(
-                "Would send email notification from {} to {} via {} for token {}",
-                from, to, smtp_server, token.id
+                "Elasticsearch indexed {} of {} alert event(s)",
+                tokens.len() - failures.len(),
+                tokens.len()
             );
+            return Err(RedTokenError::PartialBatchError(failures));
+        }
 
-            // Simplified implementation - just return success
-            Ok(())
+        info!("Elasticsearch indexed {} alert event(s)", tokens.len());
+        Ok(())
+    }
+
+    async fn send_pagerduty(
+        &self,
+        routing_key: &str,
+        url: Option<&str>,
+        token: &Honeytoken,
+        resolve: bool,
+    ) -> RedTokenResult<()> {
+        let body = if resolve {
+            json!({
+                "routing_key": routing_key,
+                "event_action": "resolve",
+                "dedup_key": dedup_key(token),
+            })
         } else {
-            Err(RedTokenError::NotificationError(
-                "Invalid email configuration".to_string(),
-            ))
+            let severity = match token.severity {
+                Severity::Critical => "critical",
+                Severity::High => "error",
+                Severity::Medium => "warning",
+                Severity::Low => "info",
+            };
+
+            let alert = self.templates.render("pagerduty", token);
+
+            json!({
+                "routing_key": routing_key,
+                "event_action": "trigger",
+                "dedup_key": dedup_key(token),
+                "payload": {
+                    "summary": alert.title,
+                    "source": syslog::hostname(),
+                    "severity": severity,
+                    "timestamp": syslog::trigger_time(token).to_rfc3339(),
+                    "component": token.file_path,
+                    "group": token.kind.clone().unwrap_or_default(),
+                    "class": "honeytoken",
+                    "custom_details": ecs::event_document(token),
+                }
+            })
+        };
+
+        let response = self
+            .http_client
+            .post(url.unwrap_or(PAGERDUTY_EVENTS_URL))
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| {
+                RedTokenError::NotificationError(format!("PagerDuty request failed: {}", e))
+            })?;
+
+        if !response.status().is_success() {
+            return Err(RedTokenError::ApiError {
+                status_code: response.status().as_u16(),
+                message: format!(
+                    "PagerDuty API error: {}",
+                    response.text().await.unwrap_or_default()
+                ),
+            });
         }
+
+        info!(
+            "PagerDuty {} sent for token {}",
+            if resolve { "resolve" } else { "trigger" },
+            token.id
+        );
+        Ok(())
+    }
+
+    async fn send_opsgenie(
+        &self,
+        api_key: &str,
+        url: Option<&str>,
+        token: &Honeytoken,
+        resolve: bool,
+    ) -> RedTokenResult<()> {
+        let base = url.unwrap_or(OPSGENIE_API_URL).trim_end_matches('/');
+
+        let request = if resolve {
+            self.http_client
+                .post(format!(
+                    "{}/v2/alerts/{}/close?identifierType=alias",
+                    base,
+                    dedup_key(token)
+                ))
+                .json(&json!({
+                    "source": "RedToken",
+                    "note": "Honeytoken acknowledged or retired",
+                }))
+        } else {
+            let priority = match token.severity {
+                Severity::Critical => "P1",
+                Severity::High => "P2",
+                Severity::Medium => "P3",
+                Severity::Low => "P4",
+            };
+            let trigger = token.last_trigger.clone().unwrap_or_default();
+            let alert = self.templates.render("opsgenie", token);
+
+            self.http_client
+                .post(format!("{}/v2/alerts", base))
+                .json(&json!({
+                    "message": alert.title,
+                    "alias": dedup_key(token),
+                    "description": alert.body,
+                    "priority": priority,
+                    "tags": token.tags,
+                    "entity": token.file_path,
+                    "source": syslog::hostname(),
+                    "details": {
+                        "token_id": token.id.to_string(),
+                        "severity": token.severity.to_string(),
+                        "source_ip": trigger.source_ip.unwrap_or_default(),
+                        "user_agent": trigger.user_agent.unwrap_or_default(),
+                    }
+                }))
+        };
+
+        let response = request
+            .header("Authorization", format!("GenieKey {}", api_key))
+            .send()
+            .await
+            .map_err(|e| {
+                RedTokenError::NotificationError(format!("Opsgenie request failed: {}", e))
+            })?;
+
+        if !response.status().is_success() {
+            return Err(RedTokenError::ApiError {
+                status_code: response.status().as_u16(),
+                message: format!(
+                    "Opsgenie API error: {}",
+                    response.text().await.unwrap_or_default()
+                ),
+            });
+        }
+
+        info!(
+            "Opsgenie {} sent for token {}",
+            if resolve { "close" } else { "alert" },
+            token.id
+        );
+        Ok(())
     }
 }
 
 #[async_trait]
 impl NotificationService for CompositeNotificationService {
-    async fn send_alert(&self, token: &Honeytoken) -> anyhow::Result<()> {
-        let mut success = false;
-
-        for channel in &self.config.channels {
-            match channel {
-                NotificationChannel::Telegram { webhook_url } => {
-                    if let Err(e) = self.send_telegram(webhook_url, token).await {
-                        error!("Failed to send Telegram notification: {}", e);
-                    } else {
-                        success = true;
-                    }
+    fn route(&self, token: &Honeytoken) -> Vec<RoutedChannel> {
+        let hour = chrono::Local::now().hour();
+        let mut routed: Vec<RoutedChannel> = Vec::new();
+
+        for rule in self.config.routes.iter().filter(|r| r.matches(token, hour)) {
+            let selected = self
+                .config
+                .channels
+                .iter()
+                .filter(|entry| entry.enabled)
+                .filter(|entry| {
+                    rule.channels.is_empty()
+                        || rule
+                            .channels
+                            .iter()
+                            .any(|selector| entry.matches_selector(selector))
+                });
+
+            for entry in selected {
+                if !routed.iter().any(|r| r.channel == entry.channel) {
+                    routed.push(RoutedChannel {
+                        name: entry.name.clone(),
+                        channel: entry.channel.clone(),
+                        rule: Some(rule.name.clone()),
+                        escalation: false,
+                    });
                 }
-                NotificationChannel::Discord { webhook_url } => {
-                    if let Err(e) = self.send_discord(webhook_url, token).await {
-                        error!("Failed to send Discord notification: {}", e);
-                    } else {
-                        success = true;
-                    }
+            }
+
+            for (index, channel) in rule.escalate.iter().enumerate() {
+                if !routed.iter().any(|r| &r.channel == channel) {
+                    routed.push(RoutedChannel {
+                        name: rule.escalation_name(index),
+                        channel: channel.clone(),
+                        rule: Some(rule.name.clone()),
+                        escalation: true,
+                    });
                 }
-                NotificationChannel::Email { .. } => {
-                    if let Err(e) = self.send_email(channel, token).await {
-                        error!("Failed to send Email notification: {}", e);
-                    } else {
-                        success = true;
+            }
+        }
+
+        // Without a matching rule the default channels are notified
+        if routed.is_empty() {
+            routed = self
+                .config
+                .channels
+                .iter()
+                .filter(|entry| entry.enabled)
+                .filter(|entry| {
+                    self.config
+                        .default_channels
+                        .iter()
+                        .any(|selector| entry.matches_selector(selector))
+                })
+                .map(|entry| RoutedChannel {
+                    name: entry.name.clone(),
+                    channel: entry.channel.clone(),
+                    rule: None,
+                    escalation: false,
+                })
+                .collect();
+        }
+
+        routed
+    }
+
+    fn channel(&self, name: &str) -> Option<NotificationChannel> {
+        let configured = self
+            .config
+            .channels
+            .iter()
+            .find(|entry| entry.enabled && entry.name == name)
+            .map(|entry| entry.channel.clone());
+
+        configured.or_else(|| {
+            self.config.routes.iter().find_map(|rule| {
+                (0..rule.escalate.len())
+                    .find(|&index| rule.escalation_name(index) == name)
+                    .map(|index| rule.escalate[index].clone())
+            })
+        })
+    }
+
+    async fn test_channels(&self, token: &Honeytoken) -> Vec<ChannelResult> {
+        let mut results = Vec::new();
+
+        // Enabled channels, then escalation targets that only routing rules reach
+        let mut targets: Vec<(String, &NotificationChannel)> = self
+            .config
+            .channels
+            .iter()
+            .filter(|entry| entry.enabled)
+            .map(|entry| (entry.name.clone(), &entry.channel))
+            .collect();
+        for rule in &self.config.routes {
+            for (index, channel) in rule.escalate.iter().enumerate() {
+                if !targets.iter().any(|(_, target)| *target == channel) {
+                    targets.push((rule.escalation_name(index), channel));
+                }
+            }
+        }
+
+        for (name, channel) in targets {
+            let started = Instant::now();
+            let mut outcome = self.deliver(channel, token).await;
+
+            // Close the test incident straight away so nobody gets paged twice
+            if outcome.is_ok() && channel.supports_resolve() {
+                outcome = self.resolve(channel, token).await;
+            }
+
+            let latency_ms = started.elapsed().as_millis() as u64;
+            let result = match outcome {
+                Ok(()) => ChannelResult {
+                    name: name.clone(),
+                    channel: channel.kind().to_string(),
+                    success: true,
+                    latency_ms,
+                    status_code: None,
+                    error: None,
+                },
+                Err(e) => {
+                    let status_code = match e.downcast_ref::<RedTokenError>() {
+                        Some(RedTokenError::ApiError { status_code, .. }) => Some(*status_code),
+                        _ => None,
+                    };
+                    ChannelResult {
+                        name: name.clone(),
+                        channel: channel.kind().to_string(),
+                        success: false,
+                        latency_ms,
+                        status_code,
+                        error: Some(e.to_string()),
                     }
                 }
+            };
+            results.push(result);
+        }
+
+        results
+    }
+
+    async fn deliver(
+        &self,
+        channel: &NotificationChannel,
+        token: &Honeytoken,
+    ) -> anyhow::Result<()> {
+        match channel {
+            NotificationChannel::Telegram { webhook_url } => {
+                self.send_telegram(webhook_url, token).await?
+            }
+            NotificationChannel::Discord { webhook_url } => {
+                self.send_discord(webhook_url, token).await?
+            }
+            NotificationChannel::Email { .. } => self.send_email(channel, token).await?,
+            NotificationChannel::Syslog { address, format } => {
+                self.send_syslog(address, *format, token).await?
+            }
+            NotificationChannel::PagerDuty { routing_key, url } => {
+                self.send_pagerduty(routing_key, url.as_deref(), token, false)
+                    .await?
+            }
+            NotificationChannel::Opsgenie { api_key, url } => {
+                self.send_opsgenie(api_key, url.as_deref(), token, false)
+                    .await?
+            }
+            NotificationChannel::SplunkHec { .. } | NotificationChannel::Elasticsearch { .. } => {
+                self.deliver_batch(channel, std::slice::from_ref(token))
+                    .await?
             }
         }
 
-        if success {
-            Ok(())
-        } else {
-            Err(anyhow::anyhow!("All notification channels failed"))
+        Ok(())
+    }
+
+    async fn resolve(
+        &self,
+        channel: &NotificationChannel,
+        token: &Honeytoken,
+    ) -> anyhow::Result<()> {
+        match channel {
+            NotificationChannel::PagerDuty { routing_key, url } => {
+                self.send_pagerduty(routing_key, url.as_deref(), token, true)
+                    .await?
+            }
+            NotificationChannel::Opsgenie { api_key, url } => {
+                self.send_opsgenie(api_key, url.as_deref(), token, true)
+                    .await?
+            }
+            // Other channels have no incident to close
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    async fn deliver_batch(
+        &self,
+        channel: &NotificationChannel,
+        tokens: &[Honeytoken],
+    ) -> anyhow::Result<()> {
+        match channel {
+            NotificationChannel::SplunkHec { .. } => self.send_splunk_hec(channel, tokens).await?,
+            NotificationChannel::Elasticsearch { .. } => {
+                self.send_elasticsearch(channel, tokens).await?
+            }
+            _ => {
+                for token in tokens {
+                    self.deliver(channel, token).await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{notification::default_channels, token::TriggerEvent};
+    use crate::infrastructure::test_server::TestServer;
+
+    fn notifier() -> CompositeNotificationService {
+        CompositeNotificationService::new(NotificationConfig {
+            channels: Vec::new(),
+            rate_limit: None,
+            routes: Vec::new(),
+            default_channels: default_channels(),
+            templates: Default::default(),
+        })
+        .unwrap()
+    }
+
+    fn triggered(file_path: &str) -> Honeytoken {
+        let mut token = Honeytoken::new(format!("RT_{}", file_path), file_path.to_string());
+        token.kind = Some("env".to_string());
+        token.tags = vec!["prod".to_string()];
+        token.mark_as_triggered(TriggerEvent {
+            source_ip: Some("198.51.100.4".to_string()),
+            user_agent: Some("aws-cli/2.15".to_string()),
+        });
+        token
+    }
+
+    // ECS field names every SIEM event must use
+    fn assert_ecs(event: &Value, token: &Honeytoken) {
+        assert!(
+            chrono::DateTime::parse_from_rfc3339(event["@timestamp"].as_str().unwrap()).is_ok()
+        );
+        assert_eq!(event["event"]["kind"], "alert");
+        assert_eq!(event["event"]["category"], json!(["intrusion_detection"]));
+        assert_eq!(event["event"]["action"], "honeytoken-triggered");
+        assert_eq!(event["event"]["severity"], 47);
+        assert_eq!(event["file"]["path"], token.file_path);
+        assert_eq!(event["source"]["ip"], "198.51.100.4");
+        assert_eq!(event["user_agent"]["original"], "aws-cli/2.15");
+        assert_eq!(event["labels"]["token_id"], token.id.to_string());
+        assert_eq!(event["labels"]["token_kind"], "env");
+        assert_eq!(event["tags"], json!(["prod"]));
+        assert_eq!(event["observer"]["vendor"], "RedToken");
+        assert!(event["host"]["hostname"].is_string());
+    }
+
+    #[tokio::test]
+    async fn hec_batch_is_one_request_of_enveloped_events() {
+        let server = TestServer::start(&[]).await;
+        let channel = NotificationChannel::SplunkHec {
+            url: format!("{}/", server.url),
+            token: "hec-token".to_string(),
+            index: Some("security".to_string()),
+            sourcetype: None,
+            batch_size: 50,
+            tls: TlsOptions::default(),
+        };
+        let tokens = [triggered("/srv/a/.env"), triggered("/srv/b/.env")];
+
+        notifier().deliver_batch(&channel, &tokens).await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        let request = &requests[0];
+        assert_eq!(request.path, "/services/collector/event");
+        assert_eq!(request.header("authorization"), Some("Splunk hec-token"));
+
+        let events: Vec<Value> = request
+            .body
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(events.len(), tokens.len());
+        for (envelope, token) in events.iter().zip(&tokens) {
+            assert_eq!(envelope["index"], "security");
+            assert_eq!(envelope["sourcetype"], "redtoken:alert");
+            assert_eq!(envelope["source"], "redtoken");
+            assert!(envelope["time"].is_f64());
+            assert!(envelope["host"].is_string());
+            assert_ecs(&envelope["event"], token);
+        }
+    }
+
+    #[tokio::test]
+    async fn bulk_batch_pairs_create_actions_with_ecs_documents() {
+        let server = TestServer::start(&[]).await;
+        let channel = |api_key: Option<&str>| NotificationChannel::Elasticsearch {
+            url: server.url.clone(),
+            index: "logs-redtoken-default".to_string(),
+            api_key: api_key.map(str::to_string),
+            username: Some("elastic".to_string()),
+            password: Some("changeme".to_string()),
+            batch_size: 50,
+            tls: TlsOptions::default(),
+        };
+        let tokens = [triggered("/srv/a/.env"), triggered("/srv/b/.env")];
+
+        notifier()
+            .deliver_batch(&channel(Some("key-id")), &tokens)
+            .await
+            .unwrap();
+        notifier()
+            .deliver_batch(&channel(None), &tokens[..1])
+            .await
+            .unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        let request = &requests[0];
+        assert_eq!(request.path, "/_bulk");
+        assert_eq!(request.header("content-type"), Some("application/x-ndjson"));
+        assert_eq!(request.header("authorization"), Some("ApiKey key-id"));
+        assert_eq!(
+            requests[1].header("authorization"),
+            Some("Basic ZWxhc3RpYzpjaGFuZ2VtZQ==")
+        );
+
+        // NDJSON must end with a newline, one action line before each document
+        assert!(request.body.ends_with('\n'));
+        let lines: Vec<Value> = request
+            .body
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2 * tokens.len());
+        for (pair, token) in lines.chunks(2).zip(&tokens) {
+            assert_eq!(
+                pair[0],
+                json!({ "create": { "_index": "logs-redtoken-default" } })
+            );
+            assert_ecs(&pair[1], token);
         }
     }
 }
@@ -1,10 +1,12 @@
 use crate::core::{
+    backup::{BackupRecord, BackupRepository},
     error::{RedTokenError, RedTokenResult},
+    outbox::{OutboxJob, OutboxRepository},
     token::{Honeytoken, TokenRepository},
 };
 use async_trait::async_trait;
-use log::{error, info};
-use serde_json::{self, json};
+use rusqlite::{params, Connection};
+use serde::{de::DeserializeOwned, Serialize};
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
@@ -173,3 +175,645 @@ impl TokenRepository for FileTokenRepository {
         Ok(())
     }
 }
+
+// In-memory outbox implementation
+pub struct InMemoryOutboxRepository {
+    jobs: Arc<Mutex<HashMap<Uuid, OutboxJob>>>,
+}
+
+impl InMemoryOutboxRepository {
+    pub fn new() -> Self {
+        Self {
+            jobs: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+}
+
+#[async_trait]
+impl OutboxRepository for InMemoryOutboxRepository {
+    async fn save(&self, job: &OutboxJob) -> anyhow::Result<()> {
+        let mut jobs = self
+            .jobs
+            .lock()
+            .map_err(|e| anyhow::anyhow!("Lock error: {}", e))?;
+        jobs.insert(job.id, job.clone());
+        Ok(())
+    }
+
+    async fn find_by_id(&self, id: Uuid) -> anyhow::Result<Option<OutboxJob>> {
+        let jobs = self
+            .jobs
+            .lock()
+            .map_err(|e| anyhow::anyhow!("Lock error: {}", e))?;
+        Ok(jobs.get(&id).cloned())
+    }
+
+    async fn find_all(&self) -> anyhow::Result<Vec<OutboxJob>> {
+        let jobs = self
+            .jobs
+            .lock()
+            .map_err(|e| anyhow::anyhow!("Lock error: {}", e))?;
+        Ok(jobs.values().cloned().collect())
+    }
+
+    async fn update(&self, job: &OutboxJob) -> anyhow::Result<()> {
+        let mut jobs = self
+            .jobs
+            .lock()
+            .map_err(|e| anyhow::anyhow!("Lock error: {}", e))?;
+        jobs.insert(job.id, job.clone());
+        Ok(())
+    }
+
+    async fn delete(&self, id: Uuid) -> anyhow::Result<()> {
+        let mut jobs = self
+            .jobs
+            .lock()
+            .map_err(|e| anyhow::anyhow!("Lock error: {}", e))?;
+        jobs.remove(&id);
+        Ok(())
+    }
+}
+
+// File-based outbox implementation. `serve` and CLI commands may use the file at the same
+// time, so changes hold an advisory lock on `<outbox>.lock` and replace the file atomically.
+pub struct FileOutboxRepository {
+    db_path: PathBuf,
+}
+
+impl FileOutboxRepository {
+    pub fn new<P: AsRef<Path>>(db_path: P) -> Self {
+        Self {
+            db_path: db_path.as_ref().to_path_buf(),
+        }
+    }
+
+    fn parse(content: &str) -> RedTokenResult<HashMap<Uuid, OutboxJob>> {
+        if content.trim().is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let jobs = serde_json::from_str::<Vec<OutboxJob>>(content)
+            .map_err(|e| RedTokenError::DatabaseError(format!("Failed to parse outbox: {}", e)))?;
+
+        Ok(jobs.into_iter().map(|job| (job.id, job)).collect())
+    }
+
+    // Readers need no lock: the file is only ever replaced whole
+    async fn read_db(&self) -> RedTokenResult<HashMap<Uuid, OutboxJob>> {
+        if !self.db_path.exists() {
+            return Ok(HashMap::new());
+        }
+
+        let content =
+            fs::read_to_string(&self.db_path)
+                .await
+                .map_err(|e| RedTokenError::FileReadError {
+                    path: self.db_path.clone(),
+                    source: e,
+                })?;
+
+        Self::parse(&content)
+    }
+
+    async fn modify<F>(&self, change: F) -> RedTokenResult<()>
+    where
+        F: FnOnce(&mut HashMap<Uuid, OutboxJob>) + Send + 'static,
+    {
+        let db_path = self.db_path.clone();
+        tokio::task::spawn_blocking(move || Self::modify_blocking(&db_path, change))
+            .await
+            .map_err(|e| RedTokenError::DatabaseError(format!("Outbox update failed: {}", e)))?
+    }
+
+    // Read, change and rewrite the jobs while holding the lock
+    fn modify_blocking<F>(db_path: &Path, change: F) -> RedTokenResult<()>
+    where
+        F: FnOnce(&mut HashMap<Uuid, OutboxJob>),
+    {
+        let write_error = |path: &Path, source: std::io::Error| RedTokenError::FileWriteError {
+            path: path.to_path_buf(),
+            source,
+        };
+
+        if let Some(parent) = db_path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent).map_err(|e| write_error(parent, e))?;
+        }
+
+        let file_name = db_path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let lock_path = db_path.with_file_name(format!("{}.lock", file_name));
+        let lock = std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)
+            .map_err(|e| write_error(&lock_path, e))?;
+        // Released when `lock` is dropped
+        lock.lock().map_err(|e| write_error(&lock_path, e))?;
+
+        let content = match std::fs::read_to_string(db_path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => {
+                return Err(RedTokenError::FileReadError {
+                    path: db_path.to_path_buf(),
+                    source: e,
+                })
+            }
+        };
+        let mut jobs = Self::parse(&content)?;
+        change(&mut jobs);
+
+        let jobs_vec: Vec<&OutboxJob> = jobs.values().collect();
+        let content = serde_json::to_string_pretty(&jobs_vec).map_err(|e| {
+            RedTokenError::DatabaseError(format!("Failed to serialize outbox: {}", e))
+        })?;
+
+        let temp_path =
+            db_path.with_file_name(format!(".{}.{}.tmp", file_name, std::process::id()));
+        std::fs::write(&temp_path, content).map_err(|e| write_error(&temp_path, e))?;
+        std::fs::rename(&temp_path, db_path).map_err(|e| {
+            let _ = std::fs::remove_file(&temp_path);
+            write_error(db_path, e)
+        })?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl OutboxRepository for FileOutboxRepository {
+    async fn save(&self, job: &OutboxJob) -> anyhow::Result<()> {
+        let job = job.clone();
+        self.modify(move |jobs| {
+            jobs.insert(job.id, job);
+        })
+        .await?;
+        Ok(())
+    }
+
+    async fn find_by_id(&self, id: Uuid) -> anyhow::Result<Option<OutboxJob>> {
+        let jobs = self.read_db().await?;
+        Ok(jobs.get(&id).cloned())
+    }
+
+    async fn find_all(&self) -> anyhow::Result<Vec<OutboxJob>> {
+        let jobs = self.read_db().await?;
+        Ok(jobs.values().cloned().collect())
+    }
+
+    async fn update(&self, job: &OutboxJob) -> anyhow::Result<()> {
+        self.save(job).await
+    }
+
+    async fn delete(&self, id: Uuid) -> anyhow::Result<()> {
+        self.modify(move |jobs| {
+            jobs.remove(&id);
+        })
+        .await?;
+        Ok(())
+    }
+}
+
+// In-memory backup catalog
+pub struct InMemoryBackupRepository {
+    records: Arc<Mutex<HashMap<Uuid, BackupRecord>>>,
+}
+
+impl InMemoryBackupRepository {
+    pub fn new() -> Self {
+        Self {
+            records: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+}
+
+#[async_trait]
+impl BackupRepository for InMemoryBackupRepository {
+    async fn save(&self, record: &BackupRecord) -> anyhow::Result<()> {
+        let mut records = self
+            .records
+            .lock()
+            .map_err(|e| anyhow::anyhow!("Lock error: {}", e))?;
+        records.insert(record.id, record.clone());
+        Ok(())
+    }
+
+    async fn find_by_id(&self, id: Uuid) -> anyhow::Result<Option<BackupRecord>> {
+        let records = self
+            .records
+            .lock()
+            .map_err(|e| anyhow::anyhow!("Lock error: {}", e))?;
+        Ok(records.get(&id).cloned())
+    }
+
+    async fn find_all(&self) -> anyhow::Result<Vec<BackupRecord>> {
+        let records = self
+            .records
+            .lock()
+            .map_err(|e| anyhow::anyhow!("Lock error: {}", e))?;
+        Ok(records.values().cloned().collect())
+    }
+
+    async fn delete(&self, id: Uuid) -> anyhow::Result<()> {
+        let mut records = self
+            .records
+            .lock()
+            .map_err(|e| anyhow::anyhow!("Lock error: {}", e))?;
+        records.remove(&id);
+        Ok(())
+    }
+}
+
+// File-based backup catalog
+pub struct FileBackupRepository {
+    db_path: PathBuf,
+}
+
+impl FileBackupRepository {
+    pub fn new<P: AsRef<Path>>(db_path: P) -> Self {
+        Self {
+            db_path: db_path.as_ref().to_path_buf(),
+        }
+    }
+
+    async fn read_db(&self) -> RedTokenResult<HashMap<Uuid, BackupRecord>> {
+        if !self.db_path.exists() {
+            return Ok(HashMap::new());
+        }
+
+        let content =
+            fs::read_to_string(&self.db_path)
+                .await
+                .map_err(|e| RedTokenError::FileReadError {
+                    path: self.db_path.clone(),
+                    source: e,
+                })?;
+
+        if content.trim().is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let records = serde_json::from_str::<Vec<BackupRecord>>(&content).map_err(|e| {
+            RedTokenError::DatabaseError(format!("Failed to parse backup catalog: {}", e))
+        })?;
+
+        Ok(records
+            .into_iter()
+            .map(|record| (record.id, record))
+            .collect())
+    }
+
+    async fn write_db(&self, records: &HashMap<Uuid, BackupRecord>) -> RedTokenResult<()> {
+        let records_vec: Vec<BackupRecord> = records.values().cloned().collect();
+        let content = serde_json::to_string_pretty(&records_vec).map_err(|e| {
+            RedTokenError::DatabaseError(format!("Failed to serialize backup catalog: {}", e))
+        })?;
+
+        if let Some(parent) = self.db_path.parent() {
+            if !parent.exists() {
+                fs::create_dir_all(parent)
+                    .await
+                    .map_err(|e| RedTokenError::FileWriteError {
+                        path: parent.to_path_buf(),
+                        source: e,
+                    })?;
+            }
+        }
+
+        fs::write(&self.db_path, content)
+            .await
+            .map_err(|e| RedTokenError::FileWriteError {
+                path: self.db_path.clone(),
+                source: e,
+            })?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl BackupRepository for FileBackupRepository {
+    async fn save(&self, record: &BackupRecord) -> anyhow::Result<()> {
+        let mut records = self.read_db().await?;
+        records.insert(record.id, record.clone());
+        self.write_db(&records).await?;
+        Ok(())
+    }
+
+    async fn find_by_id(&self, id: Uuid) -> anyhow::Result<Option<BackupRecord>> {
+        let records = self.read_db().await?;
+        Ok(records.get(&id).cloned())
+    }
+
+    async fn find_all(&self) -> anyhow::Result<Vec<BackupRecord>> {
+        let records = self.read_db().await?;
+        Ok(records.values().cloned().collect())
+    }
+
+    async fn delete(&self, id: Uuid) -> anyhow::Result<()> {
+        let mut records = self.read_db().await?;
+        if records.remove(&id).is_some() {
+            self.write_db(&records).await?;
+        }
+        Ok(())
+    }
+}
+
+// SQLite database shared by the token, outbox and backup catalog repositories.
+// Records are stored as JSON so new fields need no schema migration.
+#[derive(Clone)]
+pub struct SqliteDatabase {
+    conn: Arc<Mutex<Connection>>,
+}
+
+const SQLITE_SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS tokens (
+        id TEXT PRIMARY KEY,
+        value TEXT NOT NULL,
+        data TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS tokens_value ON tokens (value);
+    CREATE TABLE IF NOT EXISTS outbox (
+        id TEXT PRIMARY KEY,
+        data TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS backups (
+        id TEXT PRIMARY KEY,
+        data TEXT NOT NULL
+    );
+";
+
+fn sqlite_error(e: rusqlite::Error) -> RedTokenError {
+    RedTokenError::DatabaseError(e.to_string())
+}
+
+impl SqliteDatabase {
+    pub fn open<P: AsRef<Path>>(path: P) -> RedTokenResult<Self> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() && !parent.exists() {
+                std::fs::create_dir_all(parent).map_err(|e| RedTokenError::FileWriteError {
+                    path: parent.to_path_buf(),
+                    source: e,
+                })?;
+            }
+        }
+
+        let conn = Connection::open(path).map_err(|e| {
+            RedTokenError::DatabaseError(format!("Failed to open {:?}: {}", path, e))
+        })?;
+        conn.execute_batch(SQLITE_SCHEMA).map_err(|e| {
+            if e.sqlite_error_code() == Some(rusqlite::ErrorCode::NotADatabase) {
+                // Usually a JSON store left behind by the json backend
+                return RedTokenError::DatabaseError(format!(
+                    "{:?} is not an SQLite database. Point storage.sqlite_path at a new file \
+                     or switch storage.backend back to json",
+                    path
+                ));
+            }
+            RedTokenError::DatabaseError(format!("Failed to initialise {:?}: {}", path, e))
+        })?;
+
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    fn with_conn<T>(
+        &self,
+        f: impl FnOnce(&Connection) -> rusqlite::Result<T>,
+    ) -> RedTokenResult<T> {
+        let conn = self
+            .conn
+            .lock()
+            .map_err(|e| RedTokenError::DatabaseError(format!("Lock error: {}", e)))?;
+        f(&conn).map_err(sqlite_error)
+    }
+
+    fn query<T: DeserializeOwned>(
+        &self,
+        sql: &str,
+        params: impl rusqlite::Params,
+    ) -> RedTokenResult<Vec<T>> {
+        let rows: Vec<String> = self.with_conn(|conn| {
+            let mut statement = conn.prepare(sql)?;
+            let rows = statement.query_map(params, |row| row.get(0))?;
+            rows.collect()
+        })?;
+
+        rows.iter()
+            .map(|data| {
+                serde_json::from_str(data).map_err(|e| {
+                    RedTokenError::DatabaseError(format!("Failed to parse record: {}", e))
+                })
+            })
+            .collect()
+    }
+}
+
+fn to_json<T: Serialize>(record: &T) -> RedTokenResult<String> {
+    serde_json::to_string(record)
+        .map_err(|e| RedTokenError::DatabaseError(format!("Failed to serialize record: {}", e)))
+}
+
+pub struct SqliteTokenRepository {
+    db: SqliteDatabase,
+}
+
+impl SqliteTokenRepository {
+    pub fn new(db: SqliteDatabase) -> Self {
+        Self { db }
+    }
+
+    fn upsert(&self, token: &Honeytoken) -> RedTokenResult<()> {
+        let data = to_json(token)?;
+        self.db.with_conn(|conn| {
+            conn.execute(
+                "INSERT OR REPLACE INTO tokens (id, value, data) VALUES (?1, ?2, ?3)",
+                params![token.id.to_string(), token.value, data],
+            )
+        })?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl TokenRepository for SqliteTokenRepository {
+    async fn save(&self, token: &Honeytoken) -> anyhow::Result<()> {
+        self.upsert(token)?;
+        Ok(())
+    }
+
+    async fn find_by_id(&self, id: Uuid) -> anyhow::Result<Option<Honeytoken>> {
+        let tokens = self.db.query(
+            "SELECT data FROM tokens WHERE id = ?1",
+            params![id.to_string()],
+        )?;
+        Ok(tokens.into_iter().next())
+    }
+
+    async fn find_by_value(&self, value: &str) -> anyhow::Result<Option<Honeytoken>> {
+        let tokens = self
+            .db
+            .query("SELECT data FROM tokens WHERE value = ?1", params![value])?;
+        Ok(tokens.into_iter().next())
+    }
+
+    async fn find_all(&self) -> anyhow::Result<Vec<Honeytoken>> {
+        Ok(self.db.query("SELECT data FROM tokens", [])?)
+    }
+
+    async fn update(&self, token: &Honeytoken) -> anyhow::Result<()> {
+        self.upsert(token)?;
+        Ok(())
+    }
+}
+
+pub struct SqliteOutboxRepository {
+    db: SqliteDatabase,
+}
+
+impl SqliteOutboxRepository {
+    pub fn new(db: SqliteDatabase) -> Self {
+        Self { db }
+    }
+
+    fn upsert(&self, job: &OutboxJob) -> RedTokenResult<()> {
+        let data = to_json(job)?;
+        self.db.with_conn(|conn| {
+            conn.execute(
+                "INSERT OR REPLACE INTO outbox (id, data) VALUES (?1, ?2)",
+                params![job.id.to_string(), data],
+            )
+        })?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl OutboxRepository for SqliteOutboxRepository {
+    async fn save(&self, job: &OutboxJob) -> anyhow::Result<()> {
+        self.upsert(job)?;
+        Ok(())
+    }
+
+    async fn find_by_id(&self, id: Uuid) -> anyhow::Result<Option<OutboxJob>> {
+        let jobs = self.db.query(
+            "SELECT data FROM outbox WHERE id = ?1",
+            params![id.to_string()],
+        )?;
+        Ok(jobs.into_iter().next())
+    }
+
+    async fn find_all(&self) -> anyhow::Result<Vec<OutboxJob>> {
+        Ok(self.db.query("SELECT data FROM outbox", [])?)
+    }
+
+    async fn update(&self, job: &OutboxJob) -> anyhow::Result<()> {
+        self.upsert(job)?;
+        Ok(())
+    }
+
+    async fn delete(&self, id: Uuid) -> anyhow::Result<()> {
+        self.db.with_conn(|conn| {
+            conn.execute("DELETE FROM outbox WHERE id = ?1", params![id.to_string()])
+        })?;
+        Ok(())
+    }
+}
+
+pub struct SqliteBackupRepository {
+    db: SqliteDatabase,
+}
+
+impl SqliteBackupRepository {
+    pub fn new(db: SqliteDatabase) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait]
+impl BackupRepository for SqliteBackupRepository {
+    async fn save(&self, record: &BackupRecord) -> anyhow::Result<()> {
+        let data = to_json(record)?;
+        self.db.with_conn(|conn| {
+            conn.execute(
+                "INSERT OR REPLACE INTO backups (id, data) VALUES (?1, ?2)",
+                params![record.id.to_string(), data],
+            )
+        })?;
+        Ok(())
+    }
+
+    async fn find_by_id(&self, id: Uuid) -> anyhow::Result<Option<BackupRecord>> {
+        let records = self.db.query(
+            "SELECT data FROM backups WHERE id = ?1",
+            params![id.to_string()],
+        )?;
+        Ok(records.into_iter().next())
+    }
+
+    async fn find_all(&self) -> anyhow::Result<Vec<BackupRecord>> {
+        Ok(self.db.query("SELECT data FROM backups", [])?)
+    }
+
+    async fn delete(&self, id: Uuid) -> anyhow::Result<()> {
+        self.db.with_conn(|conn| {
+            conn.execute("DELETE FROM backups WHERE id = ?1", params![id.to_string()])
+        })?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::outbox::AlertAction;
+
+    #[tokio::test]
+    async fn concurrent_outbox_writers_keep_every_job() {
+        let dir = std::env::temp_dir().join(format!("redtoken-outbox-{}", Uuid::new_v4()));
+        let path = dir.join("outbox.db");
+
+        // Separate repositories stand in for separate processes sharing the file
+        let writers: Vec<_> = (0..16)
+            .map(|_| {
+                let repo = FileOutboxRepository::new(&path);
+                tokio::spawn(async move {
+                    let job =
+                        OutboxJob::new(Uuid::new_v4(), "ops".to_string(), AlertAction::Trigger);
+                    repo.save(&job).await.unwrap();
+                    job.id
+                })
+            })
+            .collect();
+
+        let mut ids = Vec::new();
+        for writer in writers {
+            ids.push(writer.await.unwrap());
+        }
+
+        let repo = FileOutboxRepository::new(&path);
+        let stored = repo.find_all().await.unwrap();
+        assert_eq!(stored.len(), ids.len());
+        assert!(ids.iter().all(|id| stored.iter().any(|job| job.id == *id)));
+
+        repo.delete(ids[0]).await.unwrap();
+        assert!(repo.find_by_id(ids[0]).await.unwrap().is_none());
+        assert_eq!(repo.find_all().await.unwrap().len(), ids.len() - 1);
+
+        // Only the outbox and its lock file are left behind, no temporary files
+        let mut names: Vec<String> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["outbox.db", "outbox.db.lock"]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
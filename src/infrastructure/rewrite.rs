@@ -0,0 +1,596 @@
+use crate::core::{
+    error::{RedTokenError, RedTokenResult},
+    injection::{SymlinkPolicy, WritePolicy},
+};
+use log::{debug, info, warn};
+use similar::{DiffOp, TextDiff};
+use std::fs::{File, FileTimes, Metadata, OpenOptions, Permissions};
+use std::io::Write;
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Encoding {
+    Utf8,
+    Utf8Bom,
+    Utf16Le,
+    Utf16Be,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LineEnding {
+    Lf,
+    CrLf,
+}
+
+impl Encoding {
+    fn detect(bytes: &[u8]) -> (Self, &[u8]) {
+        match bytes {
+            [0xEF, 0xBB, 0xBF, rest @ ..] => (Encoding::Utf8Bom, rest),
+            [0xFF, 0xFE, rest @ ..] => (Encoding::Utf16Le, rest),
+            [0xFE, 0xFF, rest @ ..] => (Encoding::Utf16Be, rest),
+            _ => (Encoding::Utf8, bytes),
+        }
+    }
+
+    fn decode(self, body: &[u8]) -> Result<String, String> {
+        match self {
+            Encoding::Utf8 | Encoding::Utf8Bom => {
+                String::from_utf8(body.to_vec()).map_err(|e| e.to_string())
+            }
+            Encoding::Utf16Le | Encoding::Utf16Be => {
+                if !body.len().is_multiple_of(2) {
+                    return Err("odd number of bytes in UTF-16 text".to_string());
+                }
+                let units: Vec<u16> = body
+                    .chunks_exact(2)
+                    .map(|pair| match self {
+                        Encoding::Utf16Le => u16::from_le_bytes([pair[0], pair[1]]),
+                        _ => u16::from_be_bytes([pair[0], pair[1]]),
+                    })
+                    .collect();
+                String::from_utf16(&units).map_err(|e| e.to_string())
+            }
+        }
+    }
+
+    fn encode(self, text: &str) -> Vec<u8> {
+        match self {
+            Encoding::Utf8 => text.as_bytes().to_vec(),
+            Encoding::Utf8Bom => [&[0xEF, 0xBB, 0xBF][..], text.as_bytes()].concat(),
+            Encoding::Utf16Le => [0xFF, 0xFE]
+                .into_iter()
+                .chain(text.encode_utf16().flat_map(u16::to_le_bytes))
+                .collect(),
+            Encoding::Utf16Be => [0xFE, 0xFF]
+                .into_iter()
+                .chain(text.encode_utf16().flat_map(u16::to_be_bytes))
+                .collect(),
+        }
+    }
+}
+
+impl LineEnding {
+    // Whichever style the file mostly uses
+    fn detect(text: &str) -> Self {
+        let crlf = text.matches("\r\n").count();
+        let lf = text.matches('\n').count() - crlf;
+        if crlf > lf {
+            LineEnding::CrLf
+        } else {
+            LineEnding::Lf
+        }
+    }
+
+    // The ending of every terminated line, in order
+    fn per_line(text: &str) -> Vec<Self> {
+        text.split_inclusive('\n')
+            .filter(|line| line.ends_with('\n'))
+            .map(|line| {
+                if line.ends_with("\r\n") {
+                    LineEnding::CrLf
+                } else {
+                    LineEnding::Lf
+                }
+            })
+            .collect()
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+}
+
+fn read_error(path: &Path, message: impl Into<String>) -> RedTokenError {
+    RedTokenError::FileReadError {
+        path: path.to_path_buf(),
+        source: std::io::Error::new(std::io::ErrorKind::InvalidData, message.into()),
+    }
+}
+
+// A text file read for rewriting. Injectors work on `content` with plain \n line
+// endings; `write` puts back the original encoding, BOM, line endings and metadata.
+// Binary files such as Office documents are read into `bytes` instead.
+pub struct TextFile {
+    pub path: PathBuf, // The real file, after following a symlink
+    pub content: String,
+    pub bytes: Vec<u8>, // Only filled by `read_binary`
+    pub encoding: Encoding,
+    pub line_ending: LineEnding, // The majority style, used for new lines
+    original: String,            // Content as read, to match unchanged lines on write
+    line_endings: Vec<LineEnding>, // Per line of `original`
+    policy: WritePolicy,
+    metadata: Option<Metadata>, // None for a file that does not exist yet
+}
+
+impl TextFile {
+    pub async fn read(file_path: &str, policy: &WritePolicy) -> RedTokenResult<Self> {
+        let requested = PathBuf::from(file_path);
+        let policy = policy.clone();
+        tokio::task::spawn_blocking(move || Self::read_blocking(requested, policy, false))
+            .await
+            .map_err(|e| RedTokenError::Unknown(format!("File read task failed: {}", e)))?
+    }
+
+    // A binary file kept as raw bytes; one that does not exist yet reads as empty
+    pub async fn read_binary(file_path: &str, policy: &WritePolicy) -> RedTokenResult<Self> {
+        let requested = PathBuf::from(file_path);
+        if tokio::fs::symlink_metadata(&requested).await.is_err() {
+            return Ok(Self::new(&requested, policy));
+        }
+        let policy = policy.clone();
+        tokio::task::spawn_blocking(move || Self::read_blocking(requested, policy, true))
+            .await
+            .map_err(|e| RedTokenError::Unknown(format!("File read task failed: {}", e)))?
+    }
+
+    fn read_blocking(
+        requested: PathBuf,
+        policy: WritePolicy,
+        binary: bool,
+    ) -> RedTokenResult<Self> {
+        let io_error = |path: &Path| {
+            let path = path.to_path_buf();
+            move |source| RedTokenError::FileReadError { path, source }
+        };
+
+        let link_metadata = std::fs::symlink_metadata(&requested).map_err(io_error(&requested))?;
+        let path = if link_metadata.file_type().is_symlink() {
+            if policy.symlinks == SymlinkPolicy::Refuse {
+                return Err(RedTokenError::InvalidFileFormat(format!(
+                    "{:?} is a symbolic link and injection.symlinks is refuse",
+                    requested
+                )));
+            }
+            let target = std::fs::canonicalize(&requested).map_err(io_error(&requested))?;
+            info!("{:?} is a symbolic link, rewriting {:?}", requested, target);
+            target
+        } else {
+            requested
+        };
+
+        let metadata = std::fs::metadata(&path).map_err(io_error(&path))?;
+        if !metadata.is_file() {
+            return Err(read_error(&path, "not a regular file"));
+        }
+
+        let bytes = std::fs::read(&path).map_err(io_error(&path))?;
+        if binary {
+            return Ok(Self {
+                bytes,
+                metadata: Some(metadata),
+                ..Self::new(&path, &policy)
+            });
+        }
+        let (encoding, body) = Encoding::detect(&bytes);
+        let text = encoding
+            .decode(body)
+            .map_err(|e| read_error(&path, format!("unsupported text encoding: {}", e)))?;
+        let content = text.replace("\r\n", "\n");
+
+        Ok(Self {
+            path,
+            original: content.clone(),
+            content,
+            bytes: Vec::new(),
+            encoding,
+            line_ending: LineEnding::detect(&text),
+            line_endings: LineEnding::per_line(&text),
+            policy,
+            metadata: Some(metadata),
+        })
+    }
+
+    // A companion file that may not exist yet; a new one is created with mode 0600
+    pub async fn read_or_new(path: &Path, policy: &WritePolicy) -> RedTokenResult<Self> {
+        if tokio::fs::symlink_metadata(path).await.is_ok() {
+            return Self::read(&path.to_string_lossy(), policy).await;
+        }
+        Ok(Self::new(path, policy))
+    }
+
+    fn new(path: &Path, policy: &WritePolicy) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            content: String::new(),
+            bytes: Vec::new(),
+            encoding: Encoding::Utf8,
+            line_ending: LineEnding::Lf,
+            original: String::new(),
+            line_endings: Vec::new(),
+            policy: policy.clone(),
+            metadata: None,
+        }
+    }
+
+    pub fn exists(&self) -> bool {
+        self.metadata.is_some()
+    }
+
+    pub async fn write(&self, content: &str) -> RedTokenResult<()> {
+        let content = content.replace("\r\n", "\n");
+        let text = if self.line_endings.iter().all(|e| *e == self.line_ending) {
+            content.replace('\n', self.line_ending.as_str())
+        } else {
+            self.restore_line_endings(&content)
+        };
+        self.write_bytes(self.encoding.encode(&text)).await
+    }
+
+    // Mixed endings: lines kept from the original get their own ending back,
+    // new and changed lines get the majority one
+    fn restore_line_endings(&self, content: &str) -> String {
+        let mut kept = vec![None; content.split_inclusive('\n').count()];
+        for op in TextDiff::from_lines(self.original.as_str(), content).ops() {
+            if let DiffOp::Equal {
+                old_index,
+                new_index,
+                len,
+            } = *op
+            {
+                for offset in 0..len {
+                    kept[new_index + offset] = self.line_endings.get(old_index + offset).copied();
+                }
+            }
+        }
+
+        content
+            .split_inclusive('\n')
+            .zip(kept)
+            .map(|(line, ending)| match line.strip_suffix('\n') {
+                Some(body) => body.to_string() + ending.unwrap_or(self.line_ending).as_str(),
+                None => line.to_string(),
+            })
+            .collect()
+    }
+
+    // Raw bytes, with the same atomic replace and metadata handling as text
+    pub async fn write_bytes(&self, bytes: Vec<u8>) -> RedTokenResult<()> {
+        let path = self.path.clone();
+        let metadata = self.metadata.clone();
+        let preserve_mtime = self.policy.preserve_mtime;
+        tokio::task::spawn_blocking(move || {
+            Self::write_blocking(&path, &bytes, metadata.as_ref(), preserve_mtime).map_err(
+                |source| RedTokenError::FileWriteError {
+                    path: path.clone(),
+                    source,
+                },
+            )
+        })
+        .await
+        .map_err(|e| RedTokenError::Unknown(format!("File write task failed: {}", e)))?
+    }
+
+    fn write_blocking(
+        path: &Path,
+        bytes: &[u8],
+        metadata: Option<&Metadata>,
+        preserve_mtime: bool,
+    ) -> std::io::Result<()> {
+        let Some(metadata) = metadata else {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let mut file = OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .mode(0o600)
+                .open(path)?;
+            file.write_all(bytes)?;
+            return file.sync_all();
+        };
+
+        // Replacing a hard-linked file would split it from its other names, so write in place
+        if metadata.nlink() > 1 {
+            debug!(
+                "{:?} has {} links, writing in place",
+                path,
+                metadata.nlink()
+            );
+            let mut file = OpenOptions::new().write(true).truncate(true).open(path)?;
+            file.write_all(bytes)?;
+            file.sync_all()?;
+            if preserve_mtime {
+                Self::restore_times(&file, metadata)?;
+            }
+            return Ok(());
+        }
+
+        // Otherwise write a sibling temp file and rename it over the original,
+        // so a crash never leaves a half-written file behind
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let temp = path.with_file_name(format!(
+            ".{}.redtoken-{}.tmp",
+            name,
+            &Uuid::new_v4().simple().to_string()[..8]
+        ));
+
+        let result = (|| {
+            let mut file = OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .mode(0o600)
+                .open(&temp)?;
+            file.write_all(bytes)?;
+            Self::copy_metadata(&temp, path, metadata)?;
+            if preserve_mtime {
+                Self::restore_times(&file, metadata)?;
+            }
+            file.sync_all()?;
+            std::fs::rename(&temp, path)
+        })();
+
+        if result.is_err() {
+            let _ = std::fs::remove_file(&temp);
+        }
+        result
+    }
+
+    // Owner, mode and extended attributes of the original file
+    fn copy_metadata(temp: &Path, path: &Path, original: &Metadata) -> std::io::Result<()> {
+        let current = std::fs::metadata(temp)?;
+        if current.uid() != original.uid() || current.gid() != original.gid() {
+            // Only root may give a file away; keep going with the injecting user as owner
+            if let Err(e) =
+                std::os::unix::fs::chown(temp, Some(original.uid()), Some(original.gid()))
+            {
+                warn!("Could not keep the owner of {:?}: {}", path, e);
+            }
+        }
+
+        // After chown, which clears setuid/setgid bits
+        std::fs::set_permissions(temp, Permissions::from_mode(original.mode() & 0o7777))?;
+
+        match xattr::list(path) {
+            Ok(names) => {
+                for name in names {
+                    let copied = xattr::get(path, &name).and_then(|value| match value {
+                        Some(value) => xattr::set(temp, &name, &value),
+                        None => Ok(()),
+                    });
+                    if let Err(e) = copied {
+                        warn!("Could not keep attribute {:?} of {:?}: {}", name, path, e);
+                    }
+                }
+            }
+            Err(e) => debug!("Extended attributes of {:?} unavailable: {}", path, e),
+        }
+
+        Ok(())
+    }
+
+    fn restore_times(file: &File, original: &Metadata) -> std::io::Result<()> {
+        let mut times = FileTimes::new();
+        if let Ok(accessed) = original.accessed() {
+            times = times.set_accessed(accessed);
+        }
+        if let Ok(modified) = original.modified() {
+            times = times.set_modified(modified);
+        }
+        file.set_times(times)
+    }
+}
+
+// Unified diff between two versions of a text, labelled for display
+pub fn unified_diff(before: &str, after: &str, before_label: &str, after_label: &str) -> String {
+    TextDiff::from_lines(before, after)
+        .unified_diff()
+        .header(before_label, after_label)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, SystemTime};
+
+    // Append a line through TextFile, as an injector would
+    async fn append(path: &Path, policy: &WritePolicy) -> RedTokenResult<()> {
+        let file = TextFile::read(&path.to_string_lossy(), policy).await?;
+        file.write(&format!("{}NEW=1\n", file.content)).await
+    }
+
+    #[tokio::test]
+    async fn crlf_line_endings_are_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.env");
+        std::fs::write(&path, "A=1\r\nB=2\r\n").unwrap();
+
+        let file = TextFile::read(&path.to_string_lossy(), &WritePolicy::default())
+            .await
+            .unwrap();
+        assert_eq!(file.content, "A=1\nB=2\n");
+        assert_eq!(file.line_ending, LineEnding::CrLf);
+
+        append(&path, &WritePolicy::default()).await.unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"A=1\r\nB=2\r\nNEW=1\r\n");
+    }
+
+    #[tokio::test]
+    async fn mixed_line_endings_stay_per_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("mixed.env");
+        std::fs::write(&path, "A=1\r\nB=2\nC=3\r\nD=4").unwrap();
+
+        let file = TextFile::read(&path.to_string_lossy(), &WritePolicy::default())
+            .await
+            .unwrap();
+        assert_eq!(file.line_ending, LineEnding::CrLf);
+
+        // A line added in the middle and one changed; the rest keep their endings
+        file.write("A=1\nNEW=1\nB=2\nC=30\nD=4").await.unwrap();
+        assert_eq!(
+            std::fs::read(&path).unwrap(),
+            b"A=1\r\nNEW=1\r\nB=2\nC=30\r\nD=4"
+        );
+
+        // Writing the content back untouched gives the original bytes
+        let file = TextFile::read(&path.to_string_lossy(), &WritePolicy::default())
+            .await
+            .unwrap();
+        file.write(&file.content).await.unwrap();
+        assert_eq!(
+            std::fs::read(&path).unwrap(),
+            b"A=1\r\nNEW=1\r\nB=2\nC=30\r\nD=4"
+        );
+    }
+
+    #[tokio::test]
+    async fn bom_and_utf16_encodings_are_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        for encoding in [Encoding::Utf8Bom, Encoding::Utf16Le, Encoding::Utf16Be] {
+            let path = dir.path().join(format!("{:?}.ini", encoding));
+            std::fs::write(&path, encoding.encode("[db]\r\nuser=é\r\n")).unwrap();
+
+            let file = TextFile::read(&path.to_string_lossy(), &WritePolicy::default())
+                .await
+                .unwrap();
+            assert_eq!(file.encoding, encoding);
+            assert_eq!(file.content, "[db]\nuser=é\n");
+
+            append(&path, &WritePolicy::default()).await.unwrap();
+            assert_eq!(
+                std::fs::read(&path).unwrap(),
+                encoding.encode("[db]\r\nuser=é\r\nNEW=1\r\n")
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn mode_and_owner_are_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("secrets.env");
+        std::fs::write(&path, "A=1\n").unwrap();
+        std::fs::set_permissions(&path, Permissions::from_mode(0o640)).unwrap();
+        // Giving a file away needs root; otherwise the owner is already the writer's
+        let owned_by_other = std::os::unix::fs::chown(&path, Some(4242), Some(4343)).is_ok();
+        let before = std::fs::metadata(&path).unwrap();
+
+        append(&path, &WritePolicy::default()).await.unwrap();
+
+        let after = std::fs::metadata(&path).unwrap();
+        // Replaced through a temp file, not rewritten in place
+        assert_ne!(after.ino(), before.ino());
+        assert_eq!(after.mode() & 0o7777, 0o640);
+        assert_eq!((after.uid(), after.gid()), (before.uid(), before.gid()));
+        if owned_by_other {
+            assert_eq!((after.uid(), after.gid()), (4242, 4343));
+        }
+    }
+
+    #[tokio::test]
+    async fn extended_attributes_are_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("labelled.env");
+        std::fs::write(&path, "A=1\n").unwrap();
+        // Not every filesystem takes user attributes
+        if xattr::set(&path, "user.redtoken.test", b"kept").is_err() {
+            return;
+        }
+
+        append(&path, &WritePolicy::default()).await.unwrap();
+
+        assert_eq!(
+            xattr::get(&path, "user.redtoken.test").unwrap().as_deref(),
+            Some(&b"kept"[..])
+        );
+    }
+
+    #[tokio::test]
+    async fn symlinks_are_followed_or_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("real.env");
+        let link = dir.path().join("link.env");
+        std::fs::write(&target, "A=1\n").unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        let refuse = WritePolicy {
+            symlinks: SymlinkPolicy::Refuse,
+            ..WritePolicy::default()
+        };
+        assert!(append(&link, &refuse).await.is_err());
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "A=1\n");
+
+        append(&link, &WritePolicy::default()).await.unwrap();
+        assert!(std::fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "A=1\nNEW=1\n");
+    }
+
+    #[tokio::test]
+    async fn hard_links_stay_linked() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("shared.env");
+        let other = dir.path().join("other-name.env");
+        std::fs::write(&path, "A=1\n").unwrap();
+        std::fs::hard_link(&path, &other).unwrap();
+
+        append(&path, &WritePolicy::default()).await.unwrap();
+
+        let (first, second) = (
+            std::fs::metadata(&path).unwrap(),
+            std::fs::metadata(&other).unwrap(),
+        );
+        assert_eq!(first.ino(), second.ino());
+        assert_eq!(first.nlink(), 2);
+        assert_eq!(std::fs::read_to_string(&other).unwrap(), "A=1\nNEW=1\n");
+    }
+
+    #[tokio::test]
+    async fn mtime_is_kept_only_when_asked() {
+        let dir = tempfile::tempdir().unwrap();
+        let old = SystemTime::now() - Duration::from_secs(30 * 24 * 60 * 60);
+        let preserve = WritePolicy {
+            preserve_mtime: true,
+            ..WritePolicy::default()
+        };
+
+        for (name, policy) in [
+            ("kept.env", preserve),
+            ("touched.env", WritePolicy::default()),
+        ] {
+            let path = dir.path().join(name);
+            std::fs::write(&path, "A=1\n").unwrap();
+            File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(old)
+                .unwrap();
+
+            append(&path, &policy).await.unwrap();
+
+            let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+            assert_eq!(modified == old, policy.preserve_mtime, "{}", name);
+        }
+    }
+}
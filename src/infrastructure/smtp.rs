@@ -0,0 +1,125 @@
+use crate::core::error::{RedTokenError, RedTokenResult};
+use lettre::{
+    message::{header::ContentType, Mailbox},
+    transport::smtp::AsyncSmtpTransportBuilder,
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+// Connection URL for lettre. A bare `host:port` (optionally `user:pass@host:port`) picks the
+// TLS mode from the port: 465 is implicit TLS, 25 uses STARTTLS when offered, any other port
+// requires STARTTLS. Full smtp:// and smtps:// URLs are used as given.
+fn connection_url(server: &str) -> String {
+    if server.contains("://") {
+        return server.to_string();
+    }
+    let port = server
+        .rsplit_once(':')
+        .and_then(|(_, port)| port.parse::<u16>().ok());
+    match port {
+        Some(465) => format!("smtps://{}", server),
+        Some(25) => format!("smtp://{}?tls=opportunistic", server),
+        Some(_) => format!("smtp://{}?tls=required", server),
+        None => format!("smtp://{}:587?tls=required", server),
+    }
+}
+
+// Server without credentials, for messages
+fn display(server: &str) -> &str {
+    server.rsplit_once('@').map_or(server, |(_, host)| host)
+}
+
+fn builder(server: &str) -> RedTokenResult<AsyncSmtpTransportBuilder> {
+    AsyncSmtpTransport::<Tokio1Executor>::from_url(&connection_url(server)).map_err(|e| {
+        RedTokenError::ConfigError(format!("Invalid SMTP server '{}': {}", display(server), e))
+    })
+}
+
+fn transport(server: &str) -> RedTokenResult<AsyncSmtpTransport<Tokio1Executor>> {
+    Ok(builder(server)?.timeout(Some(TIMEOUT)).build())
+}
+
+fn mailbox(field: &str, address: &str) -> RedTokenResult<Mailbox> {
+    address.parse().map_err(|e| {
+        RedTokenError::ConfigError(format!("Invalid {} address '{}': {}", field, address, e))
+    })
+}
+
+pub fn validate(server: &str, from: &str, to: &str) -> RedTokenResult<()> {
+    builder(server)?;
+    mailbox("sender", from)?;
+    mailbox("recipient", to)?;
+    Ok(())
+}
+
+pub async fn send(
+    server: &str,
+    from: &str,
+    to: &str,
+    subject: &str,
+    body: &str,
+) -> RedTokenResult<()> {
+    let message = Message::builder()
+        .from(mailbox("sender", from)?)
+        .to(mailbox("recipient", to)?)
+        .subject(subject)
+        .header(ContentType::TEXT_PLAIN)
+        .body(body.to_string())
+        .map_err(|e| RedTokenError::NotificationError(format!("Failed to build email: {}", e)))?;
+
+    transport(server)?.send(message).await.map_err(|e| {
+        RedTokenError::NotificationError(format!(
+            "SMTP delivery via {} failed: {}",
+            display(server),
+            e
+        ))
+    })?;
+    Ok(())
+}
+
+// Connect and greet the server without sending a message
+pub async fn probe(server: &str) -> RedTokenResult<()> {
+    let connected = transport(server)?.test_connection().await.map_err(|e| {
+        RedTokenError::NotificationError(format!(
+            "SMTP server {} unreachable: {}",
+            display(server),
+            e
+        ))
+    })?;
+    if !connected {
+        return Err(RedTokenError::NotificationError(format!(
+            "SMTP server {} did not answer",
+            display(server)
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tls_mode_follows_the_port() {
+        assert_eq!(connection_url("mail.local:465"), "smtps://mail.local:465");
+        assert_eq!(
+            connection_url("mail.local:25"),
+            "smtp://mail.local:25?tls=opportunistic"
+        );
+        assert_eq!(
+            connection_url("u:p@mail.local:587"),
+            "smtp://u:p@mail.local:587?tls=required"
+        );
+        assert_eq!(
+            connection_url("mail.local"),
+            "smtp://mail.local:587?tls=required"
+        );
+        assert_eq!(
+            connection_url("smtp://mail.local:2525"),
+            "smtp://mail.local:2525"
+        );
+        assert_eq!(display("u:p@mail.local:587"), "mail.local:587");
+    }
+}
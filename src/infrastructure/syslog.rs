@@ -0,0 +1,443 @@
+use crate::core::{
+    error::{RedTokenError, RedTokenResult},
+    notification::SiemFormat,
+    token::{Honeytoken, Severity},
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use std::time::{Duration, SystemTime};
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpStream, UdpSocket, UnixDatagram};
+
+const VENDOR: &str = "RedToken";
+const PRODUCT: &str = "RedToken";
+const EVENT_ID: &str = "HoneytokenTriggered";
+const EVENT_NAME: &str = "Honeytoken triggered";
+const FACILITY_AUTH: u8 = 4;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Syslog transport parsed from a channel address
+#[derive(Debug, PartialEq)]
+enum Transport {
+    Udp(String),
+    Tcp(String),
+    Tls { addr: String, domain: String },
+    Unix(String),
+}
+
+fn parse_address(address: &str) -> RedTokenResult<Transport> {
+    let invalid = || {
+        RedTokenError::ConfigError(format!(
+            "Invalid syslog address '{}', expected udp://, tcp://, tls://host:port or unix:///path",
+            address
+        ))
+    };
+
+    if address.starts_with('/') {
+        return Ok(Transport::Unix(address.to_string()));
+    }
+
+    let (scheme, rest) = address.split_once("://").ok_or_else(invalid)?;
+    if rest.is_empty() {
+        return Err(invalid());
+    }
+
+    match scheme {
+        "udp" => Ok(Transport::Udp(rest.to_string())),
+        "tcp" => Ok(Transport::Tcp(rest.to_string())),
+        "tls" => {
+            let domain = rest.rsplit_once(':').map(|(host, _)| host).unwrap_or(rest);
+            Ok(Transport::Tls {
+                addr: rest.to_string(),
+                domain: domain.trim_matches(|c| c == '[' || c == ']').to_string(),
+            })
+        }
+        "unix" => Ok(Transport::Unix(rest.to_string())),
+        _ => Err(invalid()),
+    }
+}
+
+pub fn validate_address(address: &str) -> RedTokenResult<()> {
+    parse_address(address).map(|_| ())
+}
+
+// Connect without sending anything; UDP is connectionless so only the address is checked
+pub async fn probe(address: &str) -> RedTokenResult<()> {
+    let io_error = |e: std::io::Error| {
+        RedTokenError::NotificationError(format!("Syslog endpoint {} unreachable: {}", address, e))
+    };
+
+    match parse_address(address)? {
+        Transport::Udp(_) => Ok(()),
+        Transport::Tcp(addr) | Transport::Tls { addr, .. } => {
+            tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect(&addr))
+                .await
+                .map_err(|_| {
+                    RedTokenError::NotificationError(format!(
+                        "Syslog connection to {} timed out",
+                        address
+                    ))
+                })?
+                .map_err(io_error)?;
+            Ok(())
+        }
+        Transport::Unix(path) => {
+            std::fs::metadata(&path).map_err(io_error)?;
+            Ok(())
+        }
+    }
+}
+
+pub fn hostname() -> String {
+    let name = std::fs::read_to_string("/proc/sys/kernel/hostname")
+        .ok()
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .unwrap_or_default();
+
+    // RFC 5424 HOSTNAME is printable ASCII without spaces
+    let name: String = name
+        .trim()
+        .chars()
+        .filter(|c| c.is_ascii_graphic())
+        .take(255)
+        .collect();
+
+    if name.is_empty() {
+        "-".to_string()
+    } else {
+        name
+    }
+}
+
+pub fn trigger_time(token: &Honeytoken) -> DateTime<Utc> {
+    DateTime::<Utc>::from(token.last_checked.unwrap_or_else(SystemTime::now))
+}
+
+fn syslog_severity(severity: Severity) -> u8 {
+    match severity {
+        Severity::Critical => 2,
+        Severity::High => 3,
+        Severity::Medium => 4,
+        Severity::Low => 5,
+    }
+}
+
+// CEF and LEEF both use a 0-10 scale
+fn siem_severity(severity: Severity) -> u8 {
+    match severity {
+        Severity::Critical => 10,
+        Severity::High => 8,
+        Severity::Medium => 5,
+        Severity::Low => 3,
+    }
+}
+
+fn cef_escape_header(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('|', "\\|")
+        .replace(['\r', '\n'], " ")
+}
+
+fn cef_escape_extension(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('=', "\\=")
+        .replace('\r', "\\r")
+        .replace('\n', "\\n")
+}
+
+fn leef_escape_header(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('|', "\\|")
+}
+
+fn leef_escape_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\r', "\\r")
+        .replace('\n', "\\n")
+}
+
+// Token fields shared by both formats, in emission order
+fn event_fields(token: &Honeytoken) -> Vec<(&'static str, String)> {
+    let trigger = token.last_trigger.clone().unwrap_or_default();
+    let mut fields = vec![("tokenId", token.id.to_string())];
+
+    fields.push(("filePath", token.file_path.clone()));
+    if let Some(source_ip) = trigger.source_ip {
+        fields.push(("src", source_ip));
+    }
+    if let Some(user_agent) = trigger.user_agent {
+        fields.push(("userAgent", user_agent));
+    }
+    if let Some(kind) = &token.kind {
+        fields.push(("kind", kind.clone()));
+    }
+    if !token.tags.is_empty() {
+        fields.push(("tags", token.tags.join(",")));
+    }
+    fields
+}
+
+pub fn format_cef(token: &Honeytoken) -> String {
+    let header = [
+        VENDOR,
+        PRODUCT,
+        env!("CARGO_PKG_VERSION"),
+        EVENT_ID,
+        EVENT_NAME,
+    ]
+    .iter()
+    .map(|field| cef_escape_header(field))
+    .collect::<Vec<_>>()
+    .join("|");
+
+    let mut extension = vec![format!("rt={}", trigger_time(token).timestamp_millis())];
+    for (key, value) in event_fields(token) {
+        // Map onto standard CEF keys where one exists
+        let entry = match key {
+            "tokenId" => format!("cs1Label=tokenId cs1={}", cef_escape_extension(&value)),
+            "filePath" => format!("filePath={}", cef_escape_extension(&value)),
+            "src" => format!("src={}", cef_escape_extension(&value)),
+            "userAgent" => format!("requestClientApplication={}", cef_escape_extension(&value)),
+            "kind" => format!("cs2Label=kind cs2={}", cef_escape_extension(&value)),
+            "tags" => format!("cs3Label=tags cs3={}", cef_escape_extension(&value)),
+            _ => continue,
+        };
+        extension.push(entry);
+    }
+    extension.push(format!("cs4Label=severity cs4={}", token.severity));
+
+    format!(
+        "CEF:0|{}|{}|{}",
+        header,
+        siem_severity(token.severity),
+        extension.join(" ")
+    )
+}
+
+pub fn format_leef(token: &Honeytoken) -> String {
+    let header = [VENDOR, PRODUCT, env!("CARGO_PKG_VERSION"), EVENT_ID]
+        .iter()
+        .map(|field| leef_escape_header(field))
+        .collect::<Vec<_>>()
+        .join("|");
+
+    let mut attributes = vec![
+        format!("devTime={}", trigger_time(token).timestamp_millis()),
+        format!("sev={}", siem_severity(token.severity)),
+        "cat=honeytoken".to_string(),
+    ];
+    for (key, value) in event_fields(token) {
+        attributes.push(format!("{}={}", key, leef_escape_value(&value)));
+    }
+    attributes.push(format!("severity={}", token.severity));
+
+    // LEEF 2.0 header names the attribute delimiter; x09 is a tab
+    format!("LEEF:2.0|{}|x09|{}", header, attributes.join("\t"))
+}
+
+pub fn format_rfc5424(token: &Honeytoken, body: &str) -> String {
+    let priority = FACILITY_AUTH * 8 + syslog_severity(token.severity);
+    let timestamp = trigger_time(token).to_rfc3339_opts(SecondsFormat::Millis, true);
+
+    format!(
+        "<{}>1 {} {} redtoken {} TRIGGER - {}",
+        priority,
+        timestamp,
+        hostname(),
+        std::process::id(),
+        body
+    )
+}
+
+pub fn format_message(token: &Honeytoken, format: SiemFormat) -> String {
+    let body = match format {
+        SiemFormat::Cef => format_cef(token),
+        SiemFormat::Leef => format_leef(token),
+    };
+    format_rfc5424(token, &body)
+}
+
+// RFC 6587 octet-counting framing for stream transports
+fn frame(message: &str) -> Vec<u8> {
+    format!("{} {}", message.len(), message).into_bytes()
+}
+
+pub async fn send(address: &str, message: &str) -> RedTokenResult<()> {
+    let io_error = |e: std::io::Error| {
+        RedTokenError::NotificationError(format!("Syslog delivery to {} failed: {}", address, e))
+    };
+    let timeout =
+        || RedTokenError::NotificationError(format!("Syslog connection to {} timed out", address));
+
+    match parse_address(address)? {
+        Transport::Udp(addr) => {
+            let bind = if addr.starts_with('[') {
+                "[::]:0"
+            } else {
+                "0.0.0.0:0"
+            };
+            let socket = UdpSocket::bind(bind).await.map_err(io_error)?;
+            socket
+                .send_to(message.as_bytes(), &addr)
+                .await
+                .map_err(io_error)?;
+        }
+        Transport::Tcp(addr) => {
+            let mut stream = tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect(&addr))
+                .await
+                .map_err(|_| timeout())?
+                .map_err(io_error)?;
+            stream.write_all(&frame(message)).await.map_err(io_error)?;
+            stream.shutdown().await.map_err(io_error)?;
+        }
+        Transport::Tls { addr, domain } => {
+            let connector = native_tls::TlsConnector::new().map_err(|e| {
+                RedTokenError::NotificationError(format!("Failed to create TLS connector: {}", e))
+            })?;
+            let connector = tokio_native_tls::TlsConnector::from(connector);
+
+            let stream = tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect(&addr))
+                .await
+                .map_err(|_| timeout())?
+                .map_err(io_error)?;
+            let mut stream = connector.connect(&domain, stream).await.map_err(|e| {
+                RedTokenError::NotificationError(format!(
+                    "TLS handshake with {} failed: {}",
+                    address, e
+                ))
+            })?;
+            stream.write_all(&frame(message)).await.map_err(io_error)?;
+            stream.shutdown().await.map_err(io_error)?;
+        }
+        Transport::Unix(path) => {
+            let socket = UnixDatagram::unbound().map_err(io_error)?;
+            socket
+                .send_to(message.as_bytes(), &path)
+                .await
+                .map_err(io_error)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::token::TriggerEvent;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+
+    // Every character the formats must escape, and a multi-byte one for the framing
+    fn awkward_token() -> Honeytoken {
+        let mut token = Honeytoken::new(
+            "RT_syslog".to_string(),
+            "C:\\shares\\a|b=c\nrapport-été.env".to_string(),
+        );
+        token.kind = Some("env".to_string());
+        token.mark_as_triggered(TriggerEvent {
+            source_ip: Some("203.0.113.7".to_string()),
+            user_agent: Some("curl/8.0\tx=y".to_string()),
+        });
+        token
+    }
+
+    // The first `count - 1` fields split on unescaped `|`, then the rest, where a
+    // literal `|` needs no escaping
+    fn split_header(message: &str, count: usize) -> Vec<String> {
+        let mut fields = vec![String::new()];
+        let mut chars = message.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => {
+                    let field = fields.last_mut().unwrap();
+                    field.push(c);
+                    field.extend(chars.next());
+                }
+                '|' if fields.len() < count => fields.push(String::new()),
+                _ => fields.last_mut().unwrap().push(c),
+            }
+        }
+        fields
+    }
+
+    #[tokio::test]
+    async fn tcp_delivery_is_octet_counted_rfc5424() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("tcp://{}", listener.local_addr().unwrap());
+        let received = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut bytes = Vec::new();
+            stream.read_to_end(&mut bytes).await.unwrap();
+            String::from_utf8(bytes).unwrap()
+        });
+
+        let token = awkward_token();
+        let message = format_message(&token, SiemFormat::Cef);
+        send(&address, &message).await.unwrap();
+        let received = received.await.unwrap();
+
+        // The length counts bytes, not characters
+        let (length, body) = received.split_once(' ').unwrap();
+        assert_eq!(length.parse::<usize>().unwrap(), body.len());
+        assert_eq!(body, message);
+
+        // <PRI>1 TIMESTAMP HOSTNAME APP-NAME PROCID MSGID STRUCTURED-DATA MSG
+        let parts: Vec<&str> = body.splitn(8, ' ').collect();
+        assert_eq!(parts[0], "<36>1"); // auth facility, warning for medium
+        assert!(DateTime::parse_from_rfc3339(parts[1]).is_ok());
+        assert!(parts[2].chars().all(|c| c.is_ascii_graphic()));
+        assert_eq!(parts[3], "redtoken");
+        assert_eq!(parts[4], std::process::id().to_string());
+        assert_eq!((parts[5], parts[6]), ("TRIGGER", "-"));
+        assert!(parts[7].starts_with("CEF:0|"));
+    }
+
+    #[tokio::test]
+    async fn udp_delivery_is_one_unframed_datagram() {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("udp://{}", socket.local_addr().unwrap());
+
+        let message = format_message(&awkward_token(), SiemFormat::Leef);
+        send(&address, &message).await.unwrap();
+
+        let mut buffer = vec![0; 4096];
+        let length = socket.recv(&mut buffer).await.unwrap();
+        assert_eq!(&buffer[..length], message.as_bytes());
+    }
+
+    #[test]
+    fn cef_escapes_header_and_extension_values() {
+        let cef = format_cef(&awkward_token());
+        // Version, vendor, product, version, event id, name, severity, extension
+        let fields = split_header(&cef, 8);
+        assert_eq!(fields.len(), 8);
+        assert_eq!(fields[0], "CEF:0");
+        assert_eq!(fields[6], "5");
+        assert!(!cef.contains('\n'));
+
+        let extension = &fields[7];
+        assert!(extension.contains("filePath=C:\\\\shares\\\\a|b\\=c\\nrapport-été.env"));
+        assert!(extension.contains("requestClientApplication=curl/8.0\tx\\=y"));
+        assert!(extension.contains("src=203.0.113.7"));
+        assert!(extension.contains("cs2Label=kind cs2=env"));
+    }
+
+    #[test]
+    fn leef_escapes_the_tab_delimiter() {
+        let leef = format_leef(&awkward_token());
+        let fields = split_header(&leef, 7);
+        assert_eq!(fields.len(), 7);
+        assert_eq!(fields[0], "LEEF:2.0");
+        assert_eq!(fields[5], "x09");
+        assert!(!leef.contains('\n'));
+
+        let attributes: Vec<&str> = fields[6].split('\t').collect();
+        assert!(attributes.contains(&"filePath=C:\\\\shares\\\\a|b=c\\nrapport-été.env"));
+        assert!(attributes.contains(&"userAgent=curl/8.0\\tx=y"));
+        assert!(attributes.contains(&"sev=5"));
+        assert!(attributes.contains(&"cat=honeytoken"));
+    }
+}
@@ -0,0 +1,302 @@
+use crate::core::{
+    error::{RedTokenError, RedTokenResult},
+    notification::{TemplateConfig, TemplateOverride},
+    token::{Honeytoken, Severity},
+};
+use crate::infrastructure::syslog;
+use chrono::Local;
+
+#[derive(Debug, Clone)]
+pub struct AlertTemplate {
+    pub title: String,
+    pub body: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct RenderedAlert {
+    pub title: String,
+    pub body: String,
+}
+
+const EN_BODY: &str = "Token ID: {{token_id}}
+File: {{file_path}}
+Severity: {{severity}}
+Source IP: {{source_ip}}
+User agent: {{user_agent}}
+Host: {{host}}
+Campaign: {{campaign}}
+Triggered: {{triggered_at}}";
+
+const ID_BODY: &str = "ID Token: {{token_id}}
+File: {{file_path}}
+Tingkat keparahan: {{severity}}
+IP sumber: {{source_ip}}
+User agent: {{user_agent}}
+Host: {{host}}
+Kampanye: {{campaign}}
+Waktu: {{triggered_at}}";
+
+// Built-in wording, used whenever no override matches
+fn builtin(language: &str, severity: Severity) -> AlertTemplate {
+    let critical = severity == Severity::Critical;
+
+    match (language, critical) {
+        ("id", false) => AlertTemplate {
+            title: "{{icon}} Honeytoken terpicu: {{file_path}}".to_string(),
+            body: ID_BODY.to_string(),
+        },
+        ("id", true) => AlertTemplate {
+            title: "{{icon}} Honeytoken KRITIS terpicu: {{file_path}}".to_string(),
+            body: format!("Perlu penanganan segera.\n\n{}", ID_BODY),
+        },
+        (_, false) => AlertTemplate {
+            title: "{{icon}} Honeytoken triggered: {{file_path}}".to_string(),
+            body: EN_BODY.to_string(),
+        },
+        (_, true) => AlertTemplate {
+            title: "{{icon}} CRITICAL honeytoken triggered: {{file_path}}".to_string(),
+            body: format!("Immediate response required.\n\n{}", EN_BODY),
+        },
+    }
+}
+
+fn severity_label(severity: Severity, language: &str) -> String {
+    if language != "id" {
+        return severity.to_string();
+    }
+
+    match severity {
+        Severity::Low => "rendah",
+        Severity::Medium => "sedang",
+        Severity::High => "tinggi",
+        Severity::Critical => "kritis",
+    }
+    .to_string()
+}
+
+fn severity_icon(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Low => "🟡",
+        Severity::Medium => "🟠",
+        Severity::High => "🔴",
+        Severity::Critical => "🚨",
+    }
+}
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+// Values available to templates as {{name}} placeholders
+pub struct AlertContext {
+    values: Vec<(&'static str, String)>,
+}
+
+impl AlertContext {
+    pub fn from_token(token: &Honeytoken, language: &str) -> Self {
+        let unknown = if language == "id" {
+            "tidak diketahui"
+        } else {
+            "unknown"
+        };
+        let trigger = token.last_trigger.clone().unwrap_or_default();
+        let triggered_at = syslog::trigger_time(token)
+            .with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M:%S %:z")
+            .to_string();
+
+        let tags = if token.tags.is_empty() {
+            "-".to_string()
+        } else {
+            token.tags.join(", ")
+        };
+
+        let values = vec![
+            ("token_id", token.id.to_string()),
+            ("file_path", token.file_path.clone()),
+            ("severity", severity_label(token.severity, language)),
+            ("icon", severity_icon(token.severity).to_string()),
+            (
+                "kind",
+                token.kind.clone().unwrap_or_else(|| "-".to_string()),
+            ),
+            ("tags", tags),
+            (
+                "campaign",
+                token.campaign.clone().unwrap_or_else(|| "-".to_string()),
+            ),
+            (
+                "source_ip",
+                trigger.source_ip.unwrap_or_else(|| unknown.to_string()),
+            ),
+            (
+                "user_agent",
+                trigger.user_agent.unwrap_or_else(|| unknown.to_string()),
+            ),
+            ("host", syslog::hostname()),
+            ("triggered_at", triggered_at),
+        ];
+
+        Self { values }
+    }
+
+    // One left-to-right pass: substituted values are never scanned again, so a user agent
+    // or path containing {{host}} stays literal. Unknown placeholders are kept as written.
+    fn render(&self, template: &str, escape: bool) -> String {
+        let mut output = String::with_capacity(template.len());
+        let mut rest = template;
+
+        while let Some(start) = rest.find("{{") {
+            output.push_str(&rest[..start]);
+            let after = &rest[start + 2..];
+
+            let value = after.find("}}").and_then(|end| {
+                self.values
+                    .iter()
+                    .find(|(name, _)| *name == &after[..end])
+                    .map(|(_, value)| (value, end))
+            });
+
+            match value {
+                Some((value, end)) => {
+                    if escape {
+                        output.push_str(&escape_html(value));
+                    } else {
+                        output.push_str(value);
+                    }
+                    rest = &after[end + 2..];
+                }
+                None => {
+                    output.push_str("{{");
+                    rest = after;
+                }
+            }
+        }
+
+        output.push_str(rest);
+        output
+    }
+}
+
+pub struct TemplateEngine {
+    language: String,
+    overrides: Vec<(TemplateOverride, AlertTemplate)>,
+}
+
+impl TemplateEngine {
+    pub fn load(config: &TemplateConfig) -> RedTokenResult<Self> {
+        let mut overrides = Vec::new();
+
+        for template_override in &config.overrides {
+            let content = std::fs::read_to_string(&template_override.path).map_err(|e| {
+                RedTokenError::FileReadError {
+                    path: template_override.path.clone(),
+                    source: e,
+                }
+            })?;
+
+            let (title, body) = content.split_once('\n').unwrap_or((&content, ""));
+            if title.trim().is_empty() {
+                return Err(RedTokenError::ConfigError(format!(
+                    "Alert template {:?} must start with a title line",
+                    template_override.path
+                )));
+            }
+
+            overrides.push((
+                template_override.clone(),
+                AlertTemplate {
+                    title: title.trim().to_string(),
+                    body: body.trim_matches('\n').to_string(),
+                },
+            ));
+        }
+
+        Ok(Self {
+            language: config.language.clone(),
+            overrides,
+        })
+    }
+
+    pub fn language(&self) -> &str {
+        &self.language
+    }
+
+    // Most specific matching override wins, falling back to the built-in template
+    pub fn select(&self, channel: &str, severity: Severity, language: &str) -> AlertTemplate {
+        let mut best: Option<(usize, &AlertTemplate)> = None;
+
+        for (selector, template) in &self.overrides {
+            let matches = selector
+                .channel
+                .as_deref()
+                .is_none_or(|c| c.eq_ignore_ascii_case(channel))
+                && selector.severity.is_none_or(|s| s == severity)
+                && selector
+                    .language
+                    .as_deref()
+                    .is_none_or(|l| l.eq_ignore_ascii_case(language));
+
+            if !matches {
+                continue;
+            }
+
+            let specificity = selector.channel.is_some() as usize
+                + selector.severity.is_some() as usize
+                + selector.language.is_some() as usize;
+            if best.is_none_or(|(score, _)| specificity > score) {
+                best = Some((specificity, template));
+            }
+        }
+
+        match best {
+            Some((_, template)) => template.clone(),
+            None => builtin(language, severity),
+        }
+    }
+
+    pub fn render(&self, channel: &str, token: &Honeytoken) -> RenderedAlert {
+        self.render_in(channel, token, &self.language)
+    }
+
+    pub fn render_in(&self, channel: &str, token: &Honeytoken, language: &str) -> RenderedAlert {
+        let template = self.select(channel, token.severity, language);
+        let context = AlertContext::from_token(token, language);
+
+        // Telegram messages are sent with HTML parse mode
+        let escape = channel == "telegram";
+
+        RenderedAlert {
+            title: context.render(&template.title, escape),
+            body: context.render(&template.body, escape),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::token::TriggerEvent;
+
+    #[test]
+    fn substituted_values_are_not_expanded_again() {
+        let mut token = Honeytoken::new("value".to_string(), "/srv/{{token_id}}/.env".to_string());
+        token.mark_as_triggered(TriggerEvent {
+            source_ip: Some("10.0.0.1".to_string()),
+            user_agent: Some("curl {{host}} <b>".to_string()),
+        });
+        let context = AlertContext::from_token(&token, "en");
+
+        assert_eq!(
+            context.render("{{user_agent}} at {{file_path}} {{unknown}} {{", false),
+            "curl {{host}} <b> at /srv/{{token_id}}/.env {{unknown}} {{"
+        );
+        assert_eq!(
+            context.render("{{source_ip}}: {{user_agent}}", true),
+            "10.0.0.1: curl {{host}} &lt;b&gt;"
+        );
+    }
+}
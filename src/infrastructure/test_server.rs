@@ -0,0 +1,82 @@
+// Local HTTP stand-in for webhook, SIEM and paging APIs in tests. Requests are recorded
+// and answered from a script of replies, then with 200 and `{}` once it runs out.
+use axum::{
+    extract::State,
+    http::{HeaderMap, Method, StatusCode, Uri},
+    Router,
+};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: Method,
+    pub path: String, // Including the query string
+    pub headers: HeaderMap,
+    pub body: String,
+}
+
+impl Request {
+    pub fn json(&self) -> serde_json::Value {
+        serde_json::from_str(&self.body).expect("JSON request body")
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).and_then(|value| value.to_str().ok())
+    }
+}
+
+#[derive(Default)]
+struct Recorder {
+    replies: VecDeque<(u16, String)>,
+    requests: Vec<Request>,
+}
+
+pub struct TestServer {
+    pub url: String,
+    recorder: Arc<Mutex<Recorder>>,
+}
+
+impl TestServer {
+    pub async fn start(replies: &[(u16, &str)]) -> Self {
+        let recorder = Arc::new(Mutex::new(Recorder {
+            replies: replies
+                .iter()
+                .map(|(status, body)| (*status, body.to_string()))
+                .collect(),
+            requests: Vec::new(),
+        }));
+        let app = Router::new().fallback(record).with_state(recorder.clone());
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        Self { url, recorder }
+    }
+
+    pub fn requests(&self) -> Vec<Request> {
+        self.recorder.lock().unwrap().requests.clone()
+    }
+}
+
+async fn record(
+    State(recorder): State<Arc<Mutex<Recorder>>>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: String,
+) -> (StatusCode, String) {
+    let mut recorder = recorder.lock().unwrap();
+    recorder.requests.push(Request {
+        method,
+        path: uri.to_string(),
+        headers,
+        body,
+    });
+    let (status, body) = recorder
+        .replies
+        .pop_front()
+        .unwrap_or((200, "{}".to_string()));
+    (StatusCode::from_u16(status).unwrap(), body)
+}
@@ -1,9 +1,21 @@
-use clap::{Parser, Subcommand};
+use crate::core::injection::KubeAuth;
+use crate::core::notification::{NotificationChannel, SiemFormat, TlsOptions};
+use crate::core::token::Severity;
+use anyhow::{anyhow, Result};
+use clap::{Args, Parser, Subcommand};
 use std::path::PathBuf;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 pub struct Cli {
+    /// Path to the config file (defaults to the XDG config directory)
+    #[arg(long, global = true, env = "REDTOKEN_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    /// Use the system-wide config in /etc/redtoken and data in /var/lib/redtoken
+    #[arg(long, global = true, conflicts_with = "config")]
+    pub system: bool,
+
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -20,9 +32,42 @@ pub enum Commands {
         #[arg(short, long)]
         value: Option<String>,
 
-        /// File type (env, json, yaml, bash)
-        #[arg(short, long)]
+        /// File type (env, json, yaml, bash, zsh, fish, powershell, psql, mysql, python, node, toml, ini, aws, kubeconfig, k8s-secret, docker,
+        /// compose, dockerfile, npmrc, pypirc, netrc, git-credentials, pip, py, js, ts, tsx, go, java, rs, tfvars, tfstate, docx, xlsx)
+        #[arg(long)]
         file_type: Option<String>,
+
+        /// Alert severity (low, medium, high, critical)
+        #[arg(short, long, default_value = "medium")]
+        severity: Severity,
+
+        /// Tags used by alert routing rules (comma separated)
+        #[arg(short, long, value_delimiter = ',')]
+        tags: Vec<String>,
+
+        /// Campaign name included in alerts
+        #[arg(long)]
+        campaign: Option<String>,
+
+        /// Keep the file's modification time unchanged
+        #[arg(long)]
+        preserve_mtime: bool,
+
+        /// For AWS credentials, also add the profile to the `config` file next to it
+        #[arg(long)]
+        aws_config: bool,
+
+        /// For kubeconfig, how the decoy user authenticates (token, cert)
+        #[arg(long, default_value = "token")]
+        kube_auth: KubeAuth,
+
+        /// For compose files, the service to add the variable to (defaults to the first)
+        #[arg(long)]
+        service: Option<String>,
+
+        /// Show the changes without writing the file or saving the token
+        #[arg(long)]
+        dry_run: bool,
     },
 
     /// List all injected tokens
@@ -33,6 +78,36 @@ pub enum Commands {
         /// Token ID to remove
         #[arg(short, long)]
         id: String,
+
+        /// Show the changes without writing the file
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Acknowledge a triggered token and resolve its open incidents
+    Resolve {
+        /// Token ID to resolve
+        id: String,
+    },
+
+    /// List or prune backups of injected files
+    Backups {
+        #[command(subcommand)]
+        command: BackupCommands,
+    },
+
+    /// Restore a file from a backup, showing the changes first
+    Restore {
+        /// Backup ID, or a token ID to undo that token's injection
+        target: String,
+
+        /// Restore without asking for confirmation
+        #[arg(short, long)]
+        yes: bool,
+
+        /// Only show the diff
+        #[arg(long, conflicts_with = "yes")]
+        dry_run: bool,
     },
 
     /// Start the web server for token monitoring
@@ -42,6 +117,18 @@ pub enum Commands {
         port: u16,
     },
 
+    /// Inspect the configuration file
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+
+    /// Add, list, remove, enable or disable notification channels
+    Channel {
+        #[command(subcommand)]
+        command: ChannelCommands,
+    },
+
     /// Configure notification channels
     Configure {
         /// Telegram webhook URL
@@ -56,4 +143,212 @@ pub enum Commands {
         #[arg(long)]
         email: Option<String>,
     },
+
+    /// Send a synthetic alert through every configured channel
+    TestAlert {
+        /// Severity of the test alert
+        #[arg(short, long, default_value = "low")]
+        severity: Severity,
+    },
+
+    /// Inspect alert routing rules
+    Route {
+        #[command(subcommand)]
+        command: RouteCommands,
+    },
+
+    /// Inspect and retry queued alert deliveries
+    Alerts {
+        #[command(subcommand)]
+        command: AlertCommands,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum BackupCommands {
+    /// List catalogued backups, newest first
+    List {
+        /// Only backups taken for this token
+        #[arg(short, long)]
+        token: Option<String>,
+
+        /// Only backups of this file
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+    },
+
+    /// Apply the retention policy to every file's backups now
+    Prune,
+}
+
+#[derive(Subcommand)]
+pub enum AlertCommands {
+    /// List alert deliveries that have not been delivered yet
+    Pending,
+
+    /// Requeue dead-lettered alert deliveries and send them now
+    Retry {
+        /// Alert job ID to retry (defaults to every dead-lettered job)
+        #[arg(short, long)]
+        id: Option<String>,
+    },
+
+    /// Render an alert message without sending it
+    Preview {
+        /// Channel whose template to render (telegram, discord, email, pagerduty, opsgenie)
+        #[arg(short, long, default_value = "telegram")]
+        channel: String,
+
+        /// Severity of the sample token
+        #[arg(short, long, default_value = "high")]
+        severity: Severity,
+
+        /// Template language (defaults to notification.templates.language)
+        #[arg(short, long)]
+        language: Option<String>,
+
+        /// Render with a stored token instead of a sample one
+        #[arg(short, long)]
+        token: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum RouteCommands {
+    /// Show which channels would fire for a token
+    Test {
+        /// Token ID to evaluate
+        id: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ConfigCommands {
+    /// Check the config for errors, including environment overrides and secret references
+    Validate,
+}
+
+#[derive(Subcommand)]
+pub enum ChannelCommands {
+    /// Add a named notification channel
+    Add {
+        /// Unique channel name, used by routing rules and other channel commands
+        name: String,
+
+        #[command(flatten)]
+        spec: ChannelSpec,
+
+        /// Check the endpoint is reachable before saving
+        #[arg(long)]
+        check: bool,
+    },
+
+    /// List configured channels with secrets masked
+    List,
+
+    /// Remove a channel
+    Remove {
+        /// Channel name
+        name: String,
+    },
+
+    /// Enable a disabled channel
+    Enable {
+        /// Channel name
+        name: String,
+    },
+
+    /// Keep a channel in the config but stop sending to it
+    Disable {
+        /// Channel name
+        name: String,
+    },
+}
+
+#[derive(Args)]
+pub struct ChannelSpec {
+    /// Channel kind (telegram, discord, email, syslog, splunk_hec, elasticsearch, pagerduty, opsgenie)
+    pub kind: String,
+
+    /// Webhook or API URL, SMTP server for email, address for syslog
+    #[arg(short, long)]
+    pub url: Option<String>,
+
+    /// HEC token, API key or PagerDuty routing key
+    #[arg(long)]
+    pub secret: Option<String>,
+
+    /// Splunk or Elasticsearch index
+    #[arg(long)]
+    pub index: Option<String>,
+
+    /// Syslog message format (cef, leef)
+    #[arg(long, default_value = "cef")]
+    pub format: String,
+
+    /// Email sender address
+    #[arg(long)]
+    pub from: Option<String>,
+
+    /// Email recipient address
+    #[arg(long)]
+    pub to: Option<String>,
+}
+
+impl ChannelSpec {
+    pub fn into_channel(self) -> Result<NotificationChannel> {
+        let kind = self.kind.to_lowercase();
+        let url = self.url;
+        let missing = |flag: &str| anyhow!("{} channels require --{}", kind, flag);
+
+        let channel = match kind.as_str() {
+            "telegram" => NotificationChannel::Telegram {
+                webhook_url: url.ok_or_else(|| missing("url"))?,
+            },
+            "discord" => NotificationChannel::Discord {
+                webhook_url: url.ok_or_else(|| missing("url"))?,
+            },
+            "email" => NotificationChannel::Email {
+                smtp_server: url.ok_or_else(|| missing("url"))?,
+                from: self.from.ok_or_else(|| missing("from"))?,
+                to: self.to.ok_or_else(|| missing("to"))?,
+            },
+            "syslog" => NotificationChannel::Syslog {
+                address: url.ok_or_else(|| missing("url"))?,
+                format: match self.format.to_lowercase().as_str() {
+                    "cef" => SiemFormat::Cef,
+                    "leef" => SiemFormat::Leef,
+                    other => return Err(anyhow!("Unknown syslog format '{}'", other)),
+                },
+            },
+            "splunk_hec" | "splunk" => NotificationChannel::SplunkHec {
+                url: url.ok_or_else(|| missing("url"))?,
+                token: self.secret.ok_or_else(|| missing("secret"))?,
+                index: self.index,
+                sourcetype: None,
+                batch_size: 50,
+                tls: TlsOptions::default(),
+            },
+            "elasticsearch" => NotificationChannel::Elasticsearch {
+                url: url.ok_or_else(|| missing("url"))?,
+                index: self.index.ok_or_else(|| missing("index"))?,
+                api_key: self.secret,
+                username: None,
+                password: None,
+                batch_size: 50,
+                tls: TlsOptions::default(),
+            },
+            "pagerduty" => NotificationChannel::PagerDuty {
+                routing_key: self.secret.ok_or_else(|| missing("secret"))?,
+                url,
+            },
+            "opsgenie" => NotificationChannel::Opsgenie {
+                api_key: self.secret.ok_or_else(|| missing("secret"))?,
+                url,
+            },
+            other => return Err(anyhow!("Unknown channel kind '{}'", other)),
+        };
+
+        Ok(channel)
+    }
 }
@@ -0,0 +1,190 @@
+use crate::application::config::{AppConfig, ConfigLocation, ConfigReport};
+use crate::application::config_loader;
+use crate::application::service::RedTokenService;
+use crate::core::error::RedTokenError;
+use crate::infrastructure::notification::{self as notify, CompositeNotificationService};
+use crate::interfaces::web::{self, TlsHandle};
+use anyhow::Result;
+use log::{error, info, warn};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio::signal::unix::{signal, SignalKind};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+// Settings that are only read at startup
+const RESTART_REQUIRED: [&str; 5] = [
+    "storage.",
+    "web.port",
+    "web.host",
+    "web.enable_ssl",
+    "notification.retry",
+];
+
+// Everything `config validate` reports, including per-channel checks
+pub fn check_config(path: &Path) -> ConfigReport {
+    let mut report = AppConfig::check(path);
+
+    if report.errors.is_empty() {
+        match AppConfig::load(path) {
+            Ok(config) => {
+                for (index, entry) in config.notification.channels.iter().enumerate() {
+                    if let Err(e) = notify::validate_channel(&entry.channel) {
+                        let message = match e {
+                            RedTokenError::ConfigError(message) => message,
+                            other => other.to_string(),
+                        };
+                        report.errors.push(format!(
+                            "notification.channels[{}] ({}): {}",
+                            index, entry.name, message
+                        ));
+                    }
+                }
+            }
+            Err(e) => report.errors.push(e.to_string()),
+        }
+    }
+
+    report
+}
+
+pub struct ConfigReloader {
+    location: ConfigLocation,
+    service: Arc<RedTokenService>,
+    tls: Option<TlsHandle>,
+    current: Mutex<AppConfig>,
+}
+
+impl ConfigReloader {
+    pub fn new(
+        location: ConfigLocation,
+        config: AppConfig,
+        service: Arc<RedTokenService>,
+        tls: Option<TlsHandle>,
+    ) -> Self {
+        Self {
+            location,
+            service,
+            tls,
+            current: Mutex::new(config),
+        }
+    }
+
+    // Validate and build everything first; nothing is swapped unless all of it succeeds
+    pub fn reload(&self) -> Result<Vec<String>> {
+        let path = &self.location.path;
+        let base_dir = self.location.base_dir();
+
+        let report = check_config(path);
+        if !report.errors.is_empty() {
+            anyhow::bail!("{}", report.errors.join("; "));
+        }
+
+        let config = AppConfig::load(path)?;
+        let notifier = CompositeNotificationService::new(config.notification_config(&base_dir))?;
+        let acceptor = match (&self.tls, config.web.tls_paths(&base_dir)) {
+            (Some(_), Some((cert, key))) => Some(web::load_tls_acceptor(&cert, &key)?),
+            _ => None,
+        };
+
+        let mut current = self.current.lock().expect("config lock poisoned");
+        let changes = config_loader::diff(
+            &serde_json::to_value(&*current)?,
+            &serde_json::to_value(&config)?,
+        );
+
+        self.service
+            .replace_notification_service(Box::new(notifier));
+        if let (Some(tls), Some(acceptor)) = (&self.tls, acceptor) {
+            tls.store(Arc::new(acceptor));
+            info!("TLS certificate reloaded");
+        }
+        *current = config;
+
+        Ok(changes)
+    }
+
+    // Config file plus the TLS certificate and key, so certificate renewals are picked up too
+    fn watched_paths(&self) -> Vec<PathBuf> {
+        let mut paths = vec![self.location.path.clone()];
+        let current = self.current.lock().expect("config lock poisoned");
+        if let Some((cert, key)) = current.web.tls_paths(&self.location.base_dir()) {
+            paths.push(cert);
+            paths.push(key);
+        }
+        paths
+    }
+
+    fn fingerprint(paths: &[PathBuf]) -> Vec<Option<(SystemTime, u64)>> {
+        paths
+            .iter()
+            .map(|path| {
+                let metadata = std::fs::metadata(path).ok()?;
+                Some((metadata.modified().ok()?, metadata.len()))
+            })
+            .collect()
+    }
+
+    fn apply(&self, reason: &str) {
+        info!("Reloading configuration ({})", reason);
+
+        match self.reload() {
+            Ok(changes) if changes.is_empty() => info!("Configuration unchanged"),
+            Ok(changes) => {
+                for change in changes {
+                    let path = &change[2..];
+                    if RESTART_REQUIRED
+                        .iter()
+                        .any(|prefix| path.starts_with(prefix))
+                    {
+                        warn!("Config change {} takes effect after a restart", change);
+                    } else {
+                        info!("Config change {}", change);
+                    }
+                }
+            }
+            Err(e) => error!(
+                "Rejected configuration reload, keeping the current config: {}",
+                e
+            ),
+        }
+    }
+
+    // Reload on SIGHUP or when a watched file changes
+    pub async fn run(self: Arc<Self>) {
+        let mut hangup = match signal(SignalKind::hangup()) {
+            Ok(hangup) => Some(hangup),
+            Err(e) => {
+                warn!("Cannot listen for SIGHUP, relying on file watching: {}", e);
+                None
+            }
+        };
+
+        let mut ticker = tokio::time::interval(POLL_INTERVAL);
+        let mut paths = self.watched_paths();
+        let mut last_seen = Self::fingerprint(&paths);
+
+        loop {
+            let reason = tokio::select! {
+                _ = ticker.tick() => {
+                    let seen = Self::fingerprint(&paths);
+                    if seen == last_seen {
+                        continue;
+                    }
+                    "file changed"
+                }
+                Some(_) = async {
+                    match hangup.as_mut() {
+                        Some(hangup) => hangup.recv().await,
+                        None => std::future::pending().await,
+                    }
+                } => "SIGHUP",
+            };
+
+            self.apply(reason);
+            paths = self.watched_paths();
+            last_seen = Self::fingerprint(&paths);
+        }
+    }
+}
@@ -1,19 +1,25 @@
+use arc_swap::ArcSwap;
 use axum::{
-    extract::{Path, Query, State},
-    http::StatusCode,
-    response::IntoResponse,
-    routing::{get, post},
-    Json, Router,
+    extract::{ConnectInfo, Path, Query, State},
+    http::{header, HeaderMap, StatusCode, Uri},
+    response::{IntoResponse, Response},
+    routing::{any, get, post},
+    Extension, Json, Router,
 };
-use log::{error, info};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hyper_util::{rt::TokioIo, service::TowerToHyperService};
+use log::{error, info, warn};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::net::SocketAddr;
 use std::sync::Arc;
 use tokio::net::TcpListener;
+use tokio_native_tls::TlsAcceptor;
 use uuid::Uuid;
 
 use crate::application::service::RedTokenService;
-use crate::core::token::Honeytoken;
+use crate::core::error::RedTokenError;
+use crate::core::injection::{FileType, InjectionPreview};
+use crate::core::token::{Honeytoken, Severity, TriggerEvent};
 
 // API response types
 #[derive(Debug, Serialize)]
@@ -26,9 +32,40 @@ struct ApiResponse<T> {
 }
 
 // Request types
+#[derive(Debug, Default, Deserialize)]
+struct TestAlertRequest {
+    #[serde(default)]
+    severity: Option<Severity>,
+}
+
 #[derive(Debug, Deserialize)]
 struct TokenQuery {
-    token: String,
+    token: Option<String>,
+}
+
+fn trigger_event(addr: SocketAddr, headers: &HeaderMap) -> TriggerEvent {
+    TriggerEvent {
+        source_ip: Some(addr.ip().to_string()),
+        user_agent: headers
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string),
+    }
+}
+
+// Access key id from an AWS SigV4 header, so a leaked AWS credentials profile can be
+// pointed at this endpoint: `AWS4-HMAC-SHA256 Credential=AKIA.../date/region/...`
+fn aws_access_key_id(headers: &HeaderMap) -> Option<String> {
+    let authorization = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
+    let credential = authorization
+        .strip_prefix("AWS4-HMAC-SHA256")?
+        .split([',', ' '])
+        .find_map(|part| part.trim().strip_prefix("Credential="))?;
+    credential
+        .split('/')
+        .next()
+        .filter(|key| !key.is_empty())
+        .map(str::to_string)
 }
 
 #[derive(Debug, Deserialize)]
@@ -36,6 +73,19 @@ struct CreateTokenRequest {
     file_path: String,
     value: Option<String>,
     file_type: Option<String>,
+    #[serde(default)]
+    severity: Severity,
+    #[serde(default)]
+    tags: Vec<String>,
+    campaign: Option<String>,
+    #[serde(default)]
+    dry_run: bool, // Return the diff instead of injecting
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct DeleteTokenQuery {
+    #[serde(default)]
+    dry_run: bool,
 }
 
 // State to hold the application service
@@ -43,14 +93,53 @@ struct AppState {
     service: Arc<RedTokenService>,
 }
 
+// Current TLS certificate, replaced when the config is reloaded
+pub type TlsHandle = Arc<ArcSwap<TlsAcceptor>>;
+
+// PEM certificate chain plus PKCS#8 private key
+pub fn load_tls_acceptor(
+    cert_path: &std::path::Path,
+    key_path: &std::path::Path,
+) -> anyhow::Result<TlsAcceptor> {
+    let read = |path: &std::path::Path| {
+        std::fs::read(path).map_err(|e| RedTokenError::FileReadError {
+            path: path.to_path_buf(),
+            source: e,
+        })
+    };
+    let identity =
+        native_tls::Identity::from_pkcs8(&read(cert_path)?, &read(key_path)?).map_err(|e| {
+            RedTokenError::ConfigError(format!(
+                "Invalid TLS certificate {:?} or key {:?}: {}",
+                cert_path, key_path, e
+            ))
+        })?;
+    let acceptor = native_tls::TlsAcceptor::new(identity)
+        .map_err(|e| RedTokenError::ConfigError(format!("Failed to create TLS acceptor: {}", e)))?;
+    Ok(TlsAcceptor::from(acceptor))
+}
+
 // Routes
-pub async fn start_server(service: Arc<RedTokenService>, port: u16) -> anyhow::Result<()> {
+pub async fn start_server(
+    service: Arc<RedTokenService>,
+    port: u16,
+    tls: Option<TlsHandle>,
+) -> anyhow::Result<()> {
     let app_state = Arc::new(AppState { service });
 
     let app = Router::new()
         .route("/api/tokens", get(list_tokens).post(create_token))
         .route("/api/tokens/:id", get(get_token).delete(delete_token))
+        .route("/api/tokens/:id/resolve", post(resolve_token))
+        .route("/api/alerts/test", post(test_alert))
         .route("/api/check", get(check_token))
+        .route("/k8s", any(kube_canary))
+        .route("/k8s/*path", any(kube_canary))
+        .route("/v2/", any(registry_canary))
+        .route("/v2/*path", any(registry_canary))
+        .route("/doc/*path", get(document_canary))
+        .route("/npm/*path", any(package_canary))
+        .route("/api/v4/projects/*path", any(package_canary))
         .route("/health", get(health_check))
         .with_state(app_state);
 
@@ -58,9 +147,49 @@ pub async fn start_server(service: Arc<RedTokenService>, port: u16) -> anyhow::R
     info!("Starting server on {}", addr);
 
     let listener = TcpListener::bind(&addr).await?;
-    axum::serve(listener, app).await?;
+    let tls = match tls {
+        Some(tls) => tls,
+        None => {
+            axum::serve(
+                listener,
+                app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    info!("TLS enabled");
+    loop {
+        let (stream, remote) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                warn!("Failed to accept connection: {}", e);
+                continue;
+            }
+        };
+
+        // Each handshake uses whichever certificate is current when the connection arrives
+        let acceptor = tls.load_full();
+        let app = app.clone().layer(Extension(ConnectInfo(remote)));
 
-    Ok(())
+        tokio::spawn(async move {
+            let stream = match acceptor.accept(stream).await {
+                Ok(stream) => stream,
+                Err(e) => {
+                    warn!("TLS handshake with {} failed: {}", remote, e);
+                    return;
+                }
+            };
+
+            if let Err(e) = hyper::server::conn::http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), TowerToHyperService::new(app))
+                .await
+            {
+                warn!("Connection from {} failed: {}", remote, e);
+            }
+        });
+    }
 }
 
 // Handler implementations
@@ -97,15 +226,28 @@ async fn get_token(
     Path(id): Path<String>,
 ) -> impl IntoResponse {
     match Uuid::parse_str(&id) {
-        Ok(uuid) => {
-            // This would need to be implemented in the service
-            let response = ApiResponse::<Honeytoken> {
-                success: false,
-                data: None,
-                error: Some("Not implemented".to_string()),
-            };
-            (StatusCode::NOT_IMPLEMENTED, Json(response))
-        }
+        Ok(uuid) => match state.service.get_token(uuid).await {
+            Ok(token) => {
+                let response = ApiResponse {
+                    success: true,
+                    data: Some(token),
+                    error: None,
+                };
+                (StatusCode::OK, Json(response))
+            }
+            Err(e) => {
+                let status = match e.downcast_ref::<RedTokenError>() {
+                    Some(RedTokenError::TokenNotFound(_)) => StatusCode::NOT_FOUND,
+                    _ => StatusCode::INTERNAL_SERVER_ERROR,
+                };
+                let response = ApiResponse::<Honeytoken> {
+                    success: false,
+                    data: None,
+                    error: Some(e.to_string()),
+                };
+                (status, Json(response))
+            }
+        },
         Err(_) => {
             let response = ApiResponse::<Honeytoken> {
                 success: false,
@@ -121,14 +263,34 @@ async fn get_token(
 async fn create_token(
     State(state): State<Arc<AppState>>,
     Json(request): Json<CreateTokenRequest>,
-) -> impl IntoResponse {
+) -> Response {
+    // Auto-detected from the file name when not given
+    let file_type = request.file_type.as_deref().map(FileType::from_name);
+
+    if request.dry_run {
+        let result = state
+            .service
+            .preview_inject(
+                &request.file_path,
+                file_type,
+                request.value,
+                request.severity,
+                request.tags,
+                request.campaign,
+            )
+            .await;
+        return preview_response(result.map(|(_, preview)| preview));
+    }
+
     let result = state
         .service
         .inject_token(
             &request.file_path,
-            request
-                .value
-                .unwrap_or_else(|| format!("fake_token_{}", Uuid::new_v4())),
+            file_type,
+            request.value,
+            request.severity,
+            request.tags,
+            request.campaign,
         )
         .await;
 
@@ -139,7 +301,7 @@ async fn create_token(
                 data: Some(token),
                 error: None,
             };
-            (StatusCode::CREATED, Json(response))
+            (StatusCode::CREATED, Json(response)).into_response()
         }
         Err(e) => {
             let response = ApiResponse::<Honeytoken> {
@@ -147,7 +309,32 @@ async fn create_token(
                 data: None,
                 error: Some(e.to_string()),
             };
-            (StatusCode::INTERNAL_SERVER_ERROR, Json(response))
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(response)).into_response()
+        }
+    }
+}
+
+fn preview_response(result: anyhow::Result<InjectionPreview>) -> Response {
+    match result {
+        Ok(preview) => {
+            let response = ApiResponse {
+                success: true,
+                data: Some(preview),
+                error: None,
+            };
+            (StatusCode::OK, Json(response)).into_response()
+        }
+        Err(e) => {
+            let status = match e.downcast_ref::<RedTokenError>() {
+                Some(RedTokenError::TokenNotFound(_)) => StatusCode::NOT_FOUND,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            let response = ApiResponse::<InjectionPreview> {
+                success: false,
+                data: None,
+                error: Some(e.to_string()),
+            };
+            (status, Json(response)).into_response()
         }
     }
 }
@@ -156,8 +343,10 @@ async fn create_token(
 async fn delete_token(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
-) -> impl IntoResponse {
+    Query(query): Query<DeleteTokenQuery>,
+) -> Response {
     match Uuid::parse_str(&id) {
+        Ok(uuid) if query.dry_run => preview_response(state.service.preview_remove(uuid).await),
         Ok(uuid) => match state.service.remove_token(uuid).await {
             Ok(_) => {
                 let response = ApiResponse::<()> {
@@ -165,7 +354,7 @@ async fn delete_token(
                     data: None,
                     error: None,
                 };
-                (StatusCode::OK, Json(response))
+                (StatusCode::OK, Json(response)).into_response()
             }
             Err(e) => {
                 let response = ApiResponse::<()> {
@@ -173,7 +362,7 @@ async fn delete_token(
                     data: None,
                     error: Some(e.to_string()),
                 };
-                (StatusCode::INTERNAL_SERVER_ERROR, Json(response))
+                (StatusCode::INTERNAL_SERVER_ERROR, Json(response)).into_response()
             }
         },
         Err(_) => {
@@ -182,19 +371,91 @@ async fn delete_token(
                 data: None,
                 error: Some("Invalid UUID format".to_string()),
             };
+            (StatusCode::BAD_REQUEST, Json(response)).into_response()
+        }
+    }
+}
+
+#[axum::debug_handler]
+async fn resolve_token(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match Uuid::parse_str(&id) {
+        Ok(uuid) => match state.service.resolve_token(uuid).await {
+            Ok(queued) => {
+                let response = ApiResponse {
+                    success: true,
+                    data: Some(queued),
+                    error: None,
+                };
+                (StatusCode::OK, Json(response))
+            }
+            Err(e) => {
+                let status = match e.downcast_ref::<RedTokenError>() {
+                    Some(RedTokenError::TokenNotFound(_)) => StatusCode::NOT_FOUND,
+                    _ => StatusCode::INTERNAL_SERVER_ERROR,
+                };
+                let response = ApiResponse::<usize> {
+                    success: false,
+                    data: None,
+                    error: Some(e.to_string()),
+                };
+                (status, Json(response))
+            }
+        },
+        Err(_) => {
+            let response = ApiResponse::<usize> {
+                success: false,
+                data: None,
+                error: Some("Invalid UUID format".to_string()),
+            };
             (StatusCode::BAD_REQUEST, Json(response))
         }
     }
 }
 
+#[axum::debug_handler]
+async fn test_alert(
+    State(state): State<Arc<AppState>>,
+    request: Option<Json<TestAlertRequest>>,
+) -> impl IntoResponse {
+    let request = request.map(|Json(r)| r).unwrap_or_default();
+    let results = state
+        .service
+        .test_alert(request.severity.unwrap_or(Severity::Low))
+        .await;
+
+    let failed = results.iter().filter(|r| !r.success).count();
+    let response = ApiResponse {
+        success: failed == 0,
+        data: Some(results),
+        error: (failed > 0).then(|| format!("{} channel(s) failed", failed)),
+    };
+    (StatusCode::OK, Json(response))
+}
+
 #[axum::debug_handler]
 async fn check_token(
     State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     Query(params): Query<TokenQuery>,
 ) -> impl IntoResponse {
     info!("Token check request received");
 
-    match state.service.check_token(&params.token).await {
+    let event = trigger_event(addr, &headers);
+
+    let Some(token) = params.token.or_else(|| aws_access_key_id(&headers)) else {
+        let response = ApiResponse::<()> {
+            success: true,
+            data: None,
+            error: None,
+        };
+        return (StatusCode::OK, Json(response));
+    };
+
+    match state.service.check_token(&token, event).await {
         Ok(_) => {
             // Always return OK to not reveal if token was valid
             let response = ApiResponse::<()> {
@@ -216,3 +477,195 @@ async fn check_token(
         }
     }
 }
+
+// API server for decoy kubeconfigs. The token is the first path segment of the
+// server URL; a bearer token also counts when the server is reached over HTTPS.
+async fn kube_canary(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    uri: Uri,
+) -> impl IntoResponse {
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.trim().to_string());
+    // `/k8s/clusters/<token id>/...`; older versions put the value first
+    let mut segments = uri
+        .path()
+        .strip_prefix("/k8s/")
+        .unwrap_or_default()
+        .split('/');
+    let (id, value) = match segments.next() {
+        Some("clusters") => (
+            segments.next().and_then(|id| Uuid::parse_str(id).ok()),
+            None,
+        ),
+        Some(segment) if !segment.is_empty() => (None, Some(segment.to_string())),
+        _ => (None, None),
+    };
+
+    info!("Kubernetes canary request for {}", uri.path());
+    if let Some(id) = id {
+        if let Err(e) = state
+            .service
+            .check_token_id(id, trigger_event(addr, &headers))
+            .await
+        {
+            error!("Error checking token: {}", e);
+        }
+    }
+    for token in bearer.into_iter().chain(value) {
+        if let Err(e) = state
+            .service
+            .check_token(&token, trigger_event(addr, &headers))
+            .await
+        {
+            error!("Error checking token: {}", e);
+        }
+    }
+
+    // What a real API server answers to bad credentials
+    (
+        StatusCode::UNAUTHORIZED,
+        Json(serde_json::json!({
+            "kind": "Status",
+            "apiVersion": "v1",
+            "metadata": {},
+            "status": "Failure",
+            "message": "Unauthorized",
+            "reason": "Unauthorized",
+            "code": 401
+        })),
+    )
+}
+
+// Remote template and linked image of decoy Office documents, fetched when the
+// document is opened. The token's id is the first path segment; documents from
+// older versions carry the value there instead.
+async fn document_canary(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Path(path): Path<String>,
+) -> Response {
+    info!("Document canary request for /doc/{}", path);
+    let token = path.split('/').next().unwrap_or_default();
+    if !token.is_empty() {
+        let event = trigger_event(addr, &headers);
+        let checked = match Uuid::parse_str(token) {
+            Ok(id) => state.service.check_token_id(id, event).await,
+            Err(_) => state.service.check_token(token, event).await,
+        };
+        if let Err(e) = checked {
+            error!("Error checking token: {}", e);
+        }
+    }
+
+    // A transparent pixel keeps the picture blank; a missing template is what
+    // Word expects from a template server that is gone
+    if path.ends_with(".png") {
+        return (
+            StatusCode::OK,
+            [(header::CONTENT_TYPE, "image/png")],
+            PIXEL.to_vec(),
+        )
+            .into_response();
+    }
+    StatusCode::NOT_FOUND.into_response()
+}
+
+// 1x1 transparent PNG
+const PIXEL: [u8; 68] = [
+    0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A, 0x00, 0x00, 0x00, 0x0D, 0x49, 0x48, 0x44, 0x52,
+    0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x06, 0x00, 0x00, 0x00, 0x1F, 0x15, 0xC4,
+    0x89, 0x00, 0x00, 0x00, 0x0B, 0x49, 0x44, 0x41, 0x54, 0x78, 0x9C, 0x63, 0x60, 0x00, 0x02, 0x00,
+    0x00, 0x05, 0x00, 0x01, 0x7A, 0x5E, 0xAB, 0x3F, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4E, 0x44,
+    0xAE, 0x42, 0x60, 0x82,
+];
+
+// Password of `Authorization: Basic` credentials
+fn basic_password(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Basic "))
+        .and_then(|encoded| STANDARD.decode(encoded.trim()).ok())
+        .and_then(|decoded| String::from_utf8(decoded).ok())
+        .and_then(|credentials| {
+            credentials
+                .split_once(':')
+                .map(|(_, password)| password.to_string())
+        })
+}
+
+// npm registry and GitLab PyPI index for decoy .npmrc and pip.conf entries. npm
+// sends its token as a bearer token, pip as the basic auth password.
+async fn package_canary(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    uri: Uri,
+) -> impl IntoResponse {
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.trim().to_string());
+
+    info!("Package registry canary request for {}", uri.path());
+    for token in bearer.into_iter().chain(basic_password(&headers)) {
+        if let Err(e) = state
+            .service
+            .check_token(&token, trigger_event(addr, &headers))
+            .await
+        {
+            error!("Error checking token: {}", e);
+        }
+    }
+
+    (
+        StatusCode::UNAUTHORIZED,
+        [(header::WWW_AUTHENTICATE, "Basic realm=\"Package Registry\"")],
+        Json(serde_json::json!({ "message": "401 Unauthorized" })),
+    )
+}
+
+// Container registry for decoy docker credentials. Clients first call /v2/ without
+// credentials, then retry with the stored `user:token` as basic auth.
+async fn registry_canary(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    uri: Uri,
+) -> impl IntoResponse {
+    info!("Registry canary request for {}", uri.path());
+    if let Some(password) = basic_password(&headers) {
+        if let Err(e) = state
+            .service
+            .check_token(&password, trigger_event(addr, &headers))
+            .await
+        {
+            error!("Error checking token: {}", e);
+        }
+    }
+
+    (
+        StatusCode::UNAUTHORIZED,
+        [
+            (header::WWW_AUTHENTICATE, "Basic realm=\"Registry Realm\""),
+            (
+                header::HeaderName::from_static("docker-distribution-api-version"),
+                "registry/2.0",
+            ),
+        ],
+        Json(serde_json::json!({
+            "errors": [{
+                "code": "UNAUTHORIZED",
+                "message": "authentication required",
+                "detail": null
+            }]
+        })),
+    )
+}